@@ -1,36 +1,107 @@
-use dirs::data_local_dir;
-
-use rust_dice::dice::{Die, DieResultType};
+use rust_dice::bag::{Bag, BagData};
+use rust_dice::commitment::SeedCommitment;
+use rust_dice::deck::{DISCARD_PILE, DRAW_PILE, Deck, DeckData};
+use rust_dice::dice::{Die, DieMode, DieResultType, StepDirection, StepLadder};
 use rust_dice::dice_allocator::DiceAllocator;
-use rust_dice::dice_profile::{DieProfile, DieProfileType};
-use rust_dice::tray::Tray;
+use rust_dice::dice_profile::{DieProfile, DieProfileSet, DieProfileType, load_profiles_from_dir};
+use rust_dice::tables::{
+    DiceResultTable, TableDeckEvent, TableRoll, TableSet, load_tables_from_dir, load_tables_from_file,
+};
+use rust_dice::patterns::FaceAnalysis;
+use rust_dice::statistics::DieStats;
+use rust_dice::tray::{Tray, TrayResultType};
+use rust_dice::yahtzee::{ROLLS_PER_TURN, YAHTZEE_DICE, ScoreCategory, YahtzeeGame};
 
 use indexmap::IndexMap;
+use rand::Rng;
 
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::create_dir_all;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 use crate::cli_dice_allocator::CliDiceAllocator;
 use crate::cli_dice_tray::{CliTrayData};
-use crate::cli_parser::DiceTargets;
-use crate::logger::detailed_log_tray;
+use crate::cli_macros::{MacroStore, RollMacro, macros_path};
+use crate::cli_parser::{DiceTargets, parse_dice_notation};
+use crate::logger::{
+    detailed_log_tray, log_backups, log_bag, log_commitments, log_deck, log_die_stats, log_patterns, log_result_table,
+    log_reveals, log_roll_history, log_scorecards, log_stats_histogram, log_table_roll, log_verification,
+    log_yahtzee_suggestions,
+};
+use crate::roll_log::{
+    RollLogEntry, RollLogFilter, append_entries, format_timestamp, last_state_hash, read_entries, roll_log_path,
+    sha256_hex, verify_chain,
+};
+use crate::save_data::{CliSaveData, SAVE_VERSION, TrayExport};
+use crate::save_file::{conflict_path, list_backups, restore_backup, rotate_backups, write_atomic};
+use crate::share_code::{decode_tray, encode_tray};
+use crate::workspace::{
+    active_workspace, data_dir, delete_workspace, list_workspaces, new_workspace, switch_workspace,
+};
 
 pub struct CliDiceTrayApp {
     dice_allocator: CliDiceAllocator,
     dice_trays: IndexMap<String, Box<dyn Tray>>,
+    decks: IndexMap<String, Deck>,
+    bags: IndexMap<String, Bag>,
+    yahtzee: Option<YahtzeeGame>,
+    ///Set while a yahtzee command runs, so the game can change the dice in its locked tray.
+    yahtzee_unlocked: bool,
+    save_locked: bool,
+    save_file: PathBuf,
+    ///SHA-256 of the save file as this run first loaded it, recorded in the roll log's hash chain.
+    loaded_hash: Option<String>,
+    ///Rolls made this run, appended to the roll log on close.
+    pending_rolls: Vec<RollLogEntry>,
+    ///The save file as it was when loaded, None if it didn't exist. Used to spot changes made by something else.
+    loaded_content: Option<String>,
+}
+
+///What to do when an imported tray has the same ID as an existing one.
+#[derive(Clone, Copy)]
+pub enum ImportConflict {
+    ///Refuse the import.
+    Fail,
+    ///Replace the existing tray.
+    Replace,
+    ///Import under the ID with a number added, i.e. "Main-2".
+    Rename,
 }
 
+///ID of the tray Yahtzee games are played in.
+pub const YAHTZEE_TRAY: &str = "yahtzee";
+const NO_YAHTZEE_GAME: &str = "No game of Yahtzee in progress. Start one with: dicetray yahtzee new <players>";
+
 impl CliDiceTrayApp {
-    pub fn new() -> Self {
+    ///Creates the app, which loads from and saves to the provided save file.
+    pub fn new(save_file: PathBuf) -> Self {
         CliDiceTrayApp {
             dice_allocator: CliDiceAllocator::new(),
             dice_trays: IndexMap::new(),
+            decks: IndexMap::new(),
+            bags: IndexMap::new(),
+            yahtzee: None,
+            yahtzee_unlocked: false,
+            save_locked: false,
+            save_file,
+            loaded_content: None,
+            loaded_hash: None,
+            pending_rolls: Vec::new(),
         }
     }
 
     pub fn init(&mut self) {
         println!("Welcome to dice_tray_cli.");
-        match self.load_trays_from_file() {
+        self.load_state();
+        self.loaded_hash = self.loaded_content.as_ref().map(|content| sha256_hex(content.as_bytes()));
+    }
+
+    ///Loads the trays, decks, bags and games from the save file. If the save file exists but can't be loaded,
+    ///saving is turned off for this run so the file is never overwritten.
+    fn load_state(&mut self) {
+        match self.load_save_file() {
             Ok(trays) => {
                 let mut tray_duplicate = false;
                 for tray in trays.into_iter() {
@@ -50,6 +121,8 @@ impl CliDiceTrayApp {
             }
             Err(e) => {
                 println!("Error loading trays from file: {}", e);
+                println!("Changes won't be saved until the save file is fixed. Run \"dicetray restore\" to restore a backup.");
+                self.save_locked = true;
             }
         }
 
@@ -61,8 +134,34 @@ impl CliDiceTrayApp {
     }
 
     pub fn close(&mut self) {
-        if let Err(e) = self.save_trays_to_file() {
-            println!("Error saving trays to file: {}", e);
+        let loaded_hash = self.loaded_hash.clone();
+        let saved = if self.save_locked {
+            println!("The save file couldn't be loaded, so changes from this run were not saved to avoid overwriting it.");
+            false
+        } else {
+            match self.save_to_file() {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("Error saving trays to file: {}", e);
+                    false
+                }
+            }
+        };
+
+        //Rolls happened whether or not the save could be written, so they're always logged.
+        let roll_log = roll_log_path(&self.save_file);
+        let mut entries = std::mem::take(&mut self.pending_rolls);
+        if saved && let Some(content) = &self.loaded_content {
+            let state_hash = sha256_hex(content.as_bytes());
+            match last_state_hash(&roll_log) {
+                //Only runs that changed the save, or found it changed, need to record it.
+                Ok(last) if last.as_ref() == Some(&state_hash) && last == loaded_hash => {}
+                Ok(_) => entries.push(RollLogEntry::state(loaded_hash, state_hash)),
+                Err(e) => println!("Error reading the roll log {}: {}", roll_log.display(), e),
+            }
+        }
+        if let Err(e) = append_entries(&roll_log, &entries) {
+            println!("Error writing to the roll log {}: {}", roll_log.display(), e);
         }
     }
 
@@ -87,6 +186,77 @@ impl CliDiceTrayApp {
         }
     }
 
+    ///Adds a flat modifier to the tray's existing modifier. Fails if the new modifier would overflow.
+    pub fn add_modifier(&mut self, tray_id: Option<&str>, modifier: i64) -> Result<(), String> {
+        let tray = self.get_tray_mut(tray_id)?;
+        let new_modifier = tray
+            .get_modifier()
+            .checked_add(modifier)
+            .ok_or("Tray modifier is too large.".to_string())?;
+        tray.set_modifier(new_modifier);
+        Ok(())
+    }
+
+    ///Adds count dice built from the named profile in the profiles directory. A provided result type overrides the profile's result type.
+    pub fn add_dice_from_profile(
+        &mut self,
+        tray_id: Option<&str>,
+        profile_name: &str,
+        count: u32,
+        result_type: Option<DieResultType>,
+    ) -> Result<(), String> {
+        let profiles = self.load_profiles()?;
+        let mut profile = match profiles.get(profile_name) {
+            Some(profile) => profile.clone(),
+            None => {
+                return Err(format!(
+                    "No profile named {} found in {}.",
+                    profile_name,
+                    profiles_dir().map_err(|e| e.to_string())?.display()
+                ));
+            }
+        };
+        if result_type.is_some() {
+            profile.result_type = result_type;
+        }
+
+        let mut new_dice: Vec<Box<dyn Die>> = Vec::new();
+        for _i in 0..count {
+            new_dice.push(self.dice_allocator.new_die(&profile)?);
+        }
+        self.get_tray_mut(tray_id)?.add_dice(new_dice);
+        Ok(())
+    }
+
+    ///Prints the name and definition of every profile in the profiles directory.
+    pub fn show_profiles(&self) {
+        match self.load_profiles() {
+            Ok(profiles) => {
+                if profiles.is_empty() {
+                    println!("No profiles found. Add .json or .toml profile files to the profiles directory.");
+                }
+                for (name, profile) in profiles.iter() {
+                    let die_type = match profile.die_type {
+                        DieProfileType::Numerical(faces) => format!("d{}", faces),
+                        DieProfileType::Custom => "Custom".to_string(),
+                    };
+                    let result_type = match profile.result_type {
+                        Some(result_type) => result_type.to_string(),
+                        None => "Face".to_string(),
+                    };
+                    println!(
+                        "{}: {} labeled '{}' with result type {}",
+                        name,
+                        die_type,
+                        profile.label.as_deref().unwrap_or(&die_type),
+                        result_type
+                    );
+                }
+            }
+            Err(e) => println!("Failed to load profiles with error {}", e),
+        }
+    }
+
     pub fn show_tray(&self, tray_id: Option<&str>) {
         match tray_id {
             Some(id) => {
@@ -106,6 +276,22 @@ impl CliDiceTrayApp {
         }
     }
 
+    ///Sets how the tray combines its dice into a result, i.e. a sum or the best pattern in the faces.
+    pub fn set_tray_result_type(&mut self, tray_id: Option<&str>, result_type: TrayResultType) -> Result<(), String> {
+        let tray = self.get_tray_mut(tray_id)?;
+        tray.set_result_type(result_type);
+        println!("Tray {} now reports: {}", tray.get_id(), result_type);
+        Ok(())
+    }
+
+    ///Shows every pattern found in the faces of the tray's dice, along with its One-Roll Engine sets.
+    pub fn show_patterns(&self, tray_id: Option<&str>) -> Result<(), String> {
+        let tray = self.get_tray(tray_id)?;
+        println!("Patterns in tray: {}", tray.get_id());
+        log_patterns(&FaceAnalysis::from_dice(tray.get_dice()));
+        Ok(())
+    }
+
     pub fn summarize_trays(&self){
         for tray in self.dice_trays.iter(){
             println!("Summarizing tray {}", tray.1.get_id());
@@ -124,6 +310,8 @@ impl CliDiceTrayApp {
             Some(tray_str) => {
                 if tray_str == "Main" {
                     println!("The Main tray cannot be deleted.")
+                } else if let Err(e) = self.check_yahtzee_lock(tray_str) {
+                    println!("{}", e);
                 } else {
                     if let Some(_removed_tray) = self.dice_trays.shift_remove(tray_str) {
                         println!("Tray '{}' has been deleted.", tray_str);
@@ -140,7 +328,19 @@ impl CliDiceTrayApp {
 
     pub fn roll_all(&mut self, tray_id: Option<&str>, result_type: Option<DieResultType>) {
         match self.get_tray_mut(tray_id) {
-            Ok(active_tray) => active_tray.roll_all(result_type),
+            Ok(active_tray) => {
+                let rolled: Vec<usize> = active_tray
+                    .get_dice()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, die)| die.can_roll())
+                    .map(|(i, _)| i)
+                    .collect();
+                active_tray.roll_all(result_type);
+                self.log_rolls(tray_id, &rolled);
+                self.report_usage_dice(tray_id, &rolled);
+                self.roll_bound_tables(tray_id, &rolled);
+            }
             Err(e) => println!("Roll all failed with error {}", e),
         }
     }
@@ -151,26 +351,459 @@ impl CliDiceTrayApp {
         targets: Vec<DiceTargets>,
         result_type: Option<DieResultType>,
     ) -> Result<(), String> {
+        let mut rolled: Vec<usize> = Vec::new();
         if let Ok(active_tray) = self.get_tray_mut(tray_id) {
             targets.iter().for_each(|target| match target {
                 DiceTargets::Index(indecies) => {
                     for i in indecies.iter() {
-                        let _ = active_tray.roll_at(*i, result_type);
+                        match active_tray.roll_at(*i, result_type) {
+                            Ok(()) => rolled.push(*i),
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                }
+                DiceTargets::Label(label) => {
+                    let matching: Vec<usize> = active_tray
+                        .get_dice()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, die)| die.get_label() == label && die.can_roll())
+                        .map(|(i, _)| i)
+                        .collect();
+                    match active_tray.roll_by_label(label, result_type) {
+                        Ok(()) => rolled.extend(matching),
+                        Err(e) => println!("{}", e),
                     }
                 }
-                DiceTargets::Label(label) => match active_tray.roll_by_label(label, result_type) {
-                    Ok(_) => {}
-                    Err(_) => {}
-                },
             });
         }
+        self.log_rolls(tray_id, &rolled);
+        self.report_usage_dice(tray_id, &rolled);
+        self.roll_bound_tables(tray_id, &rolled);
+        Ok(())
+    }
+
+    ///Queues the dice at indices for the roll log, as they are straight after being rolled or set.
+    fn log_rolls(&mut self, tray_id: Option<&str>, indices: &[usize]) {
+        if indices.is_empty() {
+            return;
+        }
+        if let Ok(tray) = self.get_tray(tray_id) {
+            let entry = RollLogEntry::new(tray, indices);
+            self.pending_rolls.push(entry);
+        }
+    }
+
+    ///Checks the roll log's hash chain and reports the first broken link, then checks the save file against the last state the log recorded.
+    ///Returns an error if the chain is broken, so a tampered log fails the command.
+    pub fn verify_log(&self) -> Result<(), String> {
+        let report = verify_chain(&roll_log_path(&self.save_file), &self.save_file)?;
+        if report.unchained > 0 {
+            println!(
+                "{} entries were written before the roll log was chained and can't be checked.",
+                report.unchained
+            );
+        }
+        if let Some(broken) = report.broken {
+            let time = broken.timestamp.map(|time| format!(" ({})", format_timestamp(time))).unwrap_or_default();
+            return Err(format!(
+                "Roll log BROKEN at line {}{}: {} The {} entries before it are intact.",
+                broken.line, time, broken.reason, report.entries
+            ));
+        }
+        println!("Roll log intact: all {} chained entries verified.", report.entries);
+        match report.save_changed {
+            Some(save_changed) => println!("{}", save_changed),
+            None => println!("The save file matches the last state recorded in the roll log."),
+        }
+        Ok(())
+    }
+
+    ///Shows the roll log, or prints it as JSON.
+    pub fn show_roll_log(&self, filter: &RollLogFilter, json: bool) -> Result<(), String> {
+        let (entries, skipped) = read_entries(&roll_log_path(&self.save_file), filter)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?);
+        } else {
+            log_roll_history(&entries);
+        }
+        if skipped > 0 {
+            println!("Skipped {} roll log lines that couldn't be read.", skipped);
+        }
+        Ok(())
+    }
+
+    ///Reports the usage dice that stepped down or ran out on the last roll.
+    fn report_usage_dice(&self, tray_id: Option<&str>, rolled: &[usize]) {
+        let Ok(tray) = self.get_tray(tray_id) else {
+            return;
+        };
+        for i in rolled.iter() {
+            let Some(die) = tray.get_dice().get(*i) else {
+                continue;
+            };
+            if let DieMode::Usage { threshold, .. } = die.get_mode() {
+                if die.is_exhausted() {
+                    println!("@{} {} rolled {} and is exhausted!", i, die.get_label(), die.get_current_face());
+                } else if die.get_current_face() <= *threshold as i32 {
+                    println!("@{} {} rolled {} and steps down to a d{}.", i, die.get_label(), die.get_current_face(), die.get_face_count());
+                }
+            }
+        }
+    }
+
+    ///Dice labeled with the name of a table are bound to that table. Looks up the faces of the rolled bound dice and logs the results.
+    fn roll_bound_tables(&self, tray_id: Option<&str>, rolled: &[usize]) {
+        let Ok(tray) = self.get_tray(tray_id) else {
+            return;
+        };
+        let Ok(mut tables) = load_tables() else {
+            return;
+        };
+        if tables.is_empty() {
+            return;
+        }
+
+        let mut rng = rand::rng();
+        let mut table_rolls: Vec<TableRoll> = Vec::new();
+        for i in rolled.iter() {
+            let Some(die) = tray.get_dice().get(*i) else {
+                continue;
+            };
+            if tables.get(die.get_label()).is_some() {
+                let face = die.get_current_face().max(0) as u32;
+                print!("@{} ", i);
+                match tables.roll_on(die.get_label(), Some(face), &mut rng) {
+                    Ok(table_roll) => {
+                        log_table_roll(&table_roll);
+                        table_rolls.push(table_roll);
+                    }
+                    Err(e) => println!("Failed to look up table {} with error {}", die.get_label(), e),
+                }
+            }
+        }
+        if let Err(e) = save_drawn_tables(&tables, &table_rolls) {
+            println!("{}", e);
+        }
+    }
+
+    ///Holds or releases the dice at the targets. Held dice are skipped whenever the tray is rolled.
+    pub fn hold_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Vec<DiceTargets>,
+        held: bool,
+    ) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+
+        for target in targets.iter() {
+            match target {
+                DiceTargets::Index(indices) => {
+                    for i in indices.iter() {
+                        active_tray.set_held_at(*i, held)?;
+                    }
+                }
+                DiceTargets::Label(label) => {
+                    active_tray.set_held_by_label(label, held)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Steps the dice at the targets up or down the ladder, i.e. d6 -> d8. Dice at the end of the ladder stay the same size.
+    pub fn step_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Vec<DiceTargets>,
+        direction: StepDirection,
+        ladder: &StepLadder,
+    ) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+
+        for target in targets.iter() {
+            match target {
+                DiceTargets::Index(indices) => {
+                    for i in indices.iter() {
+                        let faces = active_tray.step_at(*i, direction, ladder)?;
+                        println!("@{} is now a d{}.", i, faces);
+                    }
+                }
+                DiceTargets::Label(label) => {
+                    active_tray.step_by_label(label, direction, ladder)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Sets the mode of the dice at the targets, i.e. turning them into usage dice.
+    pub fn set_mode_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Vec<DiceTargets>,
+        mode: DieMode,
+    ) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+
+        for target in targets.iter() {
+            match target {
+                DiceTargets::Index(indices) => {
+                    for i in indices.iter() {
+                        active_tray.set_mode_at(*i, mode.clone())?;
+                    }
+                }
+                DiceTargets::Label(label) => {
+                    active_tray.set_mode_by_label(label, mode.clone())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Sets the dice at the targets to a face by hand. The face counts as a roll, so bound tables are looked up and usage dice step down.
+    pub fn set_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Vec<DiceTargets>,
+        face: u32,
+    ) -> Result<(), String> {
+        let mut set: Vec<usize> = Vec::new();
+        {
+            let active_tray = self.get_tray_mut(tray_id)?;
+            //Check every target before setting any, so a bad target leaves the tray unchanged.
+            for target in targets.iter() {
+                if let DiceTargets::Index(indices) = target {
+                    for i in indices.iter() {
+                        active_tray
+                            .get_dice()
+                            .get(*i)
+                            .ok_or(format!("No dice found at provided index: {}.", i))?
+                            .check_face(face)?;
+                    }
+                }
+                if let DiceTargets::Label(label) = target {
+                    for die in active_tray.get_dice().iter().filter(|die| die.get_label() == label) {
+                        die.check_face(face)?;
+                    }
+                }
+            }
+            for target in targets.iter() {
+                match target {
+                    DiceTargets::Index(indices) => {
+                        for i in indices.iter() {
+                            active_tray.record_face_at(*i, face)?;
+                            set.push(*i);
+                        }
+                    }
+                    DiceTargets::Label(label) => {
+                        active_tray.record_face_by_label(label, face)?;
+                        active_tray.get_dice().iter().enumerate().for_each(|(i, die)| {
+                            if die.get_label() == label {
+                                set.push(i);
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        self.log_rolls(tray_id, &set);
+        self.report_usage_dice(tray_id, &set);
+        self.roll_bound_tables(tray_id, &set);
+        Ok(())
+    }
+
+    ///Commits the dice at the targets, or every die in the tray, to new RNG seeds and shows the commitment hashes to publish before rolling.
+    pub fn commit_at_targets(&mut self, tray_id: Option<&str>, targets: Option<Vec<DiceTargets>>) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+        let mut committed: Vec<(usize, String, String)> = Vec::new();
+        for i in target_indices(active_tray, targets) {
+            match active_tray.commit_seed_at(i) {
+                Ok(hash) => committed.push((i, active_tray.get_dice()[i].get_label().to_string(), hash)),
+                Err(e) => println!("{}", e),
+            }
+        }
+        if committed.is_empty() {
+            return Err("No dice were committed.".to_string());
+        }
+        log_commitments(&committed);
+        println!("Publish these hashes before rolling. Reveal the seeds once the rolls are done with: dicetray reveal");
+        Ok(())
+    }
+
+    ///Reveals the seed commitments of the dice at the targets, or of every committed die in the tray.
+    ///The reveal is printed as JSON, and written to out if provided, so anyone can check it with the verify command.
+    pub fn reveal_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Option<Vec<DiceTargets>>,
+        out: Option<&str>,
+    ) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+        let reveal_all = targets.is_none();
+        let mut revealed: Vec<SeedCommitment> = Vec::new();
+        for i in target_indices(active_tray, targets) {
+            if reveal_all && active_tray.get_dice()[i].get_commitment().is_none() {
+                continue;
+            }
+            match active_tray.reveal_seed_at(i) {
+                Ok(commitment) => revealed.push(commitment),
+                Err(e) => println!("{}", e),
+            }
+        }
+        if revealed.is_empty() {
+            return Err("No committed dice to reveal. Commit dice before rolling with: dicetray commit".to_string());
+        }
+
+        log_reveals(&revealed);
+        let json_content = serde_json::to_string_pretty(&revealed).map_err(|e| e.to_string())?;
+        match out {
+            Some(out) => {
+                std::fs::write(out, &json_content).map_err(|e| format!("Failed to write {}: {}", out, e))?;
+                println!("Reveal written to {}. Anyone can check it with: dicetray verify {}", out, out);
+            }
+            None => {
+                println!("{}", json_content);
+                println!("Save the JSON above to a file and check it with: dicetray verify <file>");
+            }
+        }
+        Ok(())
+    }
+
+    ///Shows statistics of the rolls in the history of the dice at the targets, or every die in the tray.
+    ///Each die gets a row per size it has rolled at, then the dice are merged by size for the tray's totals and histograms.
+    pub fn show_stats(&self, tray_id: Option<&str>, targets: Option<Vec<DiceTargets>>) -> Result<(), String> {
+        let active_tray = self.get_tray(tray_id)?;
+        let mut die_stats: Vec<(String, DieStats)> = Vec::new();
+        let mut size_stats: BTreeMap<u32, DieStats> = BTreeMap::new();
+        for i in target_indices(active_tray, targets) {
+            let Some(die) = active_tray.get_dice().get(i) else {
+                println!("No die found at index {}.", i);
+                continue;
+            };
+            let mut sizes: Vec<u32> = die.get_history().iter().map(|(faces, _)| *faces).collect();
+            sizes.sort();
+            sizes.dedup();
+            for faces in sizes {
+                let stats = DieStats::new(die.get_history(), faces);
+                size_stats.entry(faces).or_insert_with(|| DieStats::empty(faces)).merge(&stats)?;
+                die_stats.push((format!("@{}:{}", i, die.get_label()), stats));
+            }
+        }
+        if die_stats.is_empty() {
+            return Err("No rolls recorded for these dice yet. Statistics are kept from rolls made from now on.".to_string());
+        }
+
+        log_die_stats(&die_stats);
+        if die_stats.len() > 1 {
+            let totals: Vec<(String, DieStats)> =
+                size_stats.values().map(|stats| (format!("All d{}", stats.get_faces()), stats.clone())).collect();
+            log_die_stats(&totals);
+        }
+        for stats in size_stats.values() {
+            log_stats_histogram(stats);
+        }
+        Ok(())
+    }
+
+    ///Checks a reveal written by reveal_at_targets: each seed must match its commitment hash and replay the recorded rolls.
+    pub fn verify_reveal(&self, file: &str) -> Result<(), String> {
+        let file_content =
+            std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        let reveals: Vec<SeedCommitment> = match serde_json::from_str::<Vec<SeedCommitment>>(&file_content) {
+            Ok(reveals) => reveals,
+            Err(_) => vec![
+                serde_json::from_str::<SeedCommitment>(&file_content)
+                    .map_err(|e| format!("{} isn't a reveal written by dicetray reveal: {}", file, e))?,
+            ],
+        };
+
+        let results: Vec<(&SeedCommitment, Result<usize, String>)> =
+            reveals.iter().map(|reveal| (reveal, reveal.verify())).collect();
+        log_verification(&results);
+        if results.iter().all(|(_, result)| result.is_ok()) {
+            println!("All {} dice verified: every roll matches the committed seed.", results.len());
+        } else {
+            println!("Verification FAILED: some rolls don't match their commitments.");
+        }
+        Ok(())
+    }
+
+    ///Turns the dice at the targets by amount faces, i.e. -3 on a spindown life counter.
+    ///Counter dice that move onto their lowest or highest face, or wrap around past it, are announced.
+    pub fn count_at_targets(
+        &mut self,
+        tray_id: Option<&str>,
+        targets: Vec<DiceTargets>,
+        amount: i64,
+    ) -> Result<(), String> {
+        let active_tray = self.get_tray_mut(tray_id)?;
+        //The face each counted die was on before it turned.
+        let mut counted: Vec<(usize, i32)> = Vec::new();
+
+        for target in targets.iter() {
+            match target {
+                DiceTargets::Index(indices) => {
+                    for i in indices.iter() {
+                        let before = active_tray.get_dice().get(*i).map(|die| die.get_current_face());
+                        active_tray.count_at(*i, amount)?;
+                        counted.extend(before.map(|face| (*i, face)));
+                    }
+                }
+                DiceTargets::Label(label) => {
+                    let matching: Vec<(usize, i32)> = active_tray
+                        .get_dice()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, die)| die.get_label() == label)
+                        .map(|(i, die)| (i, die.get_current_face()))
+                        .collect();
+                    active_tray.count_by_label(label, amount)?;
+                    counted.extend(matching);
+                }
+            }
+        }
+
+        for (i, before) in counted {
+            let die = &active_tray.get_dice()[i];
+            let DieMode::Counter { wrap, .. } = die.get_mode() else {
+                continue;
+            };
+            let target = (before as i64).saturating_add(amount);
+            if *wrap && (target < 1 || target > die.get_face_count() as i64) {
+                println!("@{} {} has wrapped around to {}!", i, die.get_label(), die.get_current_face());
+            } else if die.get_current_face() == before {
+                continue;
+            } else if die.is_min() {
+                println!("@{} {} has hit its minimum of {}!", i, die.get_label(), die.get_current_face());
+            } else if die.is_max() {
+                println!("@{} {} has hit its maximum of {}!", i, die.get_label(), die.get_current_face());
+            }
+        }
         Ok(())
     }
 
+    ///Releases every held die in the tray.
+    pub fn release_all(&mut self, tray_id: Option<&str>) {
+        match self.get_tray_mut(tray_id) {
+            Ok(active_tray) => {
+                for i in 0..active_tray.get_dice().len() {
+                    let _ = active_tray.set_held_at(i, false);
+                }
+                println!("Released all dice in tray: {}", active_tray.get_id());
+            }
+            Err(e) => println!("Release all failed with error {}", e),
+        };
+    }
+
     pub fn drop_all(&mut self, tray_id: Option<&str>) {
         match self.get_tray_mut(tray_id) {
             Ok(active_tray) => {
                 let _ = active_tray.remove_all();
+                active_tray.set_modifier(0);
                 println!("Dropped all dice from table: {}", active_tray.get_id());
             }
             Err(e) => println!("Drop all failed with error {}", e),
@@ -250,99 +883,1131 @@ impl CliDiceTrayApp {
         Ok(())
     }
 
-    ///Resets the whole application by clearning all trays and dice.
-    pub fn reset(&mut self) {
-        println!("Resetting dice-tray by deleting all trays and dice and trays.");
-        self.dice_trays.clear();
-
-        //Add the main tray back so there's always at least one tray.
-        let new_tray = self.dice_allocator.new_tray("Main".to_string());
-        self.dice_trays
-            .insert(new_tray.get_id().to_string(), new_tray);
-    }
+    ///Adds a macro to the macro store, replacing any macro with the same name.
+    pub fn add_macro(&self, name: &str, command: &str, tray_id: Option<&str>) -> Result<(), String> {
+        let roll_macro = RollMacro::new(command.to_string(), tray_id.map(|t| t.to_string()));
+        //Check the command parses, filling any parameters with 1s.
+        let placeholders = vec!["1".to_string(); roll_macro.get_parameters().len()];
+        parse_dice_notation(&roll_macro.expand(&placeholders)?)?;
 
-    ///Checks if a tray ID is a valid key in the dice_trays index map.
-    pub fn is_tray_id_valid(&self, check_id: &str) -> bool {
-        self.dice_trays.contains_key(check_id)
+        let mut store = load_macros(&self.save_file)?;
+        if store.add(name.to_string(), roll_macro) {
+            println!("Replaced macro {}.", name);
+        } else {
+            println!("Added macro {}. Run it with: dicetray {}", name, name);
+        }
+        save_macros(&self.save_file, &store)
     }
 
-    pub fn new_tray(&mut self, new_tray_id: &str) -> Result<(), String>{
-        if self.dice_trays.contains_key(new_tray_id){ 
-            return Err(format!("A dice tray already exists with key {}. Cannot create a new tray.", new_tray_id));
-        }else {
-            let new_tray = self.dice_allocator.new_tray(new_tray_id.to_string());
-            self.dice_trays.insert(new_tray.get_id().to_string(), new_tray);
-            return Ok(())
-        }
+    ///Removes a macro from the macro store.
+    pub fn remove_macro(&self, name: &str) -> Result<(), String> {
+        let mut store = load_macros(&self.save_file)?;
+        store.remove(name)?;
+        println!("Removed macro {}.", name);
+        save_macros(&self.save_file, &store)
     }
 
-    fn get_tray_mut(&mut self, id: Option<&str>) -> Result<&mut dyn Tray, String> {
-        match id {
-            Some(key) => {
-                if !self.dice_trays.contains_key(key) {
-                   return Err(format!("No tray found with id {}. Tray cannot be targeted.", key));
+    ///Prints every macro in the macro store.
+    pub fn show_macros(&self) {
+        match load_macros(&self.save_file) {
+            Ok(store) => {
+                if store.is_empty() {
+                    println!("No macros found. Add one with: dicetray macro add <name> <dice command>");
+                }
+                for (name, roll_macro) in store.iter() {
+                    let tray = roll_macro.get_tray().unwrap_or(name);
+                    println!("{} = \"{}\" in tray {}", name, roll_macro.get_command(), tray);
                 }
-                Ok(self.dice_trays.get_mut(key).unwrap().as_mut())
             }
-            None => match self.dice_trays.get_index_mut(0) {
-                Some((_, tray)) => Ok(tray.as_mut()),
-                None => Err(format!("No dice trays found at all. How!?")),
-            },
+            Err(e) => println!("Failed to load macros with error {}", e),
         }
     }
 
-    fn load_trays_from_file(&mut self) -> Result<Vec<Box<dyn Tray>>, Box<dyn Error>> {
-        let data_dir = match data_local_dir() {
-            Some(dir) => dir.join("dice-tray"),
-            None => {
-                eprintln!("Warning: Could not get local data directory, using current directory");
-                std::env::current_dir()?.join("dice-tray")
-            }
-        };
+    ///Runs a macro with the given arguments and returns the ID of the tray it used.
+    ///The macro's tray is the target tray if one was given, otherwise the tray the macro was bound to, otherwise a tray named after the macro.
+    ///If the tray is empty, or the expanded command changed since the last run, the tray's dice are rebuilt. Otherwise they are rerolled.
+    pub fn run_macro(
+        &mut self,
+        name: &str,
+        args: &[String],
+        tray_id: Option<&str>,
+    ) -> Result<String, String> {
+        let mut store = load_macros(&self.save_file)?;
+        let roll_macro = store
+            .get(name)
+            .ok_or(format!("No command or macro named {} found.", name))?;
+        let expansion = roll_macro.expand(args)?;
+        let dice_command = parse_dice_notation(&expansion)?;
+        let default_tray = roll_macro.get_tray().unwrap_or(name);
+        let macro_tray = tray_id.unwrap_or(default_tray).to_string();
+        let built_tray = roll_macro
+            .get_last_tray()
+            .or(roll_macro.get_last_expansion().map(|_| default_tray));
+        let rebuild = roll_macro.get_last_expansion() != Some(expansion.as_str());
 
-        if !data_dir.exists() {
-            create_dir_all(&data_dir)?;
+        if !self.is_tray_id_valid(&macro_tray) {
+            self.new_tray(&macro_tray)?;
+        }
+
+        let tray = self.get_tray_mut(Some(&macro_tray))?;
+        let built_here = built_tray == Some(macro_tray.as_str()) && !tray.get_dice().is_empty();
+        if !built_here && !tray.get_dice().is_empty() {
+            return Err(format!(
+                "Tray {} already holds dice the macro didn't build. Run the macro in an empty or new tray instead.",
+                macro_tray
+            ));
+        }
+        if rebuild || !built_here {
+            tray.clear();
+            tray.set_modifier(dice_command.modifier);
+            for (count, faces) in dice_command.dice.iter() {
+                self.add_dice_from_raw(Some(&macro_tray), *count, *faces, None);
+            }
+            let rolled: Vec<usize> = (0..self.get_tray(Some(&macro_tray))?.get_dice().len()).collect();
+            self.log_rolls(Some(&macro_tray), &rolled);
+            println!("Macro {} built \"{}\" in tray {}.", name, expansion, macro_tray);
+        } else {
+            self.roll_all(Some(&macro_tray), None);
+            println!("Macro {} rolled \"{}\" in tray {}.", name, expansion, macro_tray);
         }
 
-        let save_file = data_dir.join("dice_tray_save.json");
-        let file_content = std::fs::read_to_string(&save_file)?;
-        let tray_data_vec: Vec<CliTrayData> = serde_json::from_str(&file_content)?;
+        if let Some(roll_macro) = store.get_mut(name) {
+            roll_macro.set_last_build(expansion, macro_tray.clone());
+        }
+        save_macros(&self.save_file, &store)?;
+        Ok(macro_tray)
+    }
 
-        let mut loaded_trays: Vec<Box<dyn Tray>> = Vec::new();
-        for data in tray_data_vec {
-            let dice_data = data.get_dice_data();
-            let mut tray = self.dice_allocator.new_tray(data.get_label().to_string());
-            let mut tray_dice: Vec<Box<dyn Die>> = Vec::new();
-            for datum in dice_data {
-                tray_dice.push(self.dice_allocator.new_die_from_data(datum)?);
+    ///Imports the tables in a CSV, Markdown, JSON or YAML file into the tables directory, replacing any tables with the same name.
+    ///A new name can only be given when the file holds a single table.
+    pub fn import_tables(&self, path: &str, name: Option<&str>) -> Result<(), String> {
+        let mut tables = load_tables_from_file(Path::new(path))?;
+        if let Some(name) = name {
+            if tables.len() != 1 {
+                return Err(format!(
+                    "{} holds {} tables, so it can't be imported under a single name.",
+                    path,
+                    tables.len()
+                ));
+            }
+            let table = tables.remove(0);
+            tables.push(DiceResultTable::from_entries(
+                name.to_string(),
+                table.get_entries().clone(),
+            )?);
+        }
+
+        let existing = load_tables()?;
+        for table in tables.iter() {
+            save_table(table)?;
+            let verb = if existing.get(table.get_name()).is_some() {
+                "Replaced"
+            } else {
+                "Imported"
+            };
+            println!(
+                "{} table {} with {} entries (rolls {}-{}).",
+                verb,
+                table.get_name(),
+                table.get_entries().len(),
+                table.get_min_face(),
+                table.get_max_face()
+            );
+        }
+        Ok(())
+    }
+
+    ///Prints the name and roll range of every imported table.
+    pub fn show_table_list(&self) {
+        match load_tables() {
+            Ok(tables) => {
+                if tables.is_empty() {
+                    println!("No tables found. Import one with: dicetray table import <file>");
+                }
+                for table in tables.iter() {
+                    let deck_state = match table.get_deck() {
+                        Some(deck) => format!(
+                            ", deck with {} of {} entries left",
+                            table.get_entries().len() - deck.get_drawn_count(),
+                            table.get_entries().len()
+                        ),
+                        None => "".to_string(),
+                    };
+                    println!(
+                        "{}: {} entries, rolls {}-{}{}",
+                        table.get_name(),
+                        table.get_entries().len(),
+                        table.get_min_face(),
+                        table.get_max_face(),
+                        deck_state
+                    );
+                }
+            }
+            Err(e) => println!("Failed to load tables with error {}", e),
+        }
+    }
+
+    ///Prints every entry in the named table.
+    pub fn show_table(&self, name: &str) -> Result<(), String> {
+        let tables = load_tables()?;
+        let table = tables.get(name).ok_or(format!("No table named {} found.", name))?;
+        log_result_table(table);
+        Ok(())
+    }
+
+    ///Rolls on the named table. If a dice command is given (i.e. "2d6+1") the dice are rolled and their total used as the roll,
+    ///otherwise a single die covering the table's range is rolled.
+    pub fn roll_table(&self, name: &str, dice_command: Option<&str>) -> Result<(), String> {
+        let mut tables = load_tables()?;
+        if tables.get(name).is_none() {
+            return Err(format!("No table named {} found.", name));
+        }
+        let mut rng = rand::rng();
+
+        let face = match dice_command {
+            Some(dice_command) => {
+                let parsed = parse_dice_notation(dice_command)?;
+                let total = parsed.dice.iter().fold(parsed.modifier, |total, (count, faces)| {
+                    (0..*count).fold(total, |total, _| {
+                        total.saturating_add(rng.random_range(1..=*faces) as i64)
+                    })
+                });
+                Some(total.clamp(0, u32::MAX as i64) as u32)
+            }
+            None => None,
+        };
+
+        let table_roll = tables.roll_on(name, face, &mut rng)?;
+        save_drawn_tables(&tables, std::slice::from_ref(&table_roll))?;
+        log_table_roll(&table_roll);
+        Ok(())
+    }
+
+    ///Turns deck mode on or off for the named table. Deck tables are drawn from without replacement until reshuffled.
+    pub fn set_table_deck(&self, name: &str, enabled: bool, auto_reshuffle: bool) -> Result<(), String> {
+        let mut tables = load_tables()?;
+        let table = tables
+            .get_mut(name)
+            .ok_or(format!("No table named {} found.", name))?;
+        if enabled {
+            table.enable_deck(auto_reshuffle);
+            println!(
+                "Table {} is now drawn without replacement{}.",
+                table.get_name(),
+                if auto_reshuffle { " and reshuffles when drawn out" } else { "" }
+            );
+        } else {
+            table.disable_deck();
+            println!("Table {} is no longer a deck.", table.get_name());
+        }
+        save_table(table)
+    }
+
+    ///Returns every drawn entry to the named deck table.
+    pub fn reshuffle_table(&self, name: &str) -> Result<(), String> {
+        let mut tables = load_tables()?;
+        let table = tables
+            .get_mut(name)
+            .ok_or(format!("No table named {} found.", name))?;
+        table.reshuffle()?;
+        println!("Reshuffled table {}.", table.get_name());
+        save_table(table)
+    }
+
+    ///Prints the draw history of the named deck table, most recent last. Only the last count events are shown if a count is given.
+    pub fn show_table_history(&self, name: &str, count: Option<usize>) -> Result<(), String> {
+        let tables = load_tables()?;
+        let table = tables.get(name).ok_or(format!("No table named {} found.", name))?;
+        let deck = table
+            .get_deck()
+            .ok_or(format!("Table {} is not a deck, so it has no draw history.", table.get_name()))?;
+        let history = deck.get_history();
+        let skip = history.len().saturating_sub(count.unwrap_or(history.len()));
+        println!("Draw history for table {}:", table.get_name());
+        for event in history.iter().skip(skip) {
+            match event {
+                TableDeckEvent::Drawn { face, text } => println!("  Drew {}: {}", face, text),
+                TableDeckEvent::Reshuffled => println!("  Reshuffled"),
+            }
+        }
+        Ok(())
+    }
+
+    ///Creates a new deck. Custom cards are used if provided, otherwise a standard deck, a standard deck with jokers, or a tarot deck is built.
+    pub fn new_deck(&mut self, name: &str, kind: &str, cards: Option<Vec<String>>) -> Result<(), String> {
+        if self.decks.contains_key(name) {
+            return Err(format!("A deck already exists with name {}. Cannot create a new deck.", name));
+        }
+        let deck = match cards {
+            Some(cards) if !cards.is_empty() => Deck::new(name.to_string(), cards),
+            Some(_) => return Err("A custom deck needs at least one card.".to_string()),
+            None => match kind {
+                "standard" => Deck::standard(name.to_string(), false),
+                "jokers" => Deck::standard(name.to_string(), true),
+                "tarot" => Deck::tarot(name.to_string()),
+                _ => {
+                    return Err(format!(
+                        "Unknown deck kind {}. Use standard, jokers or tarot, or provide --cards.",
+                        kind
+                    ));
+                }
+            },
+        };
+        println!("Created deck {} with {} cards.", name, deck.get_card_count());
+        self.decks.insert(name.to_string(), deck);
+        Ok(())
+    }
+
+    ///Deletes a deck and all of its piles.
+    pub fn delete_deck(&mut self, name: &str) -> Result<(), String> {
+        self.decks
+            .shift_remove(name)
+            .ok_or(format!("No deck found with name {}.", name))?;
+        println!("Deck '{}' has been deleted.", name);
+        Ok(())
+    }
+
+    ///Prints a line for every deck, with the number of cards in each pile.
+    pub fn show_deck_list(&self) {
+        if self.decks.is_empty() {
+            println!("No decks found. Create one with: dicetray deck new <name>");
+        }
+        for deck in self.decks.values() {
+            let piles: Vec<String> = deck
+                .get_piles()
+                .iter()
+                .map(|pile| format!("{} {}", pile.get_name(), pile.get_cards().len()))
+                .collect();
+            println!("{}: {}", deck.get_label(), piles.join(", "));
+        }
+    }
+
+    ///Shows the piles of a deck. The draw pile is face down, so only its size is shown.
+    pub fn show_deck(&self, name: &str) -> Result<(), String> {
+        log_deck(self.get_deck(name)?);
+        Ok(())
+    }
+
+    ///Shuffles a pile of the deck, the draw pile by default.
+    pub fn shuffle_deck(&mut self, name: &str, pile: Option<&str>) -> Result<(), String> {
+        let pile = pile.unwrap_or(DRAW_PILE);
+        self.get_deck_mut(name)?.shuffle(pile)?;
+        println!("Shuffled the {} pile of deck {}.", pile, name);
+        Ok(())
+    }
+
+    ///Draws cards from the deck onto a pile, the "hand" pile by default.
+    pub fn draw_cards(&mut self, name: &str, count: usize, to_pile: Option<&str>) -> Result<(), String> {
+        let to_pile = to_pile.unwrap_or("hand");
+        let drawn = self.get_deck_mut(name)?.draw(count, to_pile)?;
+        println!("Drew {} to {}: {}", drawn.len(), to_pile, drawn.join(", "));
+        Ok(())
+    }
+
+    ///Discards cards from a pile, the "hand" pile by default. If no cards are named the whole pile is discarded.
+    pub fn discard_cards(&mut self, name: &str, cards: Option<Vec<String>>, from_pile: Option<&str>) -> Result<(), String> {
+        let from_pile = from_pile.unwrap_or("hand");
+        let deck = self.get_deck_mut(name)?;
+        let cards = match cards {
+            Some(cards) => cards,
+            None => deck
+                .get_pile(from_pile)
+                .ok_or(format!("No pile named {} in deck {}.", from_pile, name))?
+                .get_cards()
+                .clone(),
+        };
+        deck.discard(from_pile, &cards)?;
+        println!("Discarded from {}: {}", from_pile, cards.join(", "));
+        Ok(())
+    }
+
+    ///Prints the top cards of the draw pile without drawing them.
+    pub fn peek_cards(&self, name: &str, count: usize) -> Result<(), String> {
+        let peeked = self.get_deck(name)?.peek(count);
+        println!("Top of deck {}: {}", name, peeked.join(", "));
+        Ok(())
+    }
+
+    ///Cuts the draw pile, at a random position if none is given.
+    pub fn cut_deck(&mut self, name: &str, position: Option<usize>) -> Result<(), String> {
+        let cut = self.get_deck_mut(name)?.cut(position)?;
+        println!("Cut deck {}, moving the top {} card(s) to the bottom.", name, cut);
+        Ok(())
+    }
+
+    ///Returns cards to the draw pile. Returns a single pile (the discard pile by default), or every card in the deck and shuffles if all is set.
+    pub fn return_cards(&mut self, name: &str, pile: Option<&str>, all: bool) -> Result<(), String> {
+        let deck = self.get_deck_mut(name)?;
+        if all {
+            let count = deck.return_all()?;
+            println!("Returned {} card(s) to deck {} and shuffled.", count, name);
+        } else {
+            let pile = pile.unwrap_or(DISCARD_PILE);
+            let count = deck.return_pile(pile)?;
+            println!("Returned {} card(s) from {} to the bottom of deck {}.", count, pile, name);
+        }
+        Ok(())
+    }
+
+    fn get_deck(&self, name: &str) -> Result<&Deck, String> {
+        self.decks
+            .get(name)
+            .ok_or(format!("No deck found with name {}.", name))
+    }
+
+    fn get_deck_mut(&mut self, name: &str) -> Result<&mut Deck, String> {
+        self.decks
+            .get_mut(name)
+            .ok_or(format!("No deck found with name {}.", name))
+    }
+
+    ///Creates a new bag holding the provided tokens, given as (label, count).
+    pub fn new_bag(&mut self, name: &str, tokens: Vec<(String, u32)>) -> Result<(), String> {
+        if self.bags.contains_key(name) {
+            return Err(format!("A bag already exists with name {}. Cannot create a new bag.", name));
+        }
+        let bag = Bag::new(name.to_string(), tokens);
+        println!("Created bag {} with {} tokens.", name, bag.get_in_bag_count());
+        self.bags.insert(name.to_string(), bag);
+        Ok(())
+    }
+
+    ///Deletes a bag and all of its tokens.
+    pub fn delete_bag(&mut self, name: &str) -> Result<(), String> {
+        self.bags
+            .shift_remove(name)
+            .ok_or(format!("No bag found with name {}.", name))?;
+        println!("Bag '{}' has been deleted.", name);
+        Ok(())
+    }
+
+    ///Prints a line for every bag, with the number of tokens in it.
+    pub fn show_bag_list(&self) {
+        if self.bags.is_empty() {
+            println!("No bags found. Create one with: dicetray bag new <name> <tokens>");
+        }
+        for bag in self.bags.values() {
+            let drawn: u32 = bag.get_tokens().iter().map(|token| token.get_drawn()).sum();
+            println!("{}: {} in bag, {} drawn", bag.get_label(), bag.get_in_bag_count(), drawn);
+        }
+    }
+
+    ///Shows the composition of a bag, without drawing from it.
+    pub fn show_bag(&self, name: &str) -> Result<(), String> {
+        log_bag(self.get_bag(name)?);
+        Ok(())
+    }
+
+    ///Puts more tokens into a bag, given as (label, count).
+    pub fn add_tokens(&mut self, name: &str, tokens: Vec<(String, u32)>) -> Result<(), String> {
+        let bag = self.get_bag_mut(name)?;
+        for (token, count) in tokens {
+            bag.add_tokens(&token, count);
+            println!("Added {} {} token(s) to bag {}.", count, token, name);
+        }
+        Ok(())
+    }
+
+    ///Takes tokens out of a bag for good, given as (label, count).
+    pub fn remove_tokens(&mut self, name: &str, tokens: Vec<(String, u32)>) -> Result<(), String> {
+        let bag = self.get_bag_mut(name)?;
+        for (token, count) in tokens {
+            bag.remove_tokens(&token, count)?;
+            println!("Removed {} {} token(s) from bag {}.", count, token, name);
+        }
+        Ok(())
+    }
+
+    ///Draws tokens from a bag without replacement.
+    pub fn draw_tokens(&mut self, name: &str, count: u32) -> Result<(), String> {
+        let drawn = self.get_bag_mut(name)?.draw(count)?;
+        println!("Drew from bag {}: {}", name, drawn.join(", "));
+        Ok(())
+    }
+
+    ///Returns drawn tokens to a bag, given as (label, count). Returns every drawn token if none are given.
+    pub fn return_tokens(&mut self, name: &str, tokens: Option<Vec<(String, u32)>>) -> Result<(), String> {
+        let bag = self.get_bag_mut(name)?;
+        match tokens {
+            Some(tokens) => {
+                for (token, count) in tokens {
+                    bag.return_tokens(&token, count)?;
+                    println!("Returned {} {} token(s) to bag {}.", count, token, name);
+                }
+            }
+            None => {
+                let returned = bag.return_all();
+                println!("Returned all {} drawn token(s) to bag {}.", returned, name);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_bag(&self, name: &str) -> Result<&Bag, String> {
+        self.bags
+            .get(name)
+            .ok_or(format!("No bag found with name {}.", name))
+    }
+
+    fn get_bag_mut(&mut self, name: &str) -> Result<&mut Bag, String> {
+        self.bags
+            .get_mut(name)
+            .ok_or(format!("No bag found with name {}.", name))
+    }
+
+    ///Starts a new game of Yahtzee for the players, replacing any game in progress.
+    ///The game is played with five d6 in the yahtzee tray. A yahtzee tray holding dice outside of a game is never cleared.
+    pub fn new_yahtzee(&mut self, players: Vec<String>) -> Result<(), String> {
+        let game = YahtzeeGame::new(YAHTZEE_TRAY.to_string(), players)?;
+        if self.yahtzee.is_none()
+            && self.get_tray(Some(YAHTZEE_TRAY)).is_ok_and(|tray| !tray.get_dice().is_empty())
+        {
+            return Err(format!(
+                "Tray {} already holds dice. Delete it first with: dicetray delete {}",
+                YAHTZEE_TRAY, YAHTZEE_TRAY
+            ));
+        }
+        if !self.is_tray_id_valid(YAHTZEE_TRAY) {
+            self.new_tray(YAHTZEE_TRAY)?;
+        }
+        let tray = self.get_tray_mut(Some(YAHTZEE_TRAY))?;
+        tray.clear();
+        tray.set_modifier(0);
+        tray.set_result_type(TrayResultType::Pattern);
+        self.add_dice_from_raw(Some(YAHTZEE_TRAY), YAHTZEE_DICE, 6, None);
+
+        println!(
+            "New game of Yahtzee! {} to roll first with: dicetray yahtzee roll",
+            game.get_current_scorecard().get_player()
+        );
+        self.yahtzee = Some(game);
+        Ok(())
+    }
+
+    ///Rolls the Yahtzee dice for the current player. The first roll of a turn rolls every die, later rolls skip held dice.
+    pub fn roll_yahtzee(&mut self) -> Result<(), String> {
+        let game = self.yahtzee.as_mut().ok_or(NO_YAHTZEE_GAME.to_string())?;
+        let first_roll = game.is_first_roll();
+        game.use_roll()?;
+        let player = game.get_current_scorecard().get_player().to_string();
+        let rolls_used = game.get_rolls_used();
+
+        if first_roll {
+            self.release_all(Some(YAHTZEE_TRAY));
+        }
+        self.roll_all(Some(YAHTZEE_TRAY), None);
+        println!("{} rolled ({} of {}).", player, rolls_used, ROLLS_PER_TURN);
+        self.show_yahtzee_suggestions()
+    }
+
+    ///Scores the Yahtzee dice in the category for the current player, then passes the turn on.
+    pub fn score_yahtzee(&mut self, category: &str) -> Result<(), String> {
+        let category = ScoreCategory::from_name(category)?;
+        let faces = self.yahtzee_faces()?;
+        let game = self.yahtzee.as_mut().ok_or(NO_YAHTZEE_GAME.to_string())?;
+        let player = game.get_current_scorecard().get_player().to_string();
+        let points = game.score(category, &faces)?;
+        println!("{} scored {} in {}.", player, points, category);
+
+        log_scorecards(game);
+        if game.is_over() {
+            let leaders: Vec<&str> = game.leaders().iter().map(|card| card.get_player()).collect();
+            println!("Game over! Winner: {}", leaders.join(" and "));
+        } else {
+            println!("{} to roll.", game.get_current_scorecard().get_player());
+        }
+        Ok(())
+    }
+
+    ///Shows every player's scorecard.
+    pub fn show_yahtzee_card(&self) -> Result<(), String> {
+        let game = self.yahtzee.as_ref().ok_or(NO_YAHTZEE_GAME.to_string())?;
+        log_scorecards(game);
+        if !game.is_over() {
+            println!(
+                "{} to play, {} of {} rolls used.",
+                game.get_current_scorecard().get_player(),
+                game.get_rolls_used(),
+                ROLLS_PER_TURN
+            );
+        }
+        Ok(())
+    }
+
+    ///Shows what the current dice would score in each of the current player's open categories.
+    pub fn show_yahtzee_suggestions(&self) -> Result<(), String> {
+        let game = self.yahtzee.as_ref().ok_or(NO_YAHTZEE_GAME.to_string())?;
+        if game.is_first_roll() {
+            println!("Roll the dice first with: dicetray yahtzee roll");
+            return Ok(());
+        }
+        log_yahtzee_suggestions(&game.suggestions(&self.yahtzee_faces()?));
+        Ok(())
+    }
+
+    ///Ends the game in progress. The yahtzee tray is kept.
+    pub fn quit_yahtzee(&mut self) -> Result<(), String> {
+        self.yahtzee.take().ok_or(NO_YAHTZEE_GAME.to_string())?;
+        println!("Ended the game of Yahtzee.");
+        Ok(())
+    }
+
+    ///Runs a yahtzee command, which may change the dice in the yahtzee tray while a game locks it.
+    pub fn with_yahtzee_tray<T>(&mut self, command: impl FnOnce(&mut Self) -> T) -> T {
+        self.yahtzee_unlocked = true;
+        let result = command(self);
+        self.yahtzee_unlocked = false;
+        result
+    }
+
+    ///Returns an error if the tray is the yahtzee tray of a game in progress, which only the yahtzee commands may change.
+    ///This keeps players to three rolls a turn and stops faces being set, or dice added and removed, between rolls.
+    fn check_yahtzee_lock(&self, tray_id: &str) -> Result<(), String> {
+        let in_progress = self.yahtzee.as_ref().is_some_and(|game| !game.is_over());
+        if in_progress && !self.yahtzee_unlocked && tray_id == YAHTZEE_TRAY {
+            return Err(format!(
+                "Tray {} is in use by a game of Yahtzee. Use the yahtzee commands, or end the game with: dicetray yahtzee quit",
+                tray_id
+            ));
+        }
+        Ok(())
+    }
+
+    fn yahtzee_faces(&self) -> Result<Vec<u32>, String> {
+        let tray = self.get_tray(Some(YAHTZEE_TRAY))?;
+        Ok(tray
+            .get_dice()
+            .iter()
+            .map(|die| die.get_current_face().max(0) as u32)
+            .collect())
+    }
+
+    ///Lists the backups of the save file, or restores one and reloads everything from it.
+    pub fn restore(&mut self, number: Option<usize>) -> Result<(), String> {
+        let save_file = self.save_file.clone();
+        let Some(number) = number else {
+            log_backups(&list_backups(&save_file));
+            return Ok(());
+        };
+
+        let replaced = restore_backup(&save_file, number)?;
+        println!("Restored backup {}. The save it replaced was kept at {}", number, replaced.display());
+
+        self.dice_trays.clear();
+        self.decks.clear();
+        self.bags.clear();
+        self.yahtzee = None;
+        self.save_locked = false;
+        self.load_state();
+        Ok(())
+    }
+
+    ///Writes a tray, with its dice, their RNG state and the tray's result settings, to its own file.
+    pub fn export_tray(&self, tray_id: &str, out: Option<&str>) -> Result<(), String> {
+        let tray = self.get_tray(Some(tray_id))?;
+        let out = match out {
+            Some(out) => PathBuf::from(out),
+            None => PathBuf::from(format!("{}.tray.json", tray_id)),
+        };
+        let json_content = TrayExport::new(CliTrayData::from(tray))
+            .to_json()
+            .map_err(|e| e.to_string())?;
+        write_atomic(&out, &json_content).map_err(|e| format!("Failed to write {}: {}", out.display(), e))?;
+        println!("Exported tray {} with {} dice to {}", tray_id, tray.get_dice().len(), out.display());
+        Ok(())
+    }
+
+    ///Imports a tray written by export_tray, under its exported ID or the one given with as_id.
+    ///If a tray with that ID already exists, on_conflict decides whether the import fails, replaces it or is renamed.
+    pub fn import_tray(&mut self, file: &str, as_id: Option<&str>, on_conflict: ImportConflict) -> Result<(), String> {
+        let file_content =
+            std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        let export = TrayExport::from_json(&file_content).map_err(|e| e.to_string())?;
+        let data = export.get_tray();
+
+        let tray_id = self.import_tray_id(as_id.unwrap_or(data.get_label()), on_conflict)?;
+        let tray = self.tray_from_data(data, &tray_id)?;
+        println!("Imported tray {} with {} dice.", tray_id, tray.get_dice().len());
+        self.dice_trays.insert(tray_id, tray);
+        Ok(())
+    }
+
+    ///Prints a share code for the tray's dice, labels and result types. With seeds, everyone loading the code gets dice that roll the same faces.
+    pub fn share_tray(&self, tray_id: &str, include_seeds: bool) -> Result<(), String> {
+        let tray = self.get_tray(Some(tray_id))?;
+        let code = encode_tray(&CliTrayData::from(tray), include_seeds);
+        println!("Share code for tray {}:", tray_id);
+        println!("{}", code);
+        println!("Load it with: dicetray load-code {}", code);
+        Ok(())
+    }
+
+    ///Builds a tray from a share code, under the tray ID in the code or the one given with as_id.
+    pub fn load_code(&mut self, code: &str, as_id: Option<&str>, on_conflict: ImportConflict) -> Result<(), String> {
+        let tray_code = decode_tray(code)?;
+        let tray_id = self.import_tray_id(as_id.unwrap_or(tray_code.get_label()), on_conflict)?;
+
+        let mut tray = self.dice_allocator.new_tray(tray_id.clone());
+        tray.set_modifier(tray_code.get_modifier());
+        tray.set_result_type(tray_code.get_result_type());
+        let mut tray_dice: Vec<Box<dyn Die>> = Vec::new();
+        for code_die in tray_code.get_dice() {
+            let profile = DieProfile::new(
+                Some(code_die.get_label().to_string()),
+                DieProfileType::Numerical(code_die.get_faces()),
+                Some(code_die.get_result_type()),
+            );
+            let die = match code_die.get_seed() {
+                Some(seed) => self.dice_allocator.new_seeded_die(&profile, seed)?,
+                None => self.dice_allocator.new_die(&profile)?,
+            };
+            tray_dice.push(die);
+        }
+        tray.add_dice(tray_dice);
+
+        let seeded = if tray_code.has_seeds() { " (shared seeds)" } else { "" };
+        println!("Loaded tray {} with {} dice{}.", tray_id, tray.get_dice().len(), seeded);
+        self.dice_trays.insert(tray_id, tray);
+        Ok(())
+    }
+
+    ///Picks the ID an imported tray is added under, following on_conflict if a tray already has the ID.
+    fn import_tray_id(&self, tray_id: &str, on_conflict: ImportConflict) -> Result<String, String> {
+        if !self.dice_trays.contains_key(tray_id) {
+            return Ok(tray_id.to_string());
+        }
+        match on_conflict {
+            ImportConflict::Fail => Err(format!(
+                "A tray with ID {} already exists. Import it under another ID with --as, or use --on-conflict rename or replace.",
+                tray_id
+            )),
+            ImportConflict::Replace => {
+                self.check_yahtzee_lock(tray_id)?;
+                println!("Replacing tray {}.", tray_id);
+                Ok(tray_id.to_string())
+            }
+            ImportConflict::Rename => {
+                let mut number = 2;
+                while self.dice_trays.contains_key(&format!("{}-{}", tray_id, number)) {
+                    number += 1;
+                }
+                Ok(format!("{}-{}", tray_id, number))
             }
-            tray.add_dice(tray_dice);
-            loaded_trays.push(tray);
+        }
+    }
+
+    ///Lists every workspace, marking the active one.
+    pub fn show_workspaces(&self) -> Result<(), String> {
+        let active = active_workspace()?;
+        for workspace in list_workspaces()? {
+            let marker = if workspace == active { "*" } else { " " };
+            println!("{} {}", marker, workspace);
+        }
+        println!("Using save file: {}", self.save_file.display());
+        Ok(())
+    }
+
+    ///Creates a new, empty workspace.
+    pub fn new_workspace(&self, name: &str) -> Result<(), String> {
+        new_workspace(name)?;
+        println!("Created workspace {}. Switch to it with: dicetray workspace switch {}", name, name);
+        Ok(())
+    }
+
+    ///Makes the workspace the active one. Takes effect from the next command.
+    pub fn switch_workspace(&self, name: &str) -> Result<(), String> {
+        switch_workspace(name)?;
+        println!("Switched to workspace {}.", name);
+        Ok(())
+    }
+
+    ///Deletes a workspace and its save file.
+    pub fn delete_workspace(&self, name: &str) -> Result<(), String> {
+        delete_workspace(name)?;
+        println!("Workspace {} has been deleted.", name);
+        Ok(())
+    }
+
+    ///Resets the whole application by clearning all trays and dice.
+    pub fn reset(&mut self) {
+        println!("Resetting dice-tray by deleting all trays, dice, decks and bags.");
+        self.dice_trays.clear();
+        self.decks.clear();
+        self.bags.clear();
+        self.yahtzee = None;
+
+        //Add the main tray back so there's always at least one tray.
+        let new_tray = self.dice_allocator.new_tray("Main".to_string());
+        self.dice_trays
+            .insert(new_tray.get_id().to_string(), new_tray);
+    }
+
+    ///Checks if a tray ID is a valid key in the dice_trays index map.
+    pub fn is_tray_id_valid(&self, check_id: &str) -> bool {
+        self.dice_trays.contains_key(check_id)
+    }
+
+    pub fn new_tray(&mut self, new_tray_id: &str) -> Result<(), String>{
+        if self.dice_trays.contains_key(new_tray_id){ 
+            Err(format!("A dice tray already exists with key {}. Cannot create a new tray.", new_tray_id))
+        }else {
+            let new_tray = self.dice_allocator.new_tray(new_tray_id.to_string());
+            self.dice_trays.insert(new_tray.get_id().to_string(), new_tray);
+            Ok(())
+        }
+    }
+
+    fn get_tray(&self, id: Option<&str>) -> Result<&dyn Tray, String> {
+        match id {
+            Some(key) => match self.dice_trays.get(key) {
+                Some(tray) => Ok(tray.as_ref()),
+                None => Err(format!("No tray found with id {}. Tray cannot be targeted.", key)),
+            },
+            None => match self.dice_trays.first() {
+                Some((_, tray)) => Ok(tray.as_ref()),
+                None => Err("No dice trays found at all. How!?".to_string()),
+            },
+        }
+    }
+
+    fn get_tray_mut(&mut self, id: Option<&str>) -> Result<&mut dyn Tray, String> {
+        match id {
+            Some(key) => {
+                if !self.dice_trays.contains_key(key) {
+                   return Err(format!("No tray found with id {}. Tray cannot be targeted.", key));
+                }
+                self.check_yahtzee_lock(key)?;
+                Ok(self.dice_trays.get_mut(key).unwrap().as_mut())
+            }
+            None => match self.dice_trays.get_index_mut(0) {
+                Some((_, tray)) => Ok(tray.as_mut()),
+                None => Err("No dice trays found at all. How!?".to_string()),
+            },
+        }
+    }
+
+    ///Loads every profile in the profiles directory, creating the directory if it doesn't exist yet.
+    fn load_profiles(&self) -> Result<DieProfileSet, String> {
+        let dir = profiles_dir().map_err(|e| e.to_string())?;
+        load_profiles_from_dir(&dir)
+    }
+
+    ///Loads the save file. Decks and bags are restored directly, while trays are returned so init can check for duplicate IDs.
+    fn load_save_file(&mut self) -> Result<Vec<Box<dyn Tray>>, Box<dyn Error>> {
+        let save_file = self.save_file.clone();
+        let file_content = match std::fs::read_to_string(&save_file) {
+            Ok(file_content) => file_content,
+            //No save file yet, so start fresh.
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.loaded_content = None;
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(Box::new(e)),
+        };
+        self.loaded_content = Some(file_content.clone());
+        let loaded_save = CliSaveData::from_json(&file_content)?;
+
+        if loaded_save.was_migrated() {
+            //Keep the old save around in case the migration lost something.
+            //Named after the save file, i.e. dice_tray_save.v1.backup.json, so saves sharing a directory keep their own backups.
+            let backup_file = save_file.with_extension(format!("v{}.backup.json", loaded_save.get_version()));
+            std::fs::copy(&save_file, &backup_file)?;
+            println!(
+                "Upgraded the save file from version {} to version {}. The old save was kept at {}",
+                loaded_save.get_version(),
+                SAVE_VERSION,
+                backup_file.display()
+            );
+        }
+        let save_data = loaded_save.get_data();
+
+        for deck_data in save_data.get_decks() {
+            let deck = Deck::from_data(deck_data);
+            self.decks.insert(deck.get_label().to_string(), deck);
+        }
+        for bag_data in save_data.get_bags() {
+            let bag = Bag::from_data(bag_data);
+            self.bags.insert(bag.get_label().to_string(), bag);
+        }
+        self.yahtzee = save_data.get_yahtzee().cloned();
+
+        let mut loaded_trays: Vec<Box<dyn Tray>> = Vec::new();
+        for data in save_data.get_trays() {
+            loaded_trays.push(self.tray_from_data(data, data.get_label())?);
         }
 
         Ok(loaded_trays)
     }
 
-    fn save_trays_to_file(&mut self) -> Result<(), Box<dyn Error>> {
+    ///Rebuilds a tray and its dice from saved tray data, giving it the provided ID.
+    fn tray_from_data(&mut self, data: &CliTrayData, tray_id: &str) -> Result<Box<dyn Tray>, String> {
+        let mut tray = self.dice_allocator.new_tray(tray_id.to_string());
+        tray.set_modifier(data.get_modifier());
+        tray.set_result_type(data.get_result_type());
+        let mut tray_dice: Vec<Box<dyn Die>> = Vec::new();
+        for datum in data.get_dice_data() {
+            tray_dice.push(self.dice_allocator.new_die_from_data(datum)?);
+        }
+        tray.add_dice(tray_dice);
+        Ok(tray)
+    }
+
+    fn save_to_file(&mut self) -> Result<(), Box<dyn Error>> {
         let mut tray_data_vec: Vec<CliTrayData> = Vec::new();
 
         for tray in self.dice_trays.iter() {
             let tray_data = CliTrayData::from(tray.1.as_ref());
             tray_data_vec.push(tray_data);
         }
+        let deck_data_vec: Vec<DeckData> = self.decks.values().map(DeckData::from).collect();
+        let bag_data_vec: Vec<BagData> = self.bags.values().map(BagData::from).collect();
 
-        let data_dir = data_local_dir()
-            .ok_or("Failed to get local data directory")?
-            .join("dice-tray");
+        let save_file = self.save_file.clone();
+        if let Some(dir) = save_file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            create_dir_all(dir)?;
+        }
+        let save_data = CliSaveData::new(tray_data_vec, deck_data_vec, bag_data_vec, self.yahtzee.clone());
+        let json_content = save_data.to_json()?;
 
-        if !data_dir.exists() {
-            create_dir_all(&data_dir)?;
+        //Other dicetray runs wait for the lock, but an editor or an older version could still have changed the file.
+        let current_content = match std::fs::read_to_string(&save_file) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(Box::new(e)),
+        };
+        if current_content != self.loaded_content {
+            let conflict_file = conflict_path(&save_file);
+            write_atomic(&conflict_file, &json_content)?;
+            return Err(format!(
+                "the save file was changed by something else during this run, so it wasn't overwritten. Changes from this run were written to {}",
+                conflict_file.display()
+            )
+            .into());
         }
 
-        let save_file = data_dir.join("dice_tray_save.json");
-        let json_content = serde_json::to_string_pretty(&tray_data_vec)?;
-        std::fs::write(&save_file, json_content)?;
+        rotate_backups(&save_file)?;
+        write_atomic(&save_file, &json_content)?;
+        self.loaded_content = Some(json_content);
 
         Ok(())
     }
 }
+
+///Turns dice targets into indices in the tray, in order and without repeats. No targets means every die in the tray.
+fn target_indices(tray: &dyn Tray, targets: Option<Vec<DiceTargets>>) -> Vec<usize> {
+    let Some(targets) = targets else {
+        return (0..tray.get_dice().len()).collect();
+    };
+    let mut indices: Vec<usize> = Vec::new();
+    for target in targets.iter() {
+        let matching: Vec<usize> = match target {
+            DiceTargets::Index(target_indices) => target_indices.clone(),
+            DiceTargets::Label(label) => tray
+                .get_dice()
+                .iter()
+                .enumerate()
+                .filter(|(_, die)| die.get_label() == label)
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        for i in matching {
+            if !indices.contains(&i) {
+                indices.push(i);
+            }
+        }
+    }
+    indices
+}
+
+///Gets the directory shared die profiles are loaded from, creating it if required.
+fn profiles_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let profiles_dir = data_dir()?.join("profiles");
+    if !profiles_dir.exists() {
+        create_dir_all(&profiles_dir)?;
+    }
+    Ok(profiles_dir)
+}
+
+fn load_macros(save_file: &Path) -> Result<MacroStore, String> {
+    MacroStore::load(&macros_path(save_file)).map_err(|e| format!("Failed to load macros: {}", e))
+}
+
+fn save_macros(save_file: &Path, store: &MacroStore) -> Result<(), String> {
+    store
+        .save(&macros_path(save_file))
+        .map_err(|e| format!("Failed to save macros: {}", e))
+}
+
+///Gets the directory imported result tables are stored in, creating it if required.
+fn tables_dir() -> Result<PathBuf, String> {
+    let tables_dir = data_dir().map_err(|e| e.to_string())?.join("tables");
+    if !tables_dir.exists() {
+        create_dir_all(&tables_dir).map_err(|e| e.to_string())?;
+    }
+    Ok(tables_dir)
+}
+
+fn load_tables() -> Result<TableSet, String> {
+    load_tables_from_dir(&tables_dir()?)
+}
+
+///Saves the deck mode tables drawn from by the table rolls, so their draw state persists. Tables that weren't drawn from are left alone.
+fn save_drawn_tables(tables: &TableSet, table_rolls: &[TableRoll]) -> Result<(), String> {
+    let mut saved: Vec<&str> = Vec::new();
+    for table_roll in table_rolls.iter().flat_map(|table_roll| table_roll.chain()) {
+        if saved.contains(&table_roll.table.as_str()) {
+            continue;
+        }
+        if let Some(table) = tables.get(&table_roll.table)
+            && table.get_deck().is_some()
+        {
+            save_table(table)?;
+        }
+        saved.push(&table_roll.table);
+    }
+    Ok(())
+}
+
+///Saves a table to the tables directory as JSON, named after the table.
+///Returns an error rather than overwriting a different table whose name maps to the same file, i.e. "Treasure B" and "Treasure_B".
+fn save_table(table: &DiceResultTable) -> Result<(), String> {
+    let file_name: String = table
+        .get_name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = tables_dir()?.join(file_name + ".json");
+    if path.exists()
+        && let Ok(existing) = load_tables_from_file(&path)
+        && let Some(other) = existing.iter().find(|other| other.get_name() != table.get_name())
+    {
+        return Err(format!(
+            "Table {} would overwrite table {} in {}. Rename one of them.",
+            table.get_name(),
+            other.get_name(),
+            path.display()
+        ));
+    }
+    let json_content = serde_json::to_string_pretty(table).map_err(|e| e.to_string())?;
+    write_atomic(&path, &json_content).map_err(|e| format!("Failed to save table {}: {}", table.get_name(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_dice::tray::TrayResult;
+
+    ///A directory of its own for a test's save files, removed when the test ends.
+    struct TestDir {
+        dir: PathBuf,
+    }
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("dicetray-app-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            create_dir_all(&dir).unwrap();
+            TestDir { dir }
+        }
+
+        fn workspace(&self, name: &str) -> PathBuf {
+            let dir = self.dir.join(name);
+            create_dir_all(&dir).unwrap();
+            dir.join("dice_tray_save.json")
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn faces(app: &CliDiceTrayApp, tray_id: &str) -> Vec<(u32, i32)> {
+        app.get_tray(Some(tray_id))
+            .unwrap()
+            .get_dice()
+            .iter()
+            .map(|die| (die.get_face_count(), die.get_current_face()))
+            .collect()
+    }
+
+    #[test]
+    fn macros_are_kept_per_save_file() {
+        let test = TestDir::new("macro-files");
+        let save_a = test.workspace("a");
+        let save_b = test.dir.join("b.json");
+        assert_ne!(macros_path(&save_a), macros_path(&save_b));
+        assert_eq!(macros_path(&save_b), test.dir.join("b.macros.json"));
+
+        let app_a = CliDiceTrayApp::new(save_a.clone());
+        app_a.add_macro("attack", "1d20", None).unwrap();
+        assert!(load_macros(&save_a).unwrap().get("attack").is_some());
+        assert!(load_macros(&save_b).unwrap().get("attack").is_none());
+    }
+
+    #[test]
+    fn macro_run_in_one_workspace_never_touches_a_tray_in_another() {
+        let test = TestDir::new("macro-workspaces");
+        let mut app_a = CliDiceTrayApp::new(test.workspace("a"));
+        app_a.add_macro("attack", "1d20", None).unwrap();
+        app_a.run_macro("attack", &[], None).unwrap();
+        app_a.run_macro("attack", &[], None).unwrap();
+
+        let mut app_b = CliDiceTrayApp::new(test.workspace("b"));
+        app_b.new_tray("attack").unwrap();
+        app_b.add_dice_from_raw(Some("attack"), 2, 6, None);
+        let before = faces(&app_b, "attack");
+
+        assert!(app_b.run_macro("attack", &[], None).is_err());
+        app_b.add_macro("attack", "1d20", None).unwrap();
+        assert!(app_b.run_macro("attack", &[], None).is_err());
+        assert_eq!(faces(&app_b, "attack"), before);
+    }
+
+    #[test]
+    fn migrated_saves_keep_a_backup_of_the_old_file() {
+        let test = TestDir::new("migration-backup");
+        let save_file = test.workspace("a");
+        let old_save = r#"{"trays":[{"label":"Main","dice_data":[{"Die32":{"rng_seed":1,"label":"d6","faces":6,"current_face":6,"current_result":{"Number":4294967295},"current_result_type":"Sum"}}]}]}"#;
+        std::fs::write(&save_file, old_save).unwrap();
+
+        let mut app = CliDiceTrayApp::new(save_file.clone());
+        app.init();
+        let backup_file = test.dir.join("a").join("dice_tray_save.v1.backup.json");
+        assert_eq!(std::fs::read_to_string(&backup_file).unwrap(), old_save);
+        assert_eq!(faces(&app, "Main"), vec![(6, 6)]);
+        assert!(matches!(app.get_tray(Some("Main")).unwrap().get_result(), TrayResult::Number(result) if result == u32::MAX as i64));
+    }
+
+    #[test]
+    fn current_saves_are_not_backed_up() {
+        let test = TestDir::new("no-migration-backup");
+        let save_file = test.workspace("a");
+        std::fs::write(&save_file, r#"{"version":2,"data":{"trays":[]}}"#).unwrap();
+
+        let mut app = CliDiceTrayApp::new(save_file);
+        app.init();
+        assert!(!test.dir.join("a").join("dice_tray_save.v2.backup.json").exists());
+    }
+
+    #[test]
+    fn changes_are_written_aside_when_the_save_changed_during_the_run() {
+        let test = TestDir::new("save-conflict");
+        let save_file = test.workspace("a");
+        std::fs::write(&save_file, r#"{"version":2,"data":{"trays":[]}}"#).unwrap();
+        let mut app = CliDiceTrayApp::new(save_file.clone());
+        app.init();
+        app.new_tray("attack").unwrap();
+
+        let edited = r#"{"version":2,"data":{"trays":[],"decks":[]}}"#;
+        std::fs::write(&save_file, edited).unwrap();
+        assert!(app.save_to_file().is_err());
+        assert_eq!(std::fs::read_to_string(&save_file).unwrap(), edited);
+        assert!(list_backups(&save_file).is_empty());
+
+        let conflict = std::fs::read_to_string(conflict_path(&save_file)).unwrap();
+        let loaded = CliSaveData::from_json(&conflict).unwrap();
+        assert!(loaded.get_data().get_trays().iter().any(|tray| tray.get_label() == "attack"));
+    }
+}
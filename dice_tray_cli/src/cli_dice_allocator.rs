@@ -1,6 +1,6 @@
 use crate::cli_dice_tray::CliTray;
 use rust_dice::dice::Die;
-use rust_dice::dice_allocator::{DiceAllocator, DieIdGenerator, new_die};
+use rust_dice::dice_allocator::{DiceAllocator, DieIdGenerator, new_die, new_seeded_die};
 use rust_dice::dice_data::TypedDieData;
 use rust_dice::dice_profile::DieProfile;
 use rust_dice::tray::Tray;
@@ -11,7 +11,7 @@ pub struct CliDiceAllocator {
 
 impl DiceAllocator for CliDiceAllocator {
     fn new_die(&mut self, profile: &DieProfile) -> Result<Box<dyn Die>, String> {
-        let new_die = new_die(self.id_gen.get_die_id()?, profile);
+        let new_die = new_die(self.id_gen.get_die_id()?, profile)?;
         Ok(Box::new(new_die))
     }
 
@@ -33,4 +33,10 @@ impl CliDiceAllocator {
             id_gen: DieIdGenerator::new(),
         }
     }
+
+    ///Creates a new die from a profile with its RNG started from the seed, i.e. to rebuild a die from a share code.
+    pub fn new_seeded_die(&mut self, profile: &DieProfile, seed: u64) -> Result<Box<dyn Die>, String> {
+        let new_die = new_seeded_die(self.id_gen.get_die_id()?, profile, seed)?;
+        Ok(Box::new(new_die))
+    }
 }
@@ -1,5 +1,7 @@
-use rust_dice::dice::{Die, DieResultType};
+use rust_dice::commitment::SeedCommitment;
+use rust_dice::dice::{Die, DieMode, DieResultType, StepDirection, StepLadder};
 use rust_dice::dice_data::{DieData, DieData32, TypedDieData};
+use rust_dice::patterns::FaceAnalysis;
 use rust_dice::tray::{Tray, TrayResult, TrayResultType};
 
 use std::fmt::Write;
@@ -8,6 +10,10 @@ use std::fmt::Write;
 pub struct CliTrayData {
     label: String,
     dice_data: Vec<TypedDieData>,
+    #[serde(default)]
+    modifier: i64,
+    #[serde(default)]
+    result_type: TrayResultType,
 }
 
 impl From<&dyn Tray> for CliTrayData {
@@ -21,6 +27,8 @@ impl From<&dyn Tray> for CliTrayData {
         CliTrayData {
             label: tray.get_id().to_string(),
             dice_data,
+            modifier: tray.get_modifier(),
+            result_type: *tray.get_result_type(),
         }
     }
 }
@@ -35,12 +43,23 @@ impl CliTrayData {
     pub fn get_dice_data(&self) -> Vec<TypedDieData> {
         self.dice_data.clone()
     }
+
+    /// Get the tray modifier. Saves from before modifiers existed load with a modifier of 0.
+    pub fn get_modifier(&self) -> i64 {
+        self.modifier
+    }
+
+    /// Get the tray result type. Saves from before result types were saved load as Sum.
+    pub fn get_result_type(&self) -> TrayResultType {
+        self.result_type
+    }
 }
 
 pub struct CliTray {
     id: String,
     dice: Vec<Box<dyn Die>>,
     tray_result_type: TrayResultType,
+    modifier: i64,
 }
 
 impl CliTray {
@@ -50,6 +69,7 @@ impl CliTray {
             id,
             dice: Vec::new(),
             tray_result_type: TrayResultType::Sum, // default result type
+            modifier: 0,
         }
     }
 }
@@ -98,10 +118,10 @@ impl Tray for CliTray {
             i -= 1;
         }
 
-        return Err(format![
+        Err(format![
             "No die with ID: {} found in tray. No die has been removed.",
             id
-        ]);
+        ])
     }
 
     /// Removes all Dice with the specified label from the tray. Returns all Dice removed.
@@ -124,17 +144,26 @@ impl Tray for CliTray {
         Ok(removed_dice)
     }
 
-    /// Rolls all Dice in the tray.
+    /// Rolls all Dice in the tray, skipping held and exhausted dice.
     fn roll_all(&mut self, result_type: Option<DieResultType>) {
-        for die in &mut self.dice {
+        for die in self.dice.iter_mut().filter(|die| die.can_roll()) {
             die.roll(result_type);
         }
     }
 
-    /// Rolls the Die at the specified index in the tray.
+    /// Rolls the Die at the specified index in the tray, unless it is held.
     fn roll_at(&mut self, index: usize, result_type: Option<DieResultType>) -> Result<(), String> {
         if index < self.dice.len() {
             let die = &mut self.dice[index];
+            if die.is_held() {
+                return Err(format!("Die at index {} is held. Release it to roll it.", index));
+            }
+            if die.is_exhausted() {
+                return Err(format!("Usage die at index {} is exhausted.", index));
+            }
+            if !die.get_mode().allows_roll() {
+                return Err(format!("Counter die at index {} is locked. Unlock it to roll it.", index));
+            }
             die.roll(result_type);
             Ok(())
         } else {
@@ -142,18 +171,166 @@ impl Tray for CliTray {
         }
     }
 
-    /// Rolls all Dice in the tray with the specified label
+    /// Rolls all Dice in the tray with the specified label, skipping held and exhausted dice.
     fn roll_by_label(
         &mut self,
         label: &str,
         result_type: Option<DieResultType>,
     ) -> Result<(), String> {
         let mut hit: bool = false;
+        let mut rolled: bool = false;
         for die in self.dice.iter_mut() {
             if label == die.get_label() {
-                die.roll(result_type);
                 hit = true;
+                if die.can_roll() {
+                    die.roll(result_type);
+                    rolled = true;
+                }
+            }
+        }
+        if !hit {
+            Err("No dice with the specified identity found".to_string())
+        } else if !rolled {
+            Err(format!("Every die labeled {} is held, exhausted or a locked counter.", label))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Holds or releases the Die at the specified index in the tray.
+    fn set_held_at(&mut self, index: usize, held: bool) -> Result<(), String> {
+        match self.dice.get_mut(index) {
+            Some(die) => {
+                die.set_held(held);
+                Ok(())
+            }
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Holds or releases all Dice in the tray with the specified label.
+    fn set_held_by_label(&mut self, label: &str, held: bool) -> Result<(), String> {
+        let mut hit: bool = false;
+        for die in self.dice.iter_mut().filter(|die| die.get_label() == label) {
+            die.set_held(held);
+            hit = true;
+        }
+        if hit {
+            Ok(())
+        } else {
+            Err("No dice with the specified identity found".to_string())
+        }
+    }
+
+    /// Steps the Die at the specified index in the tray up or down the ladder.
+    fn step_at(&mut self, index: usize, direction: StepDirection, ladder: &StepLadder) -> Result<u32, String> {
+        match self.dice.get_mut(index) {
+            Some(die) => Ok(step_die(die.as_mut(), direction, ladder)),
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Steps all Dice in the tray with the specified label up or down the ladder.
+    fn step_by_label(&mut self, label: &str, direction: StepDirection, ladder: &StepLadder) -> Result<(), String> {
+        let mut hit: bool = false;
+        for die in self.dice.iter_mut().filter(|die| die.get_label() == label) {
+            step_die(die.as_mut(), direction, ladder);
+            hit = true;
+        }
+        if hit {
+            Ok(())
+        } else {
+            Err("No dice with the specified identity found".to_string())
+        }
+    }
+
+    /// Sets the mode of the Die at the specified index in the tray.
+    fn set_mode_at(&mut self, index: usize, mode: DieMode) -> Result<(), String> {
+        match self.dice.get_mut(index) {
+            Some(die) => {
+                die.set_mode(mode);
+                Ok(())
             }
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Sets the mode of all Dice in the tray with the specified label.
+    fn set_mode_by_label(&mut self, label: &str, mode: DieMode) -> Result<(), String> {
+        let mut hit: bool = false;
+        for die in self.dice.iter_mut().filter(|die| die.get_label() == label) {
+            die.set_mode(mode.clone());
+            hit = true;
+        }
+        if hit {
+            Ok(())
+        } else {
+            Err("No dice with the specified identity found".to_string())
+        }
+    }
+
+    /// Records a face by hand on the Die at the specified index in the tray.
+    fn record_face_at(&mut self, index: usize, face: u32) -> Result<(), String> {
+        match self.dice.get_mut(index) {
+            Some(die) => die.record_face(face),
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Records a face by hand on all Dice in the tray with the specified label.
+    /// Every die is checked first, so no die is changed if any of them can't take the face.
+    fn record_face_by_label(&mut self, label: &str, face: u32) -> Result<(), String> {
+        let mut hit: bool = false;
+        for die in self.dice.iter().filter(|die| die.get_label() == label) {
+            die.check_face(face)?;
+            hit = true;
+        }
+        if !hit {
+            return Err("No dice with the specified identity found".to_string());
+        }
+        for die in self.dice.iter_mut().filter(|die| die.get_label() == label) {
+            die.record_face(face)?;
+        }
+        Ok(())
+    }
+
+    /// Commits the Die at the specified index in the tray to a new seed.
+    fn commit_seed_at(&mut self, index: usize) -> Result<String, String> {
+        match self.dice.get_mut(index) {
+            Some(die) => Ok(die.commit_seed()?.get_hash().to_string()),
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Reveals the seed commitment of the Die at the specified index in the tray.
+    fn reveal_seed_at(&mut self, index: usize) -> Result<SeedCommitment, String> {
+        match self.dice.get_mut(index) {
+            Some(die) => {
+                let label = die.get_label().to_string();
+                die.reveal_seed()
+                    .ok_or(format!("{} at index {} has no seed commitment to reveal.", label, index))
+            }
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Turns the Die at the specified index in the tray by amount faces.
+    fn count_at(&mut self, index: usize, amount: i64) -> Result<(), String> {
+        match self.dice.get_mut(index) {
+            Some(die) => {
+                die.count(amount);
+                Ok(())
+            }
+            None => Err(format!("No dice found at provided index: {}.", index)),
+        }
+    }
+
+    /// Turns all Dice in the tray with the specified label by amount faces.
+    fn count_by_label(&mut self, label: &str, amount: i64) -> Result<(), String> {
+        let mut hit: bool = false;
+        for die in self.dice.iter_mut().filter(|die| die.get_label() == label) {
+            die.count(amount);
+            hit = true;
         }
         if hit {
             Ok(())
@@ -169,9 +346,7 @@ impl Tray for CliTray {
     fn remove_all(&mut self) -> Result<Vec<Box<dyn Die>>, String> {
         let dice: Vec<Box<dyn Die>> = self.dice.drain(..).collect();
         if dice.is_empty() {
-            return Err(format![
-                "Tray is already empty, no dice returned by remove_all."
-            ]);
+            return Err("Tray is already empty, no dice returned by remove_all.".to_string());
         }
         Ok(dice)
     }
@@ -190,26 +365,26 @@ impl Tray for CliTray {
         &self.tray_result_type
     }
 
+    fn set_result_type(&mut self, result_type: TrayResultType) {
+        self.tray_result_type = result_type;
+    }
+
     fn get_result(&self) -> TrayResult {
-        match self.tray_result_type {
-            TrayResultType::Sum => {
-                let sum: u32 = self
-                    .dice
-                    .iter()
-                    .map(|die| die.get_result().is_num_or(0))
-                    .sum();
-                TrayResult::Number(sum)
-            }
+        let result = match self.tray_result_type {
+            TrayResultType::Sum => self
+                .dice
+                .iter()
+                .try_fold(0i64, |sum, die| sum.checked_add(die.get_result().is_num_or(0))),
             TrayResultType::Best => {
                 let best = self
                     .dice
                     .iter()
                     .map(|die| die.get_result().is_num_or(0))
                     .max();
-                match best {
-                    Some(value) => TrayResult::Number(value),
-                    None => TrayResult::None,
+                if best.is_none() {
+                    return TrayResult::None;
                 }
+                best
             }
             TrayResultType::Worst => {
                 let worst = self
@@ -217,19 +392,81 @@ impl Tray for CliTray {
                     .iter()
                     .map(|die| die.get_result().is_num_or(0))
                     .min();
-                match worst {
-                    Some(value) => TrayResult::Number(value),
-                    None => TrayResult::None,
+                if worst.is_none() {
+                    return TrayResult::None;
+                }
+                worst
+            }
+            TrayResultType::Pattern => {
+                if self.dice.is_empty() {
+                    return TrayResult::None;
                 }
+                //Patterns are read from the faces, so the modifier doesn't apply.
+                return TrayResult::String(FaceAnalysis::from_dice(&self.dice).best_pattern().to_string());
             }
+        };
+
+        match result.and_then(|value| value.checked_add(self.modifier)) {
+            Some(value) => TrayResult::Number(value),
+            None => TrayResult::Overflow,
         }
     }
 
+    fn get_modifier(&self) -> i64 {
+        self.modifier
+    }
+
+    fn set_modifier(&mut self, modifier: i64) {
+        self.modifier = modifier;
+    }
+
     fn get_summary(&self) -> String {
         let mut summary_string = String::new();
         for die in self.dice.iter().enumerate(){
             write!(summary_string, "@{}:{}", die.0, die.1.get_summary()).unwrap()
         }   
+        if self.modifier != 0 {
+            write!(summary_string, "{:+}", self.modifier).unwrap()
+        }
         summary_string
     }
-}
\ No newline at end of file
+}
+fn step_die(die: &mut dyn Die, direction: StepDirection, ladder: &StepLadder) -> u32 {
+    match direction {
+        StepDirection::Up => die.step_up(ladder),
+        StepDirection::Down => die.step_down(ladder),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_dice::dice::Die32;
+
+    fn sum_die(id: usize, result: i64) -> Box<dyn Die> {
+        let json = format!(
+            r#"{{"Die32":{{"rng_seed":1,"label":"d6","faces":6,"current_face":6,"current_result":{{"Number":{}}},"current_result_type":"Sum"}}}}"#,
+            result
+        );
+        serde_json::from_str::<TypedDieData>(&json).unwrap().to_die(id)
+    }
+
+    #[test]
+    fn tray_sum_overflows_instead_of_wrapping() {
+        let mut tray = CliTray::new("Main".to_string());
+        tray.add_die(sum_die(0, i64::MAX));
+        assert!(matches!(tray.get_result(), TrayResult::Number(i64::MAX)));
+        tray.add_die(sum_die(1, 1));
+        assert!(matches!(tray.get_result(), TrayResult::Overflow));
+    }
+
+    #[test]
+    fn modifier_overflows_instead_of_wrapping() {
+        let mut tray = CliTray::new("Main".to_string());
+        tray.add_die(Box::new(Die32::from_seed(0, None, 6, None, 1)));
+        tray.set_modifier(i64::MAX);
+        assert!(matches!(tray.get_result(), TrayResult::Overflow));
+        tray.set_modifier(i64::MIN);
+        assert!(matches!(tray.get_result(), TrayResult::Number(_)));
+    }
+}
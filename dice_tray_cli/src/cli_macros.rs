@@ -0,0 +1,176 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::save_file::write_atomic;
+
+///A named dice command that can be run as if it were a dicetray command, i.e. "dicetray attack".
+///Commands can contain parameters like "$bonus" or "${level}d8", which are filled in by the arguments given when the macro is run.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RollMacro {
+    command: String,
+    tray: Option<String>,
+    ///The expanded command last used to build the macro's dice. If the expansion changes the dice are rebuilt, otherwise they are rerolled.
+    #[serde(default)]
+    last_expansion: Option<String>,
+    ///The tray the macro last built its dice in. Macros only clear or reroll trays they built.
+    #[serde(default)]
+    last_tray: Option<String>,
+}
+
+impl RollMacro {
+    ///Creates a new macro for the dice command, optionally bound to a tray.
+    pub fn new(command: String, tray: Option<String>) -> Self {
+        RollMacro {
+            command,
+            tray,
+            last_expansion: None,
+            last_tray: None,
+        }
+    }
+
+    ///Gets the unexpanded dice command of the macro.
+    pub fn get_command(&self) -> &str {
+        &self.command
+    }
+
+    ///Gets the tray the macro was bound to when it was created, if any.
+    pub fn get_tray(&self) -> Option<&str> {
+        self.tray.as_deref()
+    }
+
+    ///Gets the expanded command last used to build the macro's dice.
+    pub fn get_last_expansion(&self) -> Option<&str> {
+        self.last_expansion.as_deref()
+    }
+
+    ///Gets the tray the macro last built its dice in. Macros saved before this was tracked only built in their default tray.
+    pub fn get_last_tray(&self) -> Option<&str> {
+        self.last_tray.as_deref()
+    }
+
+    ///Records the expanded command and tray the macro's dice were last built with.
+    pub fn set_last_build(&mut self, expansion: String, tray: String) {
+        self.last_expansion = Some(expansion);
+        self.last_tray = Some(tray);
+    }
+
+    ///Returns the names of the parameters in the command, in the order they first appear.
+    pub fn get_parameters(&self) -> Vec<&str> {
+        let mut parameters: Vec<&str> = Vec::new();
+        for (start, _) in self.command.match_indices('$') {
+            let (name, _) = parameter_at(&self.command[start + 1..]);
+            if !name.is_empty() && !parameters.contains(&name) {
+                parameters.push(name);
+            }
+        }
+        parameters
+    }
+
+    ///Replaces each parameter with the matching argument. Arguments are matched to parameters in the order the parameters first appear.
+    pub fn expand(&self, args: &[String]) -> Result<String, String> {
+        let parameters = self.get_parameters();
+        if args.len() != parameters.len() {
+            return Err(format!(
+                "Macro expects {} argument(s) ({}) but {} were given.",
+                parameters.len(),
+                parameters
+                    .iter()
+                    .map(|p| format!("${}", p))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                args.len()
+            ));
+        }
+
+        let mut expanded = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find('$') {
+            expanded.push_str(&rest[..start]);
+            let (name, length) = parameter_at(&rest[start + 1..]);
+            match parameters.iter().position(|p| *p == name) {
+                Some(index) => expanded.push_str(&args[index]),
+                None => expanded.push('$'),
+            }
+            rest = &rest[start + 1 + length..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+///Reads a parameter name from the start of the string, returning the name and the number of bytes it takes up.
+///Names are made of ascii letters, digits and underscores, and can be wrapped in braces to run into other text i.e. "${level}d8".
+fn parameter_at(s: &str) -> (&str, usize) {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    if let Some(braced) = s.strip_prefix('{')
+        && let Some(end) = braced.find('}')
+        && braced[..end].chars().all(is_name_char)
+    {
+        return (&braced[..end], end + 2);
+    }
+    let end = s.find(|c: char| !is_name_char(c)).unwrap_or(s.len());
+    (&s[..end], end)
+}
+
+///The macro store is kept next to the save file and named after it, i.e. campaign.macros.json, so every save file and workspace has its own.
+///The default dice_tray_save.json keeps the dice_tray_macros.json name it has always used.
+pub fn macros_path(save_file: &Path) -> PathBuf {
+    match save_file.file_stem().and_then(|stem| stem.to_str()) {
+        Some("dice_tray_save") | None => save_file.with_file_name("dice_tray_macros.json"),
+        Some(_) => save_file.with_extension("macros.json"),
+    }
+}
+
+///The persisted set of macros, kept in insertion order so "macro list" shows them in the order they were added.
+#[derive(Serialize, Deserialize, Default)]
+pub struct MacroStore {
+    macros: IndexMap<String, RollMacro>,
+}
+
+impl MacroStore {
+    ///Loads the macro store from the file. A missing file is an empty store.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(MacroStore::default());
+        }
+        let file_content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let json_content = serde_json::to_string_pretty(self)?;
+        write_atomic(path, &json_content)?;
+        Ok(())
+    }
+
+    ///Adds a macro, replacing any macro with the same name. Returns true if a macro was replaced.
+    pub fn add(&mut self, name: String, roll_macro: RollMacro) -> bool {
+        self.macros.insert(name, roll_macro).is_some()
+    }
+
+    ///Removes the macro with the given name, or returns an error if there isn't one.
+    pub fn remove(&mut self, name: &str) -> Result<RollMacro, String> {
+        self.macros
+            .shift_remove(name)
+            .ok_or(format!("No macro named {} found.", name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RollMacro> {
+        self.macros.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut RollMacro> {
+        self.macros.get_mut(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RollMacro)> {
+        self.macros.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+}
@@ -1,21 +1,35 @@
 use regex::Regex;
+use rust_dice::tables::MAX_EXPRESSION_DICE;
 use std::sync::LazyLock;
 
 static DICE_NOTATION_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(?i)^(\d*)?[d](\d+)$").unwrap());
+    LazyLock::new(|| Regex::new(r"(?i)^(\d*)?[d](\d+)([+-]\d+)?$").unwrap());
 
-pub fn parse_dice_notation(command: &str) -> Result<Vec<(u32, u32)>, String> {
+static MODIFIER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[+-]\d+$").unwrap());
+
+///Dice and a flat modifier parsed from a dice command. i.e. "2d6 d20+5 -1" = two six-sided dice, a twenty-sided die and a modifier of +4.
+pub struct DiceCommand {
+    pub dice: Vec<(u32, u32)>,
+    pub modifier: i64,
+}
+
+///A command can't hold more than MAX_EXPRESSION_DICE dice in total, so a typo like "4000000000d6" fails instead of hanging the CLI.
+pub fn parse_dice_notation(command: &str) -> Result<DiceCommand, String> {
     let split_command = command.split_whitespace();
     let mut dice_vec = Vec::new();
+    let mut modifier: i64 = 0;
+    let mut dice_count: u64 = 0;
 
     for part in split_command {
-        if let Some(captures) = DICE_NOTATION_REGEX.captures(part) {
+        if MODIFIER_REGEX.is_match(part) {
+            modifier = add_modifier(modifier, part)?;
+        } else if let Some(captures) = DICE_NOTATION_REGEX.captures(part) {
             let count = match captures.get(1) {
                 Some(m) => {
                     if m.as_str().is_empty() {
                         1
                     } else {
-                        m.as_str().parse::<u32>().unwrap_or(1)
+                        m.as_str().parse::<u32>().unwrap_or(u32::MAX)
                     }
                 }
                 None => 1,
@@ -26,17 +40,41 @@ pub fn parse_dice_notation(command: &str) -> Result<Vec<(u32, u32)>, String> {
                 None => 6,
             };
 
+            if faces == 0 {
+                return Err(format!("{} has no faces. Dice need at least 1 face.", part));
+            }
+
+            dice_count += count as u64;
+            if dice_count > MAX_EXPRESSION_DICE as u64 {
+                return Err(format!("{} is more than {} dice. Roll fewer dice at once.", command.trim(), MAX_EXPRESSION_DICE));
+            }
+
+            if let Some(m) = captures.get(3) {
+                modifier = add_modifier(modifier, m.as_str())?;
+            }
+
             dice_vec.push((count, faces));
         }
     }
 
-    if dice_vec.is_empty() {
+    if dice_vec.is_empty() && modifier == 0 {
         Err("No dice notation found in dice command.".to_string())
     } else {
-        Ok(dice_vec)
+        Ok(DiceCommand {
+            dice: dice_vec,
+            modifier,
+        })
     }
 }
 
+///Adds a signed modifier string like "+5" or "-2" to the running modifier, failing rather than overflowing.
+fn add_modifier(modifier: i64, part: &str) -> Result<i64, String> {
+    part.parse::<i64>()
+        .ok()
+        .and_then(|m| modifier.checked_add(m))
+        .ok_or(format!("Modifier {} is too large.", part))
+}
+
 pub enum DiceTargets {
     Index(Vec<usize>),
     Label(String),
@@ -69,3 +107,45 @@ pub fn parse_dice_targets(command: &str) -> Result<Vec<DiceTargets>, String> {
         Ok(targets)
     }
 }
+
+///Parses comma separated tokens with optional counts i.e. "skull:2, -1:3, elder sign" = 2 skulls, 3 "-1"s and an elder sign.
+pub fn parse_tokens(command: &str) -> Result<Vec<(String, u32)>, String> {
+    let mut tokens = Vec::new();
+
+    for part in command.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+        let token = match part.rsplit_once(':') {
+            Some((label, count)) => match count.trim().parse::<u32>() {
+                Ok(count) => (label.trim().to_string(), count),
+                Err(_) => return Err(format!("Invalid token count in {}", part)),
+            },
+            None => (part.to_string(), 1),
+        };
+        tokens.push(token);
+    }
+
+    if tokens.is_empty() {
+        Err("No tokens found in command.".to_string())
+    } else {
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dice_and_modifiers() {
+        let command = parse_dice_notation("2d6 d20+5 -1").unwrap();
+        assert_eq!(command.dice, vec![(2, 6), (1, 20)]);
+        assert_eq!(command.modifier, 4);
+    }
+
+    #[test]
+    fn dice_counts_are_capped() {
+        assert!(parse_dice_notation("1000d6").is_ok());
+        assert!(parse_dice_notation("4000000000d6").is_err());
+        assert!(parse_dice_notation("99999999999d6").is_err());
+        assert!(parse_dice_notation("600d6 600d8").is_err());
+    }
+}
@@ -1,6 +1,17 @@
-use cli_table::{Table, WithTitle, format::Justify, print_stdout};
+use cli_table::{Cell, Table, WithTitle, format::Justify, print_stdout};
+use rust_dice::bag::Bag;
+use rust_dice::commitment::SeedCommitment;
+use rust_dice::deck::{DRAW_PILE, Deck};
 use rust_dice::dice::{Die, DieResult, DieResultType};
+use rust_dice::patterns::FaceAnalysis;
+use rust_dice::statistics::DieStats;
+use rust_dice::tables::{DiceResultTable, TableRoll};
 use rust_dice::tray::Tray;
+use rust_dice::yahtzee::{ScoreCategory, YahtzeeGame};
+use std::time::SystemTime;
+
+use crate::roll_log::{RollLogEntry, format_timestamp};
+use crate::save_file::SaveBackup;
 
 #[derive(Table)]
 struct DetailedDiceState {
@@ -16,6 +27,10 @@ struct DetailedDiceState {
     result_type_string: String,
     #[table(title = "Result", justify = "Justify::Center")]
     result_string: String,
+    #[table(title = "Held", justify = "Justify::Center")]
+    held_string: String,
+    #[table(title = "Steps Left", justify = "Justify::Center")]
+    steps_string: String,
 }
 
 /// Logs the current state of the tray to the console. In table format. Using cli-table crate.
@@ -28,28 +43,60 @@ pub fn detailed_log_tray(tray: &dyn Tray) {
             index: i,
             label: die.get_label().to_string(),
             faces_string: die.get_face_count().to_string(),
-            current_face_string: die.get_current_face().to_string(),
+            current_face_string: if die.is_manual() {
+                format!("{} (set)", die.get_current_face())
+            } else {
+                die.get_current_face().to_string()
+            },
             result_type_string: die_result_type_to_string(die.as_ref()),
             result_string: die_result_to_string(die.as_ref()),
+            held_string: die_held_to_string(die.as_ref()),
+            steps_string: die_steps_to_string(die.as_ref()),
         })
         .collect();
 
     println!("Showing dice in tray: {}", tray.get_id());
     print_stdout(dice_states.with_title()).unwrap();
 
-    println!(
-        "{} = {}",
-        tray.get_result_type().to_string(),
-        tray.get_result().to_string()
-    );
+    if tray.get_modifier() != 0 {
+        println!("Modifier = {:+}", tray.get_modifier());
+    }
+    println!("{} = {}", tray.get_result_type(), tray.get_result());
 }
 
-/// Converts a DieResult to a String for logging.
+/// Converts a DieResult to a String for logging. Saturated sums are flagged so they aren't mistaken for real totals.
 fn die_result_to_string(die: &dyn Die) -> String {
-    match die.get_result() {
+    let result = match die.get_result() {
         DieResult::Number(n) => n.to_string(),
         DieResult::String(s) => s.clone(),
         DieResult::None => "None".to_string(),
+    };
+    if die.has_overflowed() {
+        format!("{} (overflow)", result)
+    } else {
+        result
+    }
+}
+
+/// Flags held dice, and counters that are locked against rolling.
+fn die_held_to_string(die: &dyn Die) -> String {
+    if die.is_held() {
+        "Held".to_string()
+    } else if !die.get_mode().allows_roll() {
+        "Counter".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Shows how many times a usage die can step down before it runs out. Blank for other dice.
+fn die_steps_to_string(die: &dyn Die) -> String {
+    if die.is_exhausted() {
+        return "Exhausted".to_string();
+    }
+    match die.get_mode().steps_remaining(die.get_face_count()) {
+        Some(steps) => steps.to_string(),
+        None => String::new(),
     }
 }
 
@@ -62,3 +109,466 @@ fn die_result_type_to_string(die: &dyn Die) -> String {
         DieResultType::Sum => "Sum".to_string(),
     }
 }
+
+#[derive(Table)]
+struct TableEntryState {
+    #[table(title = "Roll", justify = "Justify::Center")]
+    roll: String,
+    #[table(title = "Result")]
+    text: String,
+    #[table(title = "Then Roll On", justify = "Justify::Center")]
+    reference: String,
+    #[table(title = "Drawn", justify = "Justify::Center")]
+    drawn: String,
+}
+
+/// Logs every entry of a result table to the console in table format.
+pub fn log_result_table(table: &DiceResultTable) {
+    let entries: Vec<TableEntryState> = table
+        .get_entries()
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| TableEntryState {
+            roll: entry.get_range_string(),
+            text: entry.get_text().to_string(),
+            reference: entry.get_reference().unwrap_or("").to_string(),
+            drawn: if table.is_drawn(i) { "Drawn".to_string() } else { "".to_string() },
+        })
+        .collect();
+
+    println!(
+        "Showing table: {} (rolls {}-{})",
+        table.get_name(),
+        table.get_min_face(),
+        table.get_max_face()
+    );
+    print_stdout(entries.with_title()).unwrap();
+
+    if let Some(deck) = table.get_deck() {
+        println!(
+            "Deck: {} of {} entries left{}",
+            table.get_entries().len() - deck.get_drawn_count(),
+            table.get_entries().len(),
+            if deck.is_auto_reshuffle() { ", reshuffles when drawn out" } else { "" }
+        );
+    }
+}
+
+/// Logs a table roll, including every dice expression rolled and every nested table rolled on.
+pub fn log_table_roll(table_roll: &TableRoll) {
+    for (depth, roll) in table_roll.chain().iter().enumerate() {
+        let indent = "  ".repeat(depth);
+        println!("{}{} ({}): {}", indent, roll.table, roll.face, roll.text);
+        for dice_roll in roll.dice_rolls.iter() {
+            let faces: Vec<String> = dice_roll.faces.iter().map(|f| f.to_string()).collect();
+            println!(
+                "{}  {} rolled [{}] = {}",
+                indent,
+                dice_roll.expression,
+                faces.join(", "),
+                dice_roll.total
+            );
+        }
+    }
+}
+
+#[derive(Table)]
+struct PileState {
+    #[table(title = "Pile", justify = "Justify::Center")]
+    name: String,
+    #[table(title = "Count", justify = "Justify::Center")]
+    count: usize,
+    #[table(title = "Cards (top last)")]
+    cards: String,
+}
+
+/// Logs the piles of a deck in table format. The draw pile is face down, so its cards are hidden.
+pub fn log_deck(deck: &Deck) {
+    let piles: Vec<PileState> = deck
+        .get_piles()
+        .iter()
+        .map(|pile| PileState {
+            name: pile.get_name().to_string(),
+            count: pile.get_cards().len(),
+            cards: if pile.get_name() == DRAW_PILE {
+                "(face down)".to_string()
+            } else {
+                pile.get_cards().join(", ")
+            },
+        })
+        .collect();
+
+    println!("Showing deck: {}", deck.get_label());
+    print_stdout(piles.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct TokenState {
+    #[table(title = "Token", justify = "Justify::Center")]
+    label: String,
+    #[table(title = "In Bag", justify = "Justify::Center")]
+    in_bag: u32,
+    #[table(title = "Drawn", justify = "Justify::Center")]
+    drawn: u32,
+    #[table(title = "Draw Chance", justify = "Justify::Center")]
+    chance: String,
+}
+
+/// Logs the composition of a bag in table format, with the chance of drawing each token next.
+pub fn log_bag(bag: &Bag) {
+    let in_bag_count = bag.get_in_bag_count();
+    let tokens: Vec<TokenState> = bag
+        .get_tokens()
+        .iter()
+        .map(|token| TokenState {
+            label: token.get_label().to_string(),
+            in_bag: token.get_in_bag(),
+            drawn: token.get_drawn(),
+            chance: if in_bag_count == 0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}%", token.get_in_bag() as f64 * 100.0 / in_bag_count as f64)
+            },
+        })
+        .collect();
+
+    println!("Showing bag: {}", bag.get_label());
+    print_stdout(tokens.with_title()).unwrap();
+}
+
+/// Logs every pattern found in a group of faces, best first, followed by the One-Roll Engine sets and waste dice.
+pub fn log_patterns(analysis: &FaceAnalysis) {
+    let faces: Vec<String> = analysis.get_faces().iter().map(|face| face.to_string()).collect();
+    println!("Faces: {}", faces.join(" "));
+
+    let patterns = analysis.patterns();
+    if patterns.is_empty() {
+        println!("No patterns found.");
+    }
+    for pattern in patterns {
+        println!("  {}", pattern);
+    }
+
+    let sets: Vec<String> = analysis.sets().iter().map(|set| set.to_string()).collect();
+    let waste: Vec<String> = analysis.waste().iter().map(|face| face.to_string()).collect();
+    println!("ORE sets: {}", if sets.is_empty() { "none".to_string() } else { sets.join(", ") });
+    println!("Waste: {}", if waste.is_empty() { "none".to_string() } else { waste.join(" ") });
+}
+
+/// Logs every player's Yahtzee scorecard side by side, with the section totals and bonuses.
+pub fn log_scorecards(game: &YahtzeeGame) {
+    let cards = game.get_scorecards();
+    let mut title = vec!["Category".cell()];
+    title.extend(cards.iter().map(|card| card.get_player().cell().justify(Justify::Center)));
+
+    let score_row = |name: String, scores: Vec<String>| {
+        let mut row = vec![name.cell()];
+        row.extend(scores.into_iter().map(|score| score.cell().justify(Justify::Center)));
+        row
+    };
+
+    let mut rows = Vec::new();
+    for category in ScoreCategory::ALL {
+        let scores = cards
+            .iter()
+            .map(|card| card.get_score(category).map(|score| score.to_string()).unwrap_or_default())
+            .collect();
+        rows.push(score_row(category.to_string(), scores));
+        if category == ScoreCategory::Sixes {
+            let subtotals = cards.iter().map(|card| card.upper_subtotal().to_string()).collect();
+            rows.push(score_row("Upper Subtotal".to_string(), subtotals));
+            let bonuses = cards.iter().map(|card| card.upper_bonus().to_string()).collect();
+            rows.push(score_row("Upper Bonus".to_string(), bonuses));
+        }
+    }
+    let bonuses = cards.iter().map(|card| card.get_yahtzee_bonus().to_string()).collect();
+    rows.push(score_row("Yahtzee Bonus".to_string(), bonuses));
+    let totals = cards.iter().map(|card| card.total().to_string()).collect();
+    rows.push(score_row("Total".to_string(), totals));
+
+    print_stdout(rows.table().title(title)).unwrap();
+}
+
+#[derive(Table)]
+struct ScoreSuggestion {
+    #[table(title = "Category")]
+    category: String,
+    #[table(title = "Points", justify = "Justify::Center")]
+    points: u32,
+}
+
+/// Logs what the current dice would score in each open category, best first.
+pub fn log_yahtzee_suggestions(suggestions: &[(ScoreCategory, u32)]) {
+    let rows: Vec<ScoreSuggestion> = suggestions
+        .iter()
+        .map(|(category, points)| ScoreSuggestion {
+            category: category.to_string(),
+            points: *points,
+        })
+        .collect();
+    println!("Scoring options:");
+    print_stdout(rows.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct BackupState {
+    #[table(title = "Backup", justify = "Justify::Center")]
+    number: usize,
+    #[table(title = "Saved")]
+    saved: String,
+    #[table(title = "File")]
+    file: String,
+}
+
+/// Logs the backups of the save file, most recent first.
+pub fn log_backups(backups: &[SaveBackup]) {
+    if backups.is_empty() {
+        println!("No backups found. A backup is kept each time the save file is written.");
+        return;
+    }
+    let rows: Vec<BackupState> = backups
+        .iter()
+        .map(|backup| BackupState {
+            number: backup.get_number(),
+            saved: backup.get_modified().map(age_to_string).unwrap_or_else(|| "unknown".to_string()),
+            file: backup.get_path().display().to_string(),
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+    println!("Restore one with: dicetray restore <backup>");
+}
+
+/// Describes how long ago a time was, i.e. "5 minutes ago".
+fn age_to_string(time: SystemTime) -> String {
+    let seconds = SystemTime::now().duration_since(time).map(|age| age.as_secs()).unwrap_or(0);
+    match seconds {
+        0..60 => format!("{} seconds ago", seconds),
+        60..3600 => format!("{} minutes ago", seconds / 60),
+        3600..86400 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+#[derive(Table)]
+struct RollLogState {
+    #[table(title = "Time (UTC)")]
+    time: String,
+    #[table(title = "Tray")]
+    tray: String,
+    #[table(title = "Command")]
+    command: String,
+    #[table(title = "Dice")]
+    dice: String,
+}
+
+/// Logs entries from the roll log, oldest first. Results that differ from the face are shown after it, and faces set by hand are marked.
+pub fn log_roll_history(entries: &[RollLogEntry]) {
+    if entries.is_empty() {
+        println!("No rolls found.");
+        return;
+    }
+    let rows: Vec<RollLogState> = entries
+        .iter()
+        .map(|entry| RollLogState {
+            time: format_timestamp(entry.get_timestamp()),
+            tray: entry.get_tray().to_string(),
+            command: entry.get_command().to_string(),
+            dice: entry
+                .get_dice()
+                .iter()
+                .map(|die| {
+                    let mut text = format!("{}={}", die.get_label(), die.get_face());
+                    let result = die.get_result().to_string();
+                    if result != die.get_face().to_string() {
+                        text += &format!(" ({})", result);
+                    }
+                    if die.is_manual() {
+                        text += " (set)";
+                    }
+                    text
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct CommitmentState {
+    #[table(title = "Index", justify = "Justify::Center")]
+    index: usize,
+    #[table(title = "Label")]
+    label: String,
+    #[table(title = "Commitment (SHA-256)")]
+    hash: String,
+}
+
+/// Logs the commitment hashes of newly committed dice.
+pub fn log_commitments(committed: &[(usize, String, String)]) {
+    let rows: Vec<CommitmentState> = committed
+        .iter()
+        .map(|(index, label, hash)| CommitmentState {
+            index: *index,
+            label: label.clone(),
+            hash: hash.clone(),
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct RevealState {
+    #[table(title = "Label")]
+    label: String,
+    #[table(title = "Commitment (SHA-256)")]
+    hash: String,
+    #[table(title = "Seed")]
+    seed: u64,
+    #[table(title = "Rolls")]
+    rolls: String,
+}
+
+/// Logs revealed seed commitments with the faces rolled since each die was committed.
+pub fn log_reveals(revealed: &[SeedCommitment]) {
+    let rows: Vec<RevealState> = revealed
+        .iter()
+        .map(|commitment| RevealState {
+            label: commitment.get_label().to_string(),
+            hash: commitment.get_hash().to_string(),
+            seed: commitment.get_seed(),
+            rolls: rolls_to_string(commitment.get_rolls()),
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct VerificationState {
+    #[table(title = "Label")]
+    label: String,
+    #[table(title = "Rolls")]
+    rolls: String,
+    #[table(title = "Verified")]
+    verified: String,
+}
+
+/// Logs the result of verifying each revealed commitment.
+pub fn log_verification(results: &[(&SeedCommitment, Result<usize, String>)]) {
+    let rows: Vec<VerificationState> = results
+        .iter()
+        .map(|(commitment, result)| VerificationState {
+            label: commitment.get_label().to_string(),
+            rolls: rolls_to_string(commitment.get_rolls()),
+            verified: match result {
+                Ok(count) => format!("Yes, {} rolls", count),
+                Err(e) => format!("No: {}", e),
+            },
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+}
+
+/// The width of the longest bar in a statistics histogram.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Dice with up to this many faces get a histogram row per face.
+const HISTOGRAM_FACE_ROWS: u32 = 100;
+
+/// The number of face ranges in the histogram of a larger die.
+const HISTOGRAM_RANGES: u32 = 20;
+
+#[derive(Table)]
+struct DieStatsState {
+    #[table(title = "Dice")]
+    label: String,
+    #[table(title = "Size", justify = "Justify::Center")]
+    size: String,
+    #[table(title = "Rolls", justify = "Justify::Right")]
+    rolls: usize,
+    #[table(title = "Mean", justify = "Justify::Right")]
+    mean: String,
+    #[table(title = "Expected", justify = "Justify::Right")]
+    expected: String,
+    #[table(title = "Max Streak", justify = "Justify::Right")]
+    max_streak: usize,
+    #[table(title = "Min Streak", justify = "Justify::Right")]
+    min_streak: usize,
+    #[table(title = "Longest Without Max", justify = "Justify::Right")]
+    longest_without_max: usize,
+}
+
+/// Logs a row of statistics for each die, or each die size.
+pub fn log_die_stats(stats: &[(String, DieStats)]) {
+    let rows: Vec<DieStatsState> = stats
+        .iter()
+        .map(|(label, stats)| DieStatsState {
+            label: label.clone(),
+            size: format!("d{}", stats.get_faces()),
+            rolls: stats.get_roll_count(),
+            mean: stats.mean().map(|mean| format!("{:.2}", mean)).unwrap_or_else(|| "-".to_string()),
+            expected: format!("{:.2}", stats.expected_mean()),
+            max_streak: stats.get_max_streak(),
+            min_streak: stats.get_min_streak(),
+            longest_without_max: stats.get_longest_without_max(),
+        })
+        .collect();
+    print_stdout(rows.with_title()).unwrap();
+}
+
+#[derive(Table)]
+struct HistogramState {
+    #[table(title = "Face", justify = "Justify::Right")]
+    face: String,
+    #[table(title = "Count", justify = "Justify::Right")]
+    count: usize,
+    #[table(title = "Share", justify = "Justify::Right")]
+    share: String,
+    #[table(title = "Histogram")]
+    bar: String,
+}
+
+/// Logs how often each face was rolled, with a bar of # scaled to the most rolled face.
+/// Dice with more than HISTOGRAM_FACE_ROWS faces are shown as HISTOGRAM_RANGES ranges of faces instead.
+pub fn log_stats_histogram(stats: &DieStats) {
+    let total = stats.get_roll_count();
+    let buckets = if stats.get_faces() <= HISTOGRAM_FACE_ROWS {
+        stats.bucket_counts(stats.get_faces())
+    } else {
+        stats.bucket_counts(HISTOGRAM_RANGES)
+    };
+    let most = buckets.iter().map(|(_, _, count)| *count).max().unwrap_or(0);
+    let rows: Vec<HistogramState> = buckets
+        .iter()
+        .map(|(low, high, count)| HistogramState {
+            face: if low == high { low.to_string() } else { format!("{}-{}", low, high) },
+            count: *count,
+            share: if total == 0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}%", *count as f64 * 100.0 / total as f64)
+            },
+            bar: "#".repeat((count * HISTOGRAM_WIDTH).checked_div(most).unwrap_or(0)),
+        })
+        .collect();
+    println!("d{} faces over {} rolls:", stats.get_faces(), total);
+    print_stdout(rows.with_title()).unwrap();
+}
+
+/// Writes committed rolls as faces, with the die size when it changed, i.e. "d8: 3, 7, d6: 2".
+fn rolls_to_string(rolls: &[(u32, u32)]) -> String {
+    if rolls.is_empty() {
+        return "none".to_string();
+    }
+    let mut last_faces = 0;
+    let mut parts: Vec<String> = Vec::new();
+    for (faces, face) in rolls {
+        if *faces != last_faces {
+            parts.push(format!("d{}: {}", faces, face));
+            last_faces = *faces;
+        } else {
+            parts.push(face.to_string());
+        }
+    }
+    parts.join(", ")
+}
@@ -1,15 +1,28 @@
 mod app;
 mod cli_dice_allocator;
 mod cli_dice_tray;
+mod cli_macros;
 mod cli_parser;
 mod logger;
+mod roll_log;
+mod save_data;
+mod save_file;
+mod share_code;
+mod workspace;
 
-use cli_parser::{parse_dice_notation, parse_dice_targets};
+use cli_parser::{parse_dice_notation, parse_dice_targets, parse_tokens};
 
-use app::CliDiceTrayApp;
+use app::{CliDiceTrayApp, ImportConflict, YAHTZEE_TRAY};
+use roll_log::{RollLogFilter, parse_time};
+use save_file::{LOCK_TIMEOUT_SECS, SaveLock};
+use workspace::resolve_save_file;
 
-use clap::{Parser, Subcommand};
-use rust_dice::dice::DieResultType;
+use clap::{CommandFactory, Parser, Subcommand};
+use rust_dice::dice::{DieMode, DieResultType, StepDirection, StepLadder};
+use rust_dice::tray::TrayResultType;
+
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -22,13 +35,25 @@ struct Cli {
     ///A tray target. If no tray with the given ID is available the default "Main" tray will be targeted.
     tray: Option<String>,
 
+    #[arg(long)]
+    ///Use this workspace for one command, instead of the active workspace.
+    workspace: Option<String>,
+
+    #[arg(long)]
+    ///Load from and save to this file, instead of the workspace save file.
+    save_file: Option<String>,
+
+    #[arg(long, default_value_t = LOCK_TIMEOUT_SECS)]
+    ///Seconds to wait for another dicetray run using the same save file to finish.
+    lock_timeout: u64,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    ///Resets dice_tray_cli by clearing all trays and dice. Cannot be undone (at least for now)
+    ///Resets dice_tray_cli by clearing all trays, dice, decks and bags. Cannot be undone (at least for now)
     Reset,
     ///Deletes the target tray and all the dice in it. If no target tray is provided using the --tray option nothing happens. The main tray can't be deleted.
     Delete{
@@ -37,14 +62,19 @@ enum Commands {
     New {
         new_tray: String
     },
+    ///Lists the die profiles found in the profiles folder of the dice-tray data directory.
+    Profiles,
     //Dice commands
     /// Adds dice to a tray. Usage: add -t "fireball" "8d6 d2" : would roll 8 six-sided dice and a 2 sided-die to "fireball" tray.
     Add {
         #[arg(short, long)]
         ///Optional result type. Current result types supported are: 'f' = the die's current face, 'b' = the best result the die has rolled, 'w' = the worst result the die has rolled, 'e' = sum of all results.
         result_type: Option<char>,
+        #[arg(short, long)]
+        ///Optional profile name. Dice are built from the named profile and the dice command is read as the number of dice to add i.e. add --profile fate 4
+        profile: Option<String>,
         ///Basic dice notation seperated by whitespace i.e. "4d8" = four eight-sided dice, "2d4 d14" = 2 four-sided dice, and a 14 sided-die.
-        dice_command: String,
+        dice_command: Option<String>,
     },
     ///Drop removes dice from the tray based on the provided dice tragets. If no targets are provided the tray is cleared of all dice.
     Drop {
@@ -65,13 +95,441 @@ enum Commands {
         ///Optional dice targets, either by label or by index. If no targets are provided all dice in the target tray will be rolled.
         dice_targets: Option<String>,
     },
+    ///Sets what the tray reports as its result: 'sum', 'best', 'worst' or 'pattern' (the best pattern in the dice faces).
+    ResultType {
+        result_type: String,
+    },
+    ///Lists every pattern in the tray's faces: N of a kind, One-Roll Engine sets, straights, full houses and all distinct.
+    Patterns,
+    ///Steps dice up or down the die ladder (d4, d6, d8, d10, d12) in place. Usage: step up "0" or step down "d8".
+    Step {
+        ///'up' or 'down'.
+        direction: String,
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        ///Optional comma separated ladder of die sizes to step through i.e. "4,6,8,10,12,20".
+        #[arg(short, long)]
+        ladder: Option<String>,
+    },
+    ///Turns dice into usage dice: rolling the threshold or under steps them down the ladder, and at the bottom they're exhausted.
+    Usage {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        ///Rolls of this or under step the die down.
+        #[arg(long, default_value_t = 2)]
+        threshold: u32,
+        ///Optional comma separated ladder of die sizes to step down i.e. "4,6,8,10,12,20".
+        #[arg(short, long)]
+        ladder: Option<String>,
+        ///Turns the dice back into standard dice.
+        #[arg(long)]
+        off: bool,
+    },
+    ///Sets dice to a face by hand, i.e. to record a physical roll or fix a misroll. Usage: set "0,1" 6. Set dice are marked in the tray.
+    Set {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        face: u32,
+    },
+    ///Makes dice counters, like a spindown life counter. Counters stop at their ends and can't be rolled unless configured otherwise.
+    Counter {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        ///Wrap around past the ends instead of stopping at them.
+        #[arg(short, long)]
+        wrap: bool,
+        ///Allow the counters to be rolled.
+        #[arg(short, long)]
+        unlock: bool,
+        ///Turns the dice back into standard dice.
+        #[arg(long)]
+        off: bool,
+    },
+    ///Turns dice up by an amount. Usage: inc "0" 3.
+    Inc {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        #[arg(default_value_t = 1)]
+        amount: u32,
+    },
+    ///Turns dice down by an amount. Usage: dec "life" 5.
+    Dec {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+        #[arg(default_value_t = 1)]
+        amount: u32,
+    },
+    ///Holds dice so they keep their face when the tray is rolled. Usage: hold "0,2" or hold "d6".
+    Hold {
+        ///Dice targets, either by label or by index.
+        dice_targets: String,
+    },
+    ///Releases held dice so they roll again. If no targets are provided every die in the tray is released.
+    Release {
+        ///Optional dice targets, either by label or by index.
+        dice_targets: Option<String>,
+    },
+    ///Manages named roll macros. Once added, a macro is run like any other command i.e. "dicetray attack" or "dicetray smite 3".
+    Macro {
+        #[command(subcommand)]
+        command: MacroCommands,
+    },
+    ///Imports, shows and rolls on result tables. Dice labeled with a table's name look up their faces on that table when rolled.
+    Table {
+        #[command(subcommand)]
+        command: TableCommands,
+    },
+    ///Manages decks of cards. Decks persist between sessions with their own RNG, just like dice.
+    Deck {
+        #[command(subcommand)]
+        command: DeckCommands,
+    },
+    ///Manages bags of tokens that are drawn without replacement, like a chaos bag.
+    Bag {
+        #[command(subcommand)]
+        command: BagCommands,
+    },
+    ///Manages workspaces. Each workspace has its own trays, decks, bags and games, i.e. one per campaign.
+    Workspace {
+        #[command(subcommand)]
+        command: WorkspaceCommands,
+    },
+    ///Writes a tray, with its dice and their RNG state, to a file that can be imported into another workspace or machine.
+    Export {
+        ///The ID of the tray to export.
+        tray_id: String,
+        #[arg(long, short)]
+        ///The file to write. Defaults to <tray_id>.tray.json in the current directory.
+        out: Option<String>,
+    },
+    ///Imports a tray written by export.
+    Import {
+        ///The file to import.
+        file: String,
+        #[arg(long = "as")]
+        ///Import the tray under this ID instead of its exported one.
+        as_id: Option<String>,
+        #[arg(long, default_value = "fail")]
+        ///What to do if a tray with the same ID exists: fail, replace or rename (adds a number, i.e. Main-2).
+        on_conflict: String,
+    },
+    ///Prints a short code for a tray's dice, labels and result types that can be pasted into chat and loaded with load-code.
+    Share {
+        ///The ID of the tray to share.
+        tray_id: String,
+        #[arg(long, short)]
+        ///Include each die's RNG seed, so everyone who loads the code rolls the same faces.
+        seeds: bool,
+    },
+    ///Builds a tray from a share code.
+    LoadCode {
+        code: String,
+        #[arg(long = "as")]
+        ///Load the tray under this ID instead of the one in the code.
+        as_id: Option<String>,
+        #[arg(long, default_value = "fail")]
+        ///What to do if a tray with the same ID exists: fail, replace or rename (adds a number, i.e. Main-2).
+        on_conflict: String,
+    },
+    ///Shows the roll log: every roll made in the workspace, with the command that made it. Use --tray to only show one tray's rolls.
+    Log {
+        #[arg(long, short)]
+        ///Only show rolls that included dice with this label.
+        label: Option<String>,
+        #[arg(long)]
+        ///Only show rolls from this time on: a UTC date like 2025-03-14, a date and time like "2025-03-14 18:05", or a time ago like 2h or 3d.
+        since: Option<String>,
+        #[arg(long)]
+        ///Only show rolls up to this time. Takes the same times as --since.
+        until: Option<String>,
+        #[arg(long, short = 'n', default_value_t = 20)]
+        ///The number of most recent rolls to show. 0 shows every roll.
+        count: usize,
+        #[arg(long)]
+        ///Print the rolls as JSON.
+        json: bool,
+    },
+    ///Commits dice to new RNG seeds and shows a hash of each seed to publish before rolling. If no targets are provided every die in the tray is committed.
+    Commit {
+        ///Optional dice targets, either by label or by index.
+        dice_targets: Option<String>,
+    },
+    ///Reveals the seeds of committed dice, with every roll made since they were committed. If no targets are provided every committed die in the tray is revealed.
+    Reveal {
+        ///Optional dice targets, either by label or by index.
+        dice_targets: Option<String>,
+        #[arg(long, short)]
+        ///Write the reveal to this file instead of printing it.
+        out: Option<String>,
+    },
+    ///Checks a reveal file: each seed must match its published hash and replay exactly the rolls recorded.
+    Verify {
+        ///The reveal file written by reveal.
+        file: String,
+    },
+    ///Checks the roll log's hash chain and reports the first broken link, then checks the save file hasn't been edited outside dicetray.
+    VerifyLog,
+    ///Shows statistics of the faces each die has rolled: roll count, mean against the expected mean, streaks and a histogram of each die size. If no targets are provided every die in the tray is included.
+    Stats {
+        ///Optional dice targets, either by label or by index.
+        dice_targets: Option<String>,
+    },
+    ///Lists backups of the save file, or restores the numbered backup. Backup 1 is the most recent.
+    Restore {
+        backup: Option<usize>,
+    },
+    ///Plays Yahtzee with five dice in the yahtzee tray. Hold dice between rolls with: yahtzee hold "0,2".
+    Yahtzee {
+        #[command(subcommand)]
+        command: YahtzeeCommands,
+    },
+    ///Runs the macro with this name, passing any following arguments to the macro's parameters.
+    #[command(external_subcommand)]
+    RunMacro(Vec<String>),
 }
 
-fn main() {
-    let mut app = CliDiceTrayApp::new();
-    app.init();
+#[derive(Subcommand)]
+enum TableCommands {
+    ///Imports the tables in a .csv, .md, .json, .yaml or .yml file. Roll keys like "1-3" and "01–15" are detected automatically.
+    Import {
+        ///The file to import.
+        file: String,
+        ///Optional name for the imported table. Tables are named after the file, or the Markdown heading above them, by default.
+        #[arg(long = "as")]
+        name: Option<String>,
+    },
+    ///Lists all imported tables.
+    List,
+    ///Shows every entry in a table.
+    Show {
+        name: String,
+    },
+    ///Rolls on a table. Usage: table roll treasure "2d6". If no dice are given a die covering the table's range is rolled. Deck tables are drawn from.
+    Roll {
+        name: String,
+        ///Optional dice notation, the total of the dice is looked up on the table.
+        dice_command: Option<String>,
+    },
+    ///Makes a table a deck, so its entries are drawn without replacement until it is reshuffled.
+    Deck {
+        name: String,
+        ///Reshuffle the table automatically when every entry has been drawn.
+        #[arg(short, long)]
+        auto_reshuffle: bool,
+        ///Turns deck mode off again, discarding the draw state and history.
+        #[arg(long)]
+        off: bool,
+    },
+    ///Returns every drawn entry to a deck table.
+    Reshuffle {
+        name: String,
+    },
+    ///Shows the draw history of a deck table.
+    History {
+        name: String,
+        ///Only show the most recent draws.
+        #[arg(short, long)]
+        count: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeckCommands {
+    ///Creates a new, shuffled deck. Usage: deck new poker, deck new fate --kind tarot, deck new initiative --cards "Red,Blue,Green".
+    New {
+        name: String,
+        ///The kind of deck to build: 'standard' (52 cards), 'jokers' (54 cards) or 'tarot' (78 cards).
+        #[arg(short, long, default_value = "standard")]
+        kind: String,
+        ///Comma separated cards for a custom deck. Overrides --kind.
+        #[arg(short, long)]
+        cards: Option<String>,
+    },
+    ///Lists all decks and the size of their piles.
+    List,
+    ///Shows the piles of a deck.
+    Show {
+        name: String,
+    },
+    ///Deletes a deck.
+    Delete {
+        name: String,
+    },
+    ///Shuffles a pile of the deck, the draw pile by default.
+    Shuffle {
+        name: String,
+        #[arg(short, long)]
+        pile: Option<String>,
+    },
+    ///Draws cards from the top of the deck onto a pile, the "hand" pile by default.
+    Draw {
+        name: String,
+        #[arg(default_value_t = 1)]
+        count: usize,
+        ///The pile to draw to i.e. a player name.
+        #[arg(long)]
+        to: Option<String>,
+    },
+    ///Discards comma separated cards from a pile (the "hand" pile by default). Discards the whole pile if no cards are given.
+    Discard {
+        name: String,
+        cards: Option<String>,
+        #[arg(long)]
+        from: Option<String>,
+    },
+    ///Shows the top cards of the deck without drawing them.
+    Peek {
+        name: String,
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    ///Cuts the deck, moving the top cards to the bottom. Cuts at a random position if none is given.
+    Cut {
+        name: String,
+        position: Option<usize>,
+    },
+    ///Returns a pile (the discard pile by default) to the bottom of the deck, or every card with --all.
+    Return {
+        name: String,
+        #[arg(short, long)]
+        pile: Option<String>,
+        ///Returns every card to the deck and shuffles it.
+        #[arg(short, long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BagCommands {
+    ///Creates a new bag. Tokens are comma separated, with optional counts: bag new chaos "skull:2, -1:3, elder sign".
+    New {
+        name: String,
+        tokens: String,
+    },
+    ///Lists all bags.
+    List,
+    ///Shows what's in a bag and what's been drawn, without drawing.
+    Peek {
+        name: String,
+    },
+    ///Deletes a bag.
+    Delete {
+        name: String,
+    },
+    ///Puts new tokens into a bag i.e. bag add chaos "curse:2".
+    Add {
+        name: String,
+        tokens: String,
+    },
+    ///Takes tokens out of a bag for good i.e. bag remove chaos "curse:2".
+    Remove {
+        name: String,
+        tokens: String,
+    },
+    ///Draws tokens from a bag without replacement.
+    Draw {
+        name: String,
+        #[arg(default_value_t = 1)]
+        count: u32,
+    },
+    ///Returns drawn tokens to a bag i.e. bag return chaos "skull, -1".
+    Return {
+        name: String,
+        tokens: String,
+    },
+    ///Returns every drawn token to a bag.
+    ReturnAll {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum YahtzeeCommands {
+    ///Starts a new game for the players, in turn order i.e. yahtzee new Alex Sam.
+    New {
+        #[arg(required = true)]
+        players: Vec<String>,
+    },
+    ///Rolls the dice. The first roll of a turn rolls all five, the next two skip held dice.
+    Roll,
+    ///Holds dice between rolls, by index or label.
+    Hold {
+        dice_targets: String,
+    },
+    ///Releases held dice. If no targets are provided every die is released.
+    Release {
+        dice_targets: Option<String>,
+    },
+    ///Scores the dice in a category and ends the turn i.e. yahtzee score "full house" or yahtzee score 6s.
+    Score {
+        category: String,
+    },
+    ///Shows what the dice would score in each open category.
+    Suggest,
+    ///Shows every player's scorecard.
+    Card,
+    ///Ends the game in progress.
+    Quit,
+}
+
+#[derive(Subcommand)]
+enum WorkspaceCommands {
+    ///Lists every workspace. The active workspace is marked with *.
+    List,
+    ///Creates a new, empty workspace.
+    New {
+        name: String,
+    },
+    ///Makes a workspace the active one. Use "default" for the original workspace.
+    Switch {
+        name: String,
+    },
+    ///Deletes a workspace and everything saved in it.
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MacroCommands {
+    ///Adds a macro. Usage: macro add attack "1d20+5", or with parameters: macro add smite "${level}d8 +$bonus". Use --tray before "macro" to bind the macro to a tray.
+    Add {
+        ///The macro name, used to run the macro as a command.
+        name: String,
+        ///The dice command the macro runs. Words starting with $ are parameters filled in when the macro is run.
+        dice_command: String,
+    },
+    ///Lists all macros.
+    List,
+    ///Removes a macro.
+    Rm {
+        name: String,
+    },
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
-    let tray_id: Option<&str> = cli.tray.as_deref();
+    let save_file = match resolve_save_file(cli.workspace.as_deref(), cli.save_file.as_deref()) {
+        Ok(save_file) => save_file,
+        Err(e) => {
+            println!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    //Held until main returns, so no other run can load the save between this run loading and saving it.
+    let _save_lock = match SaveLock::acquire(&save_file, Duration::from_secs(cli.lock_timeout)) {
+        Ok(save_lock) => save_lock,
+        Err(e) => {
+            println!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut app = CliDiceTrayApp::new(save_file);
+    app.init();
+    let mut tray_id: Option<&str> = cli.tray.as_deref();
+    let macro_tray: String;
+    //Set by commands whose failure should reach the shell, after the save is still written as usual.
+    let mut exit_code = ExitCode::SUCCESS;
 
     match &cli.command {
         Some(Commands::Reset) => {
@@ -107,16 +565,148 @@ fn main() {
                 app.drop_all(tray_id);
             }
         },
+        Some(Commands::ResultType { result_type }) => {
+            let result = find_tray_result_type(result_type)
+                .and_then(|result_type| app.set_tray_result_type(tray_id, result_type));
+            if let Err(e) = result {
+                println!("Failed to set the tray result type with error {}", e);
+            }
+        }
+        Some(Commands::Patterns) => {
+            if let Err(e) = app.show_patterns(tray_id) {
+                println!("Failed to find patterns with error {}", e);
+            }
+        }
+        Some(Commands::Step {
+            direction,
+            dice_targets,
+            ladder,
+        }) => {
+            let result = find_step_direction(direction).and_then(|direction| {
+                let ladder = match ladder {
+                    Some(ladder) => parse_ladder(ladder)?,
+                    None => StepLadder::default(),
+                };
+                let targets = parse_dice_targets(dice_targets)?;
+                app.step_at_targets(tray_id, targets, direction, &ladder)
+            });
+            if let Err(e) = result {
+                println!("Failed to step dice with error {}", e);
+            }
+        }
+        Some(Commands::Usage {
+            dice_targets,
+            threshold,
+            ladder,
+            off,
+        }) => {
+            let mode = if *off {
+                Ok(DieMode::Standard)
+            } else {
+                match ladder {
+                    Some(ladder) => parse_ladder(ladder),
+                    None => Ok(StepLadder::default()),
+                }
+                .and_then(|ladder| DieMode::usage(*threshold, ladder))
+            };
+            let result = mode.and_then(|mode| {
+                let targets = parse_dice_targets(dice_targets)?;
+                app.set_mode_at_targets(tray_id, targets, mode)
+            });
+            if let Err(e) = result {
+                println!("Failed to set usage dice with error {}", e);
+            }
+        }
+        Some(Commands::Set { dice_targets, face }) => {
+            let result = parse_dice_targets(dice_targets)
+                .and_then(|targets| app.set_at_targets(tray_id, targets, *face));
+            if let Err(e) = result {
+                println!("Failed to set dice with error {}", e);
+            }
+        }
+        Some(Commands::Counter {
+            dice_targets,
+            wrap,
+            unlock,
+            off,
+        }) => {
+            let mode = if *off {
+                DieMode::Standard
+            } else {
+                DieMode::Counter {
+                    wrap: *wrap,
+                    unlocked: *unlock,
+                }
+            };
+            let result = parse_dice_targets(dice_targets)
+                .and_then(|targets| app.set_mode_at_targets(tray_id, targets, mode));
+            if let Err(e) = result {
+                println!("Failed to set counter dice with error {}", e);
+            }
+        }
+        Some(Commands::Inc { dice_targets, amount }) => {
+            let result = parse_dice_targets(dice_targets)
+                .and_then(|targets| app.count_at_targets(tray_id, targets, *amount as i64));
+            if let Err(e) = result {
+                println!("Failed to turn dice up with error {}", e);
+            }
+        }
+        Some(Commands::Dec { dice_targets, amount }) => {
+            let result = parse_dice_targets(dice_targets)
+                .and_then(|targets| app.count_at_targets(tray_id, targets, -(*amount as i64)));
+            if let Err(e) = result {
+                println!("Failed to turn dice down with error {}", e);
+            }
+        }
+        Some(Commands::Hold { dice_targets }) => {
+            if let Ok(targets) = parse_dice_targets(dice_targets)
+                && let Err(e) = app.hold_at_targets(tray_id, targets, true)
+            {
+                println!("Failed to hold dice at provided targets with error {}", e)
+            }
+        }
+        Some(Commands::Release { dice_targets }) => match dice_targets {
+            Some(target_string) => {
+                if let Ok(targets) = parse_dice_targets(target_string)
+                    && let Err(e) = app.hold_at_targets(tray_id, targets, false)
+                {
+                    println!("Failed to release dice at provided targets with error {}", e)
+                }
+            }
+            None => app.release_all(tray_id),
+        },
+        Some(Commands::Profiles) => {
+            app.show_profiles();
+        }
         Some(Commands::Add {
             result_type,
+            profile,
             dice_command,
         }) => {
             let result_type_unpacked = find_result_type(*result_type);
             println!("Result type is = {:?}", result_type_unpacked);
-            if let Ok(raw_dice) = parse_dice_notation(dice_command) {
-                raw_dice.iter().for_each(|dice| {
-                    app.add_dice_from_raw(tray_id, dice.0, dice.1, result_type_unpacked);
-                });
+            match (profile, dice_command) {
+                (Some(profile_name), count) => {
+                    match count.as_deref().unwrap_or("1").trim().parse::<u32>() {
+                        Ok(count) => {
+                            if let Err(e) = app.add_dice_from_profile(tray_id, profile_name, count, result_type_unpacked) {
+                                println!("Failed to add dice from profile with error {}", e);
+                            }
+                        }
+                        Err(_) => println!("When using a profile the dice command must be a number of dice to add."),
+                    }
+                }
+                (None, Some(dice_command)) => {
+                    if let Ok(parsed) = parse_dice_notation(dice_command) {
+                        parsed.dice.iter().for_each(|dice| {
+                            app.add_dice_from_raw(tray_id, dice.0, dice.1, result_type_unpacked);
+                        });
+                        if let Err(e) = app.add_modifier(tray_id, parsed.modifier) {
+                            println!("Failed to add modifier with error {}", e);
+                        }
+                    }
+                }
+                (None, None) => println!("Add needs either a dice command or a --profile to build dice from."),
             }
         },
         Some(Commands::Move { 
@@ -163,6 +753,246 @@ fn main() {
                 }
             }
         }
+        Some(Commands::Macro { command }) => match command {
+            MacroCommands::Add { name, dice_command } => {
+                if Cli::command().get_subcommands().any(|c| c.get_name() == name) {
+                    println!("{} is a dicetray command and can't be used as a macro name.", name);
+                } else if let Err(e) = app.add_macro(name, dice_command, tray_id) {
+                    println!("Failed to add macro with error {}", e);
+                }
+            }
+            MacroCommands::List => app.show_macros(),
+            MacroCommands::Rm { name } => {
+                if let Err(e) = app.remove_macro(name) {
+                    println!("Failed to remove macro with error {}", e);
+                }
+            }
+        },
+        Some(Commands::Table { command }) => {
+            let result = match command {
+                TableCommands::Import { file, name } => app.import_tables(file, name.as_deref()),
+                TableCommands::List => {
+                    app.show_table_list();
+                    Ok(())
+                }
+                TableCommands::Show { name } => app.show_table(name),
+                TableCommands::Roll { name, dice_command } => app.roll_table(name, dice_command.as_deref()),
+                TableCommands::Deck { name, auto_reshuffle, off } => {
+                    app.set_table_deck(name, !*off, *auto_reshuffle)
+                }
+                TableCommands::Reshuffle { name } => app.reshuffle_table(name),
+                TableCommands::History { name, count } => app.show_table_history(name, *count),
+            };
+            if let Err(e) = result {
+                println!("Table command failed with error {}", e);
+            }
+        }
+        Some(Commands::Deck { command }) => {
+            let (result, shown_deck) = match command {
+                DeckCommands::New { name, kind, cards } => {
+                    (app.new_deck(name, kind, cards.as_deref().map(split_cards)), Some(name))
+                }
+                DeckCommands::List => {
+                    app.show_deck_list();
+                    (Ok(()), None)
+                }
+                DeckCommands::Show { name } => (Ok(()), Some(name)),
+                DeckCommands::Delete { name } => (app.delete_deck(name), None),
+                DeckCommands::Shuffle { name, pile } => (app.shuffle_deck(name, pile.as_deref()), Some(name)),
+                DeckCommands::Draw { name, count, to } => (app.draw_cards(name, *count, to.as_deref()), Some(name)),
+                DeckCommands::Discard { name, cards, from } => (
+                    app.discard_cards(name, cards.as_deref().map(split_cards), from.as_deref()),
+                    Some(name),
+                ),
+                DeckCommands::Peek { name, count } => (app.peek_cards(name, *count), None),
+                DeckCommands::Cut { name, position } => (app.cut_deck(name, *position), Some(name)),
+                DeckCommands::Return { name, pile, all } => {
+                    (app.return_cards(name, pile.as_deref(), *all), Some(name))
+                }
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(name) = shown_deck
+                        && let Err(e) = app.show_deck(name)
+                    {
+                        println!("{}", e);
+                    }
+                }
+                Err(e) => println!("Deck command failed with error {}", e),
+            }
+        }
+        Some(Commands::Bag { command }) => {
+            let (result, shown_bag) = match command {
+                BagCommands::New { name, tokens } => (
+                    parse_tokens(tokens).and_then(|tokens| app.new_bag(name, tokens)),
+                    Some(name),
+                ),
+                BagCommands::List => {
+                    app.show_bag_list();
+                    (Ok(()), None)
+                }
+                BagCommands::Peek { name } => (Ok(()), Some(name)),
+                BagCommands::Delete { name } => (app.delete_bag(name), None),
+                BagCommands::Add { name, tokens } => (
+                    parse_tokens(tokens).and_then(|tokens| app.add_tokens(name, tokens)),
+                    Some(name),
+                ),
+                BagCommands::Remove { name, tokens } => (
+                    parse_tokens(tokens).and_then(|tokens| app.remove_tokens(name, tokens)),
+                    Some(name),
+                ),
+                BagCommands::Draw { name, count } => (app.draw_tokens(name, *count), Some(name)),
+                BagCommands::Return { name, tokens } => (
+                    parse_tokens(tokens).and_then(|tokens| app.return_tokens(name, Some(tokens))),
+                    Some(name),
+                ),
+                BagCommands::ReturnAll { name } => (app.return_tokens(name, None), Some(name)),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(name) = shown_bag
+                        && let Err(e) = app.show_bag(name)
+                    {
+                        println!("{}", e);
+                    }
+                }
+                Err(e) => println!("Bag command failed with error {}", e),
+            }
+        }
+        Some(Commands::Workspace { command }) => {
+            let result = match command {
+                WorkspaceCommands::List => app.show_workspaces(),
+                WorkspaceCommands::New { name } => app.new_workspace(name),
+                WorkspaceCommands::Switch { name } => app.switch_workspace(name),
+                WorkspaceCommands::Delete { name } => app.delete_workspace(name),
+            };
+            if let Err(e) = result {
+                println!("Workspace command failed with error {}", e);
+            }
+        }
+        Some(Commands::Export { tray_id, out }) => {
+            if let Err(e) = app.export_tray(tray_id, out.as_deref()) {
+                println!("Export failed with error {}", e);
+            }
+        }
+        Some(Commands::Import {
+            file,
+            as_id,
+            on_conflict,
+        }) => {
+            let result = find_import_conflict(on_conflict)
+                .and_then(|on_conflict| app.import_tray(file, as_id.as_deref(), on_conflict));
+            if let Err(e) = result {
+                println!("Import failed with error {}", e);
+            }
+        }
+        Some(Commands::Share { tray_id, seeds }) => {
+            if let Err(e) = app.share_tray(tray_id, *seeds) {
+                println!("Share failed with error {}", e);
+            }
+        }
+        Some(Commands::LoadCode {
+            code,
+            as_id,
+            on_conflict,
+        }) => {
+            let result = find_import_conflict(on_conflict)
+                .and_then(|on_conflict| app.load_code(code, as_id.as_deref(), on_conflict));
+            if let Err(e) = result {
+                println!("Loading the share code failed with error {}", e);
+            }
+        }
+        Some(Commands::Log {
+            label,
+            since,
+            until,
+            count,
+            json,
+        }) => {
+            let filter = build_log_filter(cli.tray.as_deref(), label.as_deref(), since.as_deref(), until.as_deref(), *count);
+            if let Err(e) = filter.and_then(|filter| app.show_roll_log(&filter, *json)) {
+                println!("Log failed with error {}", e);
+            }
+        }
+        Some(Commands::Commit { dice_targets }) => {
+            let result = dice_targets
+                .as_deref()
+                .map(parse_dice_targets)
+                .transpose()
+                .and_then(|targets| app.commit_at_targets(tray_id, targets));
+            if let Err(e) = result {
+                println!("Commit failed with error {}", e);
+            }
+        }
+        Some(Commands::Reveal { dice_targets, out }) => {
+            let result = dice_targets
+                .as_deref()
+                .map(parse_dice_targets)
+                .transpose()
+                .and_then(|targets| app.reveal_at_targets(tray_id, targets, out.as_deref()));
+            if let Err(e) = result {
+                println!("Reveal failed with error {}", e);
+            }
+        }
+        Some(Commands::Verify { file }) => {
+            if let Err(e) = app.verify_reveal(file) {
+                println!("Verify failed with error {}", e);
+            }
+        }
+        Some(Commands::VerifyLog) => {
+            if let Err(e) = app.verify_log() {
+                println!("Verify log failed with error {}", e);
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        Some(Commands::Stats { dice_targets }) => {
+            let result = dice_targets
+                .as_deref()
+                .map(parse_dice_targets)
+                .transpose()
+                .and_then(|targets| app.show_stats(tray_id, targets));
+            if let Err(e) = result {
+                println!("Stats failed with error {}", e);
+            }
+        }
+        Some(Commands::Restore { backup }) => {
+            if let Err(e) = app.restore(*backup) {
+                println!("Restore failed with error {}", e);
+            }
+        }
+        Some(Commands::Yahtzee { command }) => {
+            tray_id = Some(YAHTZEE_TRAY);
+            let result = app.with_yahtzee_tray(|app| match command {
+                YahtzeeCommands::New { players } => app.new_yahtzee(players.clone()),
+                YahtzeeCommands::Roll => app.roll_yahtzee(),
+                YahtzeeCommands::Hold { dice_targets } => parse_dice_targets(dice_targets)
+                    .and_then(|targets| app.hold_at_targets(tray_id, targets, true)),
+                YahtzeeCommands::Release { dice_targets } => match dice_targets {
+                    Some(target_string) => parse_dice_targets(target_string)
+                        .and_then(|targets| app.hold_at_targets(tray_id, targets, false)),
+                    None => {
+                        app.release_all(tray_id);
+                        Ok(())
+                    }
+                },
+                YahtzeeCommands::Score { category } => app.score_yahtzee(category),
+                YahtzeeCommands::Suggest => app.show_yahtzee_suggestions(),
+                YahtzeeCommands::Card => app.show_yahtzee_card(),
+                YahtzeeCommands::Quit => app.quit_yahtzee(),
+            });
+            if let Err(e) = result {
+                println!("Yahtzee command failed with error {}", e);
+            }
+        }
+        Some(Commands::RunMacro(args)) => {
+            match app.run_macro(&args[0], &args[1..], tray_id) {
+                Ok(used_tray) => {
+                    macro_tray = used_tray;
+                    tray_id = Some(&macro_tray);
+                }
+                Err(e) => println!("Failed to run macro with error {}", e),
+            }
+        }
         None => {
             println!("No commands found!")
         }
@@ -176,6 +1006,74 @@ fn main() {
         app.show_tray(tray_id);
     }
     app.close();
+    exit_code
+}
+
+///Splits a comma separated list of cards, trimming whitespace.
+fn split_cards(cards: &str) -> Vec<String> {
+    cards
+        .split(',')
+        .map(|card| card.trim().to_string())
+        .filter(|card| !card.is_empty())
+        .collect()
+}
+
+fn find_step_direction(direction: &str) -> Result<StepDirection, String> {
+    match direction.to_ascii_lowercase().as_str() {
+        "up" | "u" => Ok(StepDirection::Up),
+        "down" | "d" => Ok(StepDirection::Down),
+        _ => Err(format!("Unknown step direction {}. Use up or down.", direction)),
+    }
+}
+
+///Builds the roll log filter from the log command's options. A count of 0 shows every roll.
+fn build_log_filter(
+    tray: Option<&str>,
+    label: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+    count: usize,
+) -> Result<RollLogFilter, String> {
+    Ok(RollLogFilter {
+        tray: tray.map(str::to_string),
+        label: label.map(str::to_string),
+        since: since.map(parse_time).transpose()?,
+        until: until.map(parse_time).transpose()?,
+        count: (count > 0).then_some(count),
+    })
+}
+
+fn find_import_conflict(on_conflict: &str) -> Result<ImportConflict, String> {
+    match on_conflict.to_ascii_lowercase().as_str() {
+        "fail" => Ok(ImportConflict::Fail),
+        "replace" => Ok(ImportConflict::Replace),
+        "rename" => Ok(ImportConflict::Rename),
+        _ => Err(format!("Unknown conflict option {}. Use fail, replace or rename.", on_conflict)),
+    }
+}
+
+///Parses a comma separated ladder of die sizes. Sizes can be written with or without the d i.e. "d4,d6,d8" or "4,6,8".
+fn parse_ladder(ladder: &str) -> Result<StepLadder, String> {
+    let rungs = ladder
+        .split(',')
+        .map(|rung| {
+            let rung = rung.trim();
+            rung.trim_start_matches(['d', 'D'])
+                .parse::<u32>()
+                .map_err(|_| format!("{} is not a die size.", rung))
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+    StepLadder::new(rungs)
+}
+
+fn find_tray_result_type(name: &str) -> Result<TrayResultType, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "s" | "sum" => Ok(TrayResultType::Sum),
+        "b" | "best" => Ok(TrayResultType::Best),
+        "w" | "worst" => Ok(TrayResultType::Worst),
+        "p" | "pattern" => Ok(TrayResultType::Pattern),
+        _ => Err(format!("Unknown tray result type {}. Use sum, best, worst or pattern.", name)),
+    }
 }
 
 fn find_result_type(c: Option<char>) -> Option<DieResultType> {
@@ -0,0 +1,599 @@
+use rust_dice::dice::{DieResult, DieResultType};
+use rust_dice::tray::Tray;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///A die as it was logged after a roll.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoggedDie {
+    id: usize,
+    index: usize,
+    label: String,
+    faces: u32,
+    face: i32,
+    result: DieResult,
+    result_type: DieResultType,
+    ///True if the face was set by hand with the set command rather than rolled.
+    #[serde(default)]
+    manual: bool,
+    ///The hash of the die's seed commitment, if it was committed when rolled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commitment: Option<String>,
+}
+
+impl LoggedDie {
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_face(&self) -> i32 {
+        self.face
+    }
+
+    pub fn get_result(&self) -> &DieResult {
+        &self.result
+    }
+
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+}
+
+///The previous hash of the first entry in the hash chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+///What a roll log entry records.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RollLogKind {
+    ///Dice rolled, or set by hand, in a tray.
+    #[default]
+    Roll,
+    ///The save file written at the end of a run that changed it.
+    State,
+}
+
+///One line of the roll log: the dice one command rolled in one tray, or the save state a run left behind.
+///Entries are hash chained: each holds the hash of the entry before it, so editing, removing or reordering entries breaks the chain.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RollLogEntry {
+    ///Seconds since the Unix epoch.
+    timestamp: u64,
+    #[serde(default)]
+    kind: RollLogKind,
+    tray: String,
+    command: String,
+    dice: Vec<LoggedDie>,
+    ///For state entries, the SHA-256 of the save file the run loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    loaded_hash: Option<String>,
+    ///For state entries, the SHA-256 of the save file the run wrote.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_hash: Option<String>,
+    ///Entries written before the roll log was chained have no hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+impl RollLogEntry {
+    ///Records the dice at indices in the tray as they are now, i.e. straight after rolling them.
+    pub fn new(tray: &dyn Tray, indices: &[usize]) -> Self {
+        let dice = indices
+            .iter()
+            .filter_map(|index| tray.get_dice().get(*index).map(|die| (*index, die)))
+            .map(|(index, die)| LoggedDie {
+                id: die.get_id(),
+                index,
+                label: die.get_label().to_string(),
+                faces: die.get_face_count(),
+                face: die.get_current_face(),
+                result: die.get_result().clone(),
+                result_type: *die.get_result_type(),
+                manual: die.is_manual(),
+                commitment: die.get_commitment().map(|commitment| commitment.get_hash().to_string()),
+            })
+            .collect();
+        RollLogEntry {
+            timestamp: unix_now(),
+            kind: RollLogKind::Roll,
+            tray: tray.get_id().to_string(),
+            command: current_command(),
+            dice,
+            loaded_hash: None,
+            state_hash: None,
+            prev_hash: None,
+            hash: None,
+        }
+    }
+
+    ///Records the save file a run loaded and the one it wrote, so changes made to the save outside dicetray can be detected.
+    pub fn state(loaded_hash: Option<String>, state_hash: String) -> Self {
+        RollLogEntry {
+            timestamp: unix_now(),
+            kind: RollLogKind::State,
+            tray: String::new(),
+            command: current_command(),
+            dice: Vec::new(),
+            loaded_hash,
+            state_hash: Some(state_hash),
+            prev_hash: None,
+            hash: None,
+        }
+    }
+
+    ///Works out the entry's hash: the SHA-256 of the previous hash and the entry's JSON, without its own hash.
+    ///The JSON goes through serde_json::Value so its keys are always in the same order.
+    fn chain_hash(&self) -> Result<String, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Value::Object(object) = &mut value {
+            object.remove("hash");
+        }
+        let prev_hash = self.prev_hash.as_deref().unwrap_or(GENESIS_HASH);
+        Ok(sha256_hex(format!("{}\n{}", prev_hash, serde_json::to_string(&value)?).as_bytes()))
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn get_tray(&self) -> &str {
+        &self.tray
+    }
+
+    ///Gets the dicetray command that made the roll, i.e. "roll -t Main".
+    pub fn get_command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn get_dice(&self) -> &Vec<LoggedDie> {
+        &self.dice
+    }
+}
+
+///Filters for reading the roll log. Empty filters match everything.
+#[derive(Default)]
+pub struct RollLogFilter {
+    pub tray: Option<String>,
+    pub label: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    ///Only keep the last count matching entries.
+    pub count: Option<usize>,
+}
+
+impl RollLogFilter {
+    ///Returns true if the entry passes the tray and time filters and, with a label filter, rolled a die with that label.
+    fn matches(&self, entry: &RollLogEntry) -> bool {
+        self.tray.as_ref().is_none_or(|tray| entry.tray == *tray)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+            && self
+                .label
+                .as_ref()
+                .is_none_or(|label| entry.dice.iter().any(|die| die.label == *label))
+    }
+}
+
+///The roll log is kept next to the save file and named after it, i.e. campaign.rolls.jsonl, so every save file has its own.
+///The default dice_tray_save.json keeps the dice_tray_rolls.jsonl name it has always used.
+pub fn roll_log_path(save_file: &Path) -> PathBuf {
+    match save_file.file_stem().and_then(|stem| stem.to_str()) {
+        Some("dice_tray_save") | None => save_file.with_file_name("dice_tray_rolls.jsonl"),
+        Some(_) => save_file.with_extension("rolls.jsonl"),
+    }
+}
+
+///Appends entries to the roll log, one JSON object per line, chaining each to the entry before it. Earlier lines are never rewritten.
+pub fn append_entries(path: &Path, entries: &[RollLogEntry]) -> io::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut prev_hash = match fs::read_to_string(path) {
+        Ok(content) => chain_tail(&content).0,
+        Err(e) if e.kind() == ErrorKind::NotFound => GENESIS_HASH.to_string(),
+        Err(e) => return Err(e),
+    };
+    let mut lines = String::new();
+    for entry in entries {
+        let mut entry = entry.clone();
+        entry.prev_hash = Some(prev_hash);
+        let hash = entry.chain_hash()?;
+        entry.hash = Some(hash.clone());
+        prev_hash = hash;
+        lines.push_str(&serde_json::to_string(&entry)?);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
+
+///Reads the entries of the roll log that pass the filter, oldest first. Lines that can't be read are skipped and counted.
+pub fn read_entries(path: &Path, filter: &RollLogFilter) -> Result<(Vec<RollLogEntry>, usize), String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(format!("Failed to read the roll log: {}", e)),
+    };
+    let mut skipped = 0;
+    let mut entries: Vec<RollLogEntry> = Vec::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<RollLogEntry>(line) {
+            Ok(entry) if entry.kind == RollLogKind::Roll && filter.matches(&entry) => entries.push(entry),
+            Ok(_) => {}
+            Err(_) => skipped += 1,
+        }
+    }
+    if let Some(count) = filter.count {
+        entries.drain(..entries.len().saturating_sub(count));
+    }
+    Ok((entries, skipped))
+}
+
+///Gets the SHA-256 of the save state last recorded in the roll log, if any.
+pub fn last_state_hash(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(chain_tail(&content).1),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+///Finds the hash of the last entry, which the next entry chains to, and the last recorded save state.
+fn chain_tail(content: &str) -> (String, Option<String>) {
+    let mut last_hash = GENESIS_HASH.to_string();
+    let mut last_state = None;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        if let Ok(entry) = serde_json::from_str::<RollLogEntry>(line) {
+            if let Some(hash) = entry.hash {
+                last_hash = hash;
+            }
+            if entry.state_hash.is_some() {
+                last_state = entry.state_hash;
+            }
+        }
+    }
+    (last_hash, last_state)
+}
+
+///Where the roll log's hash chain first breaks.
+pub struct BrokenLink {
+    pub line: usize,
+    pub timestamp: Option<u64>,
+    pub reason: String,
+}
+
+///The result of checking the roll log's hash chain and the save file against it.
+pub struct ChainReport {
+    ///The number of chained entries checked.
+    pub entries: usize,
+    ///Entries from before the roll log was chained, which can't be checked.
+    pub unchained: usize,
+    pub broken: Option<BrokenLink>,
+    ///Set if the save file isn't the one the last run recorded, i.e. it was edited by hand.
+    pub save_changed: Option<String>,
+}
+
+///Checks every link in the roll log's hash chain, stopping at the first broken one, then checks the save file against the last recorded state.
+pub fn verify_chain(path: &Path, save_file: &Path) -> Result<ChainReport, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read the roll log: {}", e)),
+    };
+    let mut report = ChainReport {
+        entries: 0,
+        unchained: 0,
+        broken: None,
+        save_changed: None,
+    };
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut last_state: Option<(u64, String)> = None;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let broken = |timestamp: Option<u64>, reason: String| BrokenLink {
+            line: i + 1,
+            timestamp,
+            reason,
+        };
+        let entry = match serde_json::from_str::<RollLogEntry>(line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.broken = Some(broken(None, format!("the line can't be read: {}", e)));
+                return Ok(report);
+            }
+        };
+        let Some(hash) = entry.hash.clone() else {
+            if report.entries > 0 {
+                report.broken = Some(broken(Some(entry.timestamp), "the entry has no hash, in the middle of the chain.".to_string()));
+                return Ok(report);
+            }
+            report.unchained += 1;
+            continue;
+        };
+        if entry.prev_hash.as_deref() != Some(prev_hash.as_str()) {
+            report.broken = Some(broken(
+                Some(entry.timestamp),
+                "the entry doesn't follow the one before it. An entry before it was removed, added or reordered.".to_string(),
+            ));
+            return Ok(report);
+        }
+        let expected = entry.chain_hash().map_err(|e| e.to_string())?;
+        if expected != hash {
+            report.broken = Some(broken(Some(entry.timestamp), "the entry was edited after it was written.".to_string()));
+            return Ok(report);
+        }
+        if let Some((state_time, state_hash)) = &last_state
+            && entry.loaded_hash.as_ref().is_some_and(|loaded_hash| loaded_hash != state_hash)
+        {
+            report.broken = Some(broken(
+                Some(entry.timestamp),
+                format!(
+                    "the save file this run loaded isn't the one recorded at {}. It was edited outside dicetray in between.",
+                    format_timestamp(*state_time)
+                ),
+            ));
+            return Ok(report);
+        }
+        if let Some(state_hash) = &entry.state_hash {
+            last_state = Some((entry.timestamp, state_hash.clone()));
+        }
+        prev_hash = hash;
+        report.entries += 1;
+    }
+
+    if let Some((state_time, state_hash)) = last_state {
+        let current = match fs::read(save_file) {
+            Ok(content) => Some(sha256_hex(&content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("Failed to read the save file: {}", e)),
+        };
+        if current.as_deref() != Some(state_hash.as_str()) {
+            report.save_changed = Some(format!(
+                "The save file isn't the one recorded at {}. It was edited, replaced or removed outside dicetray since.",
+                format_timestamp(state_time)
+            ));
+        }
+    }
+    Ok(report)
+}
+
+///The SHA-256 of the bytes, as hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+///Gets the arguments dicetray was run with, i.e. "roll -t Main".
+fn current_command() -> String {
+    std::env::args().skip(1).collect::<Vec<String>>().join(" ")
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+///Formats a Unix timestamp as a UTC date and time, i.e. "2025-03-14 18:05:09".
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+///Parses a time for the log filters. Takes a UTC date ("2025-03-14"), date and time ("2025-03-14 18:05" or "2025-03-14T18:05:09"),
+///or a time ago, i.e. "30m", "2h", "3d" or "1w".
+pub fn parse_time(time: &str) -> Result<u64, String> {
+    let time = time.trim();
+    let invalid = || {
+        format!(
+            "Can't read the time {}. Use a date like 2025-03-14, a date and time like \"2025-03-14 18:05\", or a time ago like 2h or 3d.",
+            time
+        )
+    };
+
+    if let Some((amount, unit)) = time.split_at_checked(time.len().saturating_sub(1))
+        && !amount.is_empty()
+        && amount.chars().all(|c| c.is_ascii_digit())
+        && unit.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        let amount: u64 = amount.parse().map_err(|_| invalid())?;
+        let unit_seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            "w" => 604800,
+            _ => return Err(invalid()),
+        };
+        return Ok(unix_now().saturating_sub(amount.saturating_mul(unit_seconds)));
+    }
+
+    let (date, clock) = match time.split_once(['T', ' ']) {
+        Some((date, clock)) => (date, Some(clock)),
+        None => (time, None),
+    };
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|part| part.parse::<i64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let [year, month, day] = date[..] else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0;
+    if let Some(clock) = clock {
+        let clock: Vec<i64> = clock
+            .trim_end_matches('Z')
+            .split(':')
+            .map(|part| part.parse::<i64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        seconds = match clock[..] {
+            [hours, minutes] if hours < 24 && minutes < 60 => hours * 3600 + minutes * 60,
+            [hours, minutes, secs] if hours < 24 && minutes < 60 && secs < 60 => hours * 3600 + minutes * 60 + secs,
+            _ => return Err(invalid()),
+        };
+    }
+    let timestamp = days_from_civil(year, month, day) * 86400 + seconds;
+    u64::try_from(timestamp).map_err(|_| invalid())
+}
+
+///Converts days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+///Converts a (year, month, day) date to days since 1970-01-01. The inverse of civil_from_days.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///A roll log and save file in a directory of their own, removed when the test ends.
+    struct TestLog {
+        dir: PathBuf,
+    }
+
+    impl TestLog {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("dicetray-roll-log-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestLog { dir }
+        }
+
+        fn log(&self) -> PathBuf {
+            self.dir.join("dice_tray_rolls.jsonl")
+        }
+
+        fn save(&self) -> PathBuf {
+            self.dir.join("dice_tray_save.json")
+        }
+
+        fn write_state(&self, save: &str) {
+            let loaded_hash = fs::read(self.save()).ok().map(|content| sha256_hex(&content));
+            fs::write(self.save(), save).unwrap();
+            append_entries(&self.log(), &[RollLogEntry::state(loaded_hash, sha256_hex(save.as_bytes()))]).unwrap();
+        }
+
+        fn verify(&self) -> ChainReport {
+            verify_chain(&self.log(), &self.save()).unwrap()
+        }
+    }
+
+    impl Drop for TestLog {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn intact_chain_verifies() {
+        let test = TestLog::new("intact");
+        test.write_state("{\"a\":1}");
+        test.write_state("{\"a\":2}");
+        test.write_state("{\"a\":3}");
+        let report = test.verify();
+        assert_eq!(report.entries, 3);
+        assert!(report.broken.is_none());
+        assert!(report.save_changed.is_none());
+    }
+
+    #[test]
+    fn first_entry_chains_to_genesis() {
+        let test = TestLog::new("genesis");
+        test.write_state("{}");
+        let entry: RollLogEntry = serde_json::from_str(fs::read_to_string(test.log()).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(entry.prev_hash.as_deref(), Some(GENESIS_HASH));
+    }
+
+    #[test]
+    fn edited_entry_breaks_chain() {
+        let test = TestLog::new("edited");
+        test.write_state("{\"a\":1}");
+        test.write_state("{\"a\":2}");
+        let content = fs::read_to_string(test.log()).unwrap();
+        let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut entry: RollLogEntry = serde_json::from_str(&lines[1]).unwrap();
+        entry.command = "dicetray roll --cheat".to_string();
+        lines[1] = serde_json::to_string(&entry).unwrap();
+        fs::write(test.log(), lines.join("\n") + "\n").unwrap();
+
+        let broken = test.verify().broken.expect("an edited entry should break the chain");
+        assert_eq!(broken.line, 2);
+        assert!(broken.reason.contains("edited"));
+    }
+
+    #[test]
+    fn removed_entry_breaks_chain() {
+        let test = TestLog::new("removed");
+        test.write_state("{\"a\":1}");
+        test.write_state("{\"a\":2}");
+        test.write_state("{\"a\":3}");
+        let content = fs::read_to_string(test.log()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(test.log(), format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+
+        let broken = test.verify().broken.expect("a removed entry should break the chain");
+        assert_eq!(broken.line, 2);
+    }
+
+    #[test]
+    fn save_edited_outside_dicetray_is_reported() {
+        let test = TestLog::new("save-changed");
+        test.write_state("{\"a\":1}");
+        fs::write(test.save(), "{\"a\":99}").unwrap();
+        let report = test.verify();
+        assert!(report.broken.is_none());
+        assert!(report.save_changed.is_some());
+
+        //The next run loads the edited save, which breaks the chain at its state entry.
+        test.write_state("{\"a\":100}");
+        assert_eq!(test.verify().broken.map(|broken| broken.line), Some(2));
+    }
+}
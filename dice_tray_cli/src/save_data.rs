@@ -0,0 +1,289 @@
+use rust_dice::bag::BagData;
+use rust_dice::deck::DeckData;
+use rust_dice::yahtzee::YahtzeeGame;
+use serde_json::{Value, json};
+
+use std::error::Error;
+use std::fmt;
+
+use crate::cli_dice_tray::CliTrayData;
+
+///Version of the save file layout written by this build. Bump it and add a step to migrate_step whenever the layout changes in a way serde defaults can't absorb.
+///Version 0 was a bare list of trays, version 1 the CliSaveData object on its own, and version 2 wraps it in a versioned envelope.
+pub const SAVE_VERSION: u32 = 2;
+
+///Everything dice_tray_cli keeps in its save file.
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct CliSaveData {
+    trays: Vec<CliTrayData>,
+    #[serde(default)]
+    decks: Vec<DeckData>,
+    #[serde(default)]
+    bags: Vec<BagData>,
+    #[serde(default)]
+    yahtzee: Option<YahtzeeGame>,
+}
+
+///The versioned envelope written to the save file.
+#[derive(serde::Serialize)]
+struct SaveEnvelope<'a> {
+    version: u32,
+    data: &'a CliSaveData,
+}
+
+///A save file after it has been migrated to the current version.
+pub struct LoadedSave {
+    data: CliSaveData,
+    version: u32,
+}
+
+impl LoadedSave {
+    /// Get the save data, migrated to the current version
+    pub fn get_data(&self) -> &CliSaveData {
+        &self.data
+    }
+
+    /// Get the version the save file was written with
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns true if the save file was written by an older version and had to be migrated
+    pub fn was_migrated(&self) -> bool {
+        self.version < SAVE_VERSION
+    }
+}
+
+///A single tray written to its own file by "dicetray export", so it can be imported into another workspace or machine.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrayExport {
+    version: u32,
+    tray: CliTrayData,
+}
+
+impl TrayExport {
+    ///Creates an export of the tray data, stamped with the current save version.
+    pub fn new(tray: CliTrayData) -> Self {
+        TrayExport {
+            version: SAVE_VERSION,
+            tray,
+        }
+    }
+
+    /// Get the exported tray data
+    pub fn get_tray(&self) -> &CliTrayData {
+        &self.tray
+    }
+
+    ///Parses an exported tray. Exports from newer versions are refused, since they may hold data this build would drop.
+    pub fn from_json(file_content: &str) -> Result<TrayExport, SaveLoadError> {
+        let value: Value = serde_json::from_str(file_content)
+            .map_err(|e| SaveLoadError::Invalid(format!("the tray file is not valid JSON: {}", e)))?;
+        let version = save_version(&value)?;
+        if version > SAVE_VERSION {
+            return Err(SaveLoadError::NewerVersion(version));
+        }
+        serde_json::from_value(value)
+            .map_err(|e| SaveLoadError::Invalid(format!("failed to read tray file: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+///Errors from reading a save file.
+#[derive(Debug)]
+pub enum SaveLoadError {
+    ///The save was written by a newer version of dice_tray_cli, so it can't be read or safely overwritten.
+    NewerVersion(u32),
+    ///The save couldn't be parsed or migrated.
+    Invalid(String),
+}
+
+impl fmt::Display for SaveLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveLoadError::NewerVersion(version) => write!(
+                f,
+                "the save file is version {} but this build only understands up to version {}. Update dicetray to use it",
+                version, SAVE_VERSION
+            ),
+            SaveLoadError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SaveLoadError {}
+
+impl CliSaveData {
+    pub fn new(
+        trays: Vec<CliTrayData>,
+        decks: Vec<DeckData>,
+        bags: Vec<BagData>,
+        yahtzee: Option<YahtzeeGame>,
+    ) -> Self {
+        CliSaveData {
+            trays,
+            decks,
+            bags,
+            yahtzee,
+        }
+    }
+
+    ///Parses the contents of a save file, migrating saves written by older versions up to the current one.
+    pub fn from_json(file_content: &str) -> Result<LoadedSave, SaveLoadError> {
+        let mut value: Value = serde_json::from_str(file_content)
+            .map_err(|e| SaveLoadError::Invalid(format!("the save file is not valid JSON: {}", e)))?;
+
+        let found_version = save_version(&value)?;
+        if found_version > SAVE_VERSION {
+            return Err(SaveLoadError::NewerVersion(found_version));
+        }
+
+        let mut version = found_version;
+        while version < SAVE_VERSION {
+            value = migrate_step(value, version);
+            version += 1;
+        }
+
+        let data = value
+            .get_mut("data")
+            .map(Value::take)
+            .ok_or(SaveLoadError::Invalid("the save file has no data.".to_string()))?;
+        let data: CliSaveData = serde_json::from_value(data)
+            .map_err(|e| SaveLoadError::Invalid(format!("failed to read save data: {}", e)))?;
+        Ok(LoadedSave {
+            data,
+            version: found_version,
+        })
+    }
+
+    ///Writes the save data in the versioned envelope used by the current version.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&SaveEnvelope {
+            version: SAVE_VERSION,
+            data: self,
+        })
+    }
+
+    /// Get the saved trays
+    pub fn get_trays(&self) -> &Vec<CliTrayData> {
+        &self.trays
+    }
+
+    /// Get the saved decks
+    pub fn get_decks(&self) -> &Vec<DeckData> {
+        &self.decks
+    }
+
+    /// Get the saved bags
+    pub fn get_bags(&self) -> &Vec<BagData> {
+        &self.bags
+    }
+
+    /// Get the saved Yahtzee game, if one is in progress
+    pub fn get_yahtzee(&self) -> Option<&YahtzeeGame> {
+        self.yahtzee.as_ref()
+    }
+}
+
+///Works out which version wrote a save: version 0 saves are a bare list, version 1 saves an object without a version.
+fn save_version(value: &Value) -> Result<u32, SaveLoadError> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(object) => match object.get("version") {
+            Some(version) => version
+                .as_u64()
+                .map(|version| version as u32)
+                .ok_or(SaveLoadError::Invalid("the save file version is not a number.".to_string())),
+            None => Ok(1),
+        },
+        _ => Err(SaveLoadError::Invalid("the save file is not a dice tray save.".to_string())),
+    }
+}
+
+///Migrates a save from version to version + 1.
+fn migrate_step(value: Value, version: u32) -> Value {
+    match version {
+        //Version 0 saves only held trays.
+        0 => json!({ "trays": value }),
+        //Version 1 saves had no envelope.
+        1 => json!({ "version": 2, "data": value }),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A tray as every version has written it, holding a die whose result was saved when results were still u32.
+    const TRAY: &str = r#"{"label":"Main","dice_data":[{"Die32":{"rng_seed":1,"label":"d6","faces":6,"current_face":6,"current_result":{"Number":4294967295},"current_result_type":"Sum"}}]}"#;
+
+    fn load(json: &str) -> LoadedSave {
+        match CliSaveData::from_json(json) {
+            Ok(loaded) => loaded,
+            Err(e) => panic!("save failed to load: {}", e),
+        }
+    }
+
+    fn assert_tray_loaded(loaded: &LoadedSave) {
+        let trays = loaded.get_data().get_trays();
+        assert_eq!(trays.len(), 1);
+        assert_eq!(trays[0].get_label(), "Main");
+        let die = trays[0].get_dice_data().remove(0).to_die(0);
+        assert_eq!(die.get_result().is_num_or(0), u32::MAX as i64);
+    }
+
+    #[test]
+    fn version_0_bare_list_migrates() {
+        let loaded = load(&format!("[{}]", TRAY));
+        assert_eq!(loaded.get_version(), 0);
+        assert!(loaded.was_migrated());
+        assert_tray_loaded(&loaded);
+        assert!(loaded.get_data().get_decks().is_empty());
+        assert!(loaded.get_data().get_yahtzee().is_none());
+    }
+
+    #[test]
+    fn version_1_object_without_version_migrates() {
+        let loaded = load(&format!(r#"{{"trays":[{}],"decks":[]}}"#, TRAY));
+        assert_eq!(loaded.get_version(), 1);
+        assert!(loaded.was_migrated());
+        assert_tray_loaded(&loaded);
+    }
+
+    #[test]
+    fn version_2_loads_as_is() {
+        let loaded = load(&format!(r#"{{"version":2,"data":{{"trays":[{}]}}}}"#, TRAY));
+        assert_eq!(loaded.get_version(), 2);
+        assert!(!loaded.was_migrated());
+        assert_tray_loaded(&loaded);
+    }
+
+    #[test]
+    fn written_saves_load_back_at_the_current_version() {
+        let tray: CliTrayData = serde_json::from_str(TRAY).unwrap();
+        let json = CliSaveData::new(vec![tray], Vec::new(), Vec::new(), None).to_json().unwrap();
+        let loaded = load(&json);
+        assert_eq!(loaded.get_version(), SAVE_VERSION);
+        assert_tray_loaded(&loaded);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let json = format!(r#"{{"version":{},"data":{{"trays":[]}}}}"#, SAVE_VERSION + 1);
+        assert!(matches!(
+            CliSaveData::from_json(&json),
+            Err(SaveLoadError::NewerVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn saves_that_are_not_trays_are_invalid() {
+        for json in ["42", r#"{"version":"two","data":{}}"#, r#"{"version":2}"#, "not json"] {
+            assert!(matches!(CliSaveData::from_json(json), Err(SaveLoadError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs::{self, File, TryLockError};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+///Number of rotating backups kept next to the save file. Backup 1 is the most recent.
+pub const BACKUP_COUNT: usize = 5;
+
+///A backup of the save file.
+pub struct SaveBackup {
+    number: usize,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl SaveBackup {
+    ///Gets the backup number. 1 is the most recent backup.
+    pub fn get_number(&self) -> usize {
+        self.number
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    ///Gets the time the backup was written, if the file system reports it.
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+///Default number of seconds to wait for another dicetray run to release the save file.
+pub const LOCK_TIMEOUT_SECS: u64 = 10;
+const LOCK_POLL: Duration = Duration::from_millis(100);
+
+///An advisory lock on a save file, held from loading the save until it's written back.
+///The lock is on a separate ".lock" file, since saving renames a new file over the save. It's released when dropped.
+pub struct SaveLock {
+    _file: Option<File>,
+}
+
+impl SaveLock {
+    ///Locks the save file, waiting up to timeout for another run holding it to finish.
+    ///On file systems without lock support a warning is printed and the save is used unlocked.
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<SaveLock, String> {
+        let lock_path = lock_path(path);
+        if let Some(dir) = lock_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| format!("Failed to open lock file {}: {}", lock_path.display(), e))?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(SaveLock { _file: Some(file) }),
+                Err(TryLockError::WouldBlock) if start.elapsed() < timeout => thread::sleep(LOCK_POLL),
+                Err(TryLockError::WouldBlock) => {
+                    return Err(format!(
+                        "The save file {} is in use by another dicetray run. Gave up after {} seconds; try again once it finishes, or wait longer with --lock-timeout.",
+                        path.display(),
+                        timeout.as_secs()
+                    ));
+                }
+                Err(TryLockError::Error(e)) if e.kind() == ErrorKind::Unsupported => {
+                    eprintln!("Warning: File locking isn't supported here, so runs at the same time may overwrite each other.");
+                    return Ok(SaveLock { _file: None });
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(format!("Failed to lock {}: {}", lock_path.display(), e));
+                }
+            }
+        }
+    }
+}
+
+///Writes content to path without ever leaving a half written file behind.
+///The content is written and synced to a temporary file next to path, which is then renamed over it.
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    let mut temp_file = fs::File::create(&temp_path)?;
+    temp_file.write_all(content.as_bytes())?;
+    temp_file.sync_all()?;
+    drop(temp_file);
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+///Moves each backup of the save file along by one, dropping the oldest, then copies the save file into backup 1.
+///Does nothing if there is no save file yet.
+pub fn rotate_backups(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let oldest = backup_path(path, BACKUP_COUNT);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for number in (1..BACKUP_COUNT).rev() {
+        let backup = backup_path(path, number);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, number + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+///Lists the backups of the save file that exist, most recent first.
+pub fn list_backups(path: &Path) -> Vec<SaveBackup> {
+    (1..=BACKUP_COUNT)
+        .map(|number| (number, backup_path(path, number)))
+        .filter(|(_, backup)| backup.exists())
+        .map(|(number, backup)| SaveBackup {
+            number,
+            modified: fs::metadata(&backup).and_then(|meta| meta.modified()).ok(),
+            path: backup,
+        })
+        .collect()
+}
+
+///Restores a backup over the save file. The file being replaced is kept as a ".replaced" copy, so a restore can be undone.
+pub fn restore_backup(path: &Path, number: usize) -> Result<PathBuf, String> {
+    let backup = backup_path(path, number);
+    if !backup.exists() {
+        return Err(format!("No backup {} found. Run restore without a number to list backups.", number));
+    }
+    let content = fs::read_to_string(&backup)
+        .map_err(|e| format!("Failed to read backup {}: {}", backup.display(), e))?;
+
+    let replaced = path.with_extension("replaced.json");
+    if path.exists() {
+        fs::copy(path, &replaced)
+            .map_err(|e| format!("Failed to keep a copy of the current save: {}", e))?;
+    }
+    write_atomic(path, &content).map_err(|e| format!("Failed to restore backup {}: {}", number, e))?;
+    Ok(replaced)
+}
+
+///Backups are named after the save file, i.e. dice_tray_save.backup-1.json.
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    path.with_extension(format!("backup-{}.json", number))
+}
+
+///Where changes are written when the save file was changed by something else during this run.
+pub fn conflict_path(path: &Path) -> PathBuf {
+    path.with_extension("conflict.json")
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    path.with_extension("json.tmp")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///A directory of its own for a test's save files, removed when the test ends.
+    struct TestDir {
+        dir: PathBuf,
+    }
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("dicetray-save-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestDir { dir }
+        }
+
+        fn save_file(&self) -> PathBuf {
+            self.dir.join("dice_tray_save.json")
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    ///Writes each save in turn, rotating the backups before each write like saving does.
+    fn save_each(path: &Path, saves: impl IntoIterator<Item = usize>) {
+        for save in saves {
+            rotate_backups(path).unwrap();
+            write_atomic(path, &format!("save {}", save)).unwrap();
+        }
+    }
+
+    #[test]
+    fn write_atomic_replaces_the_file_and_cleans_up() {
+        let test = TestDir::new("atomic");
+        let path = test.save_file();
+        write_atomic(&path, "a much longer first save").unwrap();
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(read(&path), "second");
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn write_atomic_leaves_the_file_alone_on_failure() {
+        let test = TestDir::new("atomic-failure");
+        //A directory can't be renamed over, so the write fails after the temporary file is written.
+        let path = test.dir.join("taken.json");
+        fs::create_dir(&path).unwrap();
+        assert!(write_atomic(&path, "save").is_err());
+        assert!(path.is_dir());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn rotating_without_a_save_does_nothing() {
+        let test = TestDir::new("rotate-empty");
+        rotate_backups(&test.save_file()).unwrap();
+        assert!(list_backups(&test.save_file()).is_empty());
+    }
+
+    #[test]
+    fn backups_are_listed_most_recent_first() {
+        let test = TestDir::new("rotate-order");
+        let path = test.save_file();
+        save_each(&path, 1..=3);
+        let backups = list_backups(&path);
+        assert_eq!(backups.iter().map(|backup| backup.get_number()).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(read(backups[0].get_path()), "save 2");
+        assert_eq!(read(backups[1].get_path()), "save 1");
+        assert_eq!(backups[0].get_path(), test.dir.join("dice_tray_save.backup-1.json"));
+    }
+
+    #[test]
+    fn only_backup_count_backups_are_kept() {
+        let test = TestDir::new("rotate-limit");
+        let path = test.save_file();
+        save_each(&path, 1..=BACKUP_COUNT + 3);
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), BACKUP_COUNT);
+        //The newest backup holds the save before the current one, and the oldest ones have been dropped.
+        for backup in &backups {
+            assert_eq!(read(backup.get_path()), format!("save {}", BACKUP_COUNT + 3 - backup.get_number()));
+        }
+        assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+    }
+
+    #[test]
+    fn restoring_a_missing_backup_changes_nothing() {
+        let test = TestDir::new("restore-missing");
+        let path = test.save_file();
+        save_each(&path, 1..=2);
+        assert!(restore_backup(&path, 2).is_err());
+        assert!(restore_backup(&path, BACKUP_COUNT + 1).is_err());
+        assert_eq!(read(&path), "save 2");
+        assert!(!path.with_extension("replaced.json").exists());
+    }
+
+    #[test]
+    fn restoring_keeps_the_replaced_save() {
+        let test = TestDir::new("restore");
+        let path = test.save_file();
+        save_each(&path, 1..=3);
+        let replaced = restore_backup(&path, 2).unwrap();
+        assert_eq!(replaced, test.dir.join("dice_tray_save.replaced.json"));
+        assert_eq!(read(&path), "save 1");
+        assert_eq!(read(&replaced), "save 3");
+        //Restoring doesn't use up the backup.
+        assert_eq!(read(&backup_path(&path, 2)), "save 1");
+    }
+
+    #[test]
+    fn a_held_lock_times_out() {
+        let test = TestDir::new("lock");
+        let path = test.save_file();
+        let held = SaveLock::acquire(&path, Duration::ZERO).unwrap();
+
+        let timeout = Duration::from_millis(300);
+        let start = Instant::now();
+        let second = SaveLock::acquire(&path, timeout);
+        assert!(start.elapsed() >= timeout);
+        match second {
+            Ok(_) => panic!("the save was locked twice"),
+            Err(e) => assert!(e.contains("in use by another dicetray run"), "{}", e),
+        }
+
+        //Once the first lock is dropped the save can be locked again.
+        drop(held);
+        assert!(SaveLock::acquire(&path, Duration::ZERO).is_ok());
+    }
+}
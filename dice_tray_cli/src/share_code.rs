@@ -0,0 +1,461 @@
+use rust_dice::dice::DieResultType;
+use rust_dice::dice_data::TypedDieData;
+use rust_dice::tray::TrayResultType;
+
+use crate::cli_dice_tray::CliTrayData;
+
+///Version of the share code layout written by this build. Older versions must stay decodable, so add a new decoder rather than changing one.
+pub const SHARE_CODE_VERSION: u8 = 1;
+
+///The most dice a share code can build. Codes asking for more are refused before any dice are made.
+pub const MAX_CODE_DICE: u64 = 1000;
+
+const FLAG_SEEDS: u8 = 0b1;
+const FLAG_CUSTOM_LABEL: u8 = 0b100;
+const RESULT_TYPE_MASK: u8 = 0b11;
+
+///A die described by a share code. Codes carry what a die is, not what it currently shows.
+pub struct CodeDie {
+    label: String,
+    faces: u32,
+    result_type: DieResultType,
+    seed: Option<u64>,
+}
+
+impl CodeDie {
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_faces(&self) -> u32 {
+        self.faces
+    }
+
+    pub fn get_result_type(&self) -> DieResultType {
+        self.result_type
+    }
+
+    ///Gets the RNG seed of the die, if the code was made with seeds.
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+}
+
+///A tray definition decoded from a share code.
+pub struct TrayCode {
+    label: String,
+    modifier: i64,
+    result_type: TrayResultType,
+    dice: Vec<CodeDie>,
+}
+
+impl TrayCode {
+    ///Gets the ID of the tray the code was made from.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_modifier(&self) -> i64 {
+        self.modifier
+    }
+
+    pub fn get_result_type(&self) -> TrayResultType {
+        self.result_type
+    }
+
+    pub fn get_dice(&self) -> &Vec<CodeDie> {
+        &self.dice
+    }
+
+    ///Returns true if the dice in the code carry RNG seeds.
+    pub fn has_seeds(&self) -> bool {
+        self.dice.iter().any(|die| die.seed.is_some())
+    }
+}
+
+///Encodes a tray's definition as a short, URL-safe share code. With seeds, everyone who loads the code gets dice that roll the same faces.
+///The code is base64url of: version, flags, tray result type, modifier, label, then runs of identical dice, followed by a CRC-32 checksum.
+pub fn encode_tray(tray: &CliTrayData, include_seeds: bool) -> String {
+    let mut bytes = vec![SHARE_CODE_VERSION];
+    bytes.push(if include_seeds { FLAG_SEEDS } else { 0 });
+    bytes.push(tray_result_type_code(tray.get_result_type()));
+    write_varint(&mut bytes, zigzag(tray.get_modifier()));
+    write_string(&mut bytes, tray.get_label());
+
+    let dice: Vec<(String, u32, u8, u64)> = tray
+        .get_dice_data()
+        .iter()
+        .map(|data| match data {
+            TypedDieData::Die32(die) => (
+                die.get_label().to_string(),
+                die.get_faces(),
+                die_result_type_code(*die.get_current_result_type()),
+                die.get_seed(),
+            ),
+        })
+        .collect();
+
+    //Dice are written in runs of identical dice, so a code for 8d6 is barely longer than one for 1d6.
+    let mut runs: Vec<Vec<&(String, u32, u8, u64)>> = Vec::new();
+    for die in dice.iter() {
+        match runs.last_mut() {
+            Some(run) if run[0].0 == die.0 && run[0].1 == die.1 && run[0].2 == die.2 => run.push(die),
+            _ => runs.push(vec![die]),
+        }
+    }
+    write_varint(&mut bytes, runs.len() as u64);
+    for run in runs {
+        let (label, faces, result_type, _) = run[0];
+        let custom_label = *label != format!("d{}", faces);
+        write_varint(&mut bytes, run.len() as u64);
+        write_varint(&mut bytes, *faces as u64);
+        bytes.push(result_type | if custom_label { FLAG_CUSTOM_LABEL } else { 0 });
+        if custom_label {
+            write_string(&mut bytes, label);
+        }
+        if include_seeds {
+            for (_, _, _, seed) in run {
+                bytes.extend_from_slice(&seed.to_le_bytes());
+            }
+        }
+    }
+
+    let checksum = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    base64url_encode(&bytes)
+}
+
+///Decodes a share code, checking its checksum. Codes from every version up to SHARE_CODE_VERSION can be decoded.
+pub fn decode_tray(code: &str) -> Result<TrayCode, String> {
+    let bytes = base64url_decode(code.trim())?;
+    if bytes.len() < 5 {
+        return Err("The share code is too short. Check it was copied in full.".to_string());
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32(body).to_le_bytes() != checksum {
+        return Err("The share code's checksum doesn't match. Check it was copied in full.".to_string());
+    }
+
+    let mut reader = CodeReader { bytes: body, position: 0 };
+    match reader.read_byte()? {
+        1 => decode_v1(&mut reader),
+        version => Err(format!(
+            "The share code is version {} but this build only understands up to version {}. Update dicetray to load it.",
+            version, SHARE_CODE_VERSION
+        )),
+    }
+}
+
+fn decode_v1(reader: &mut CodeReader) -> Result<TrayCode, String> {
+    let flags = reader.read_byte()?;
+    let result_type = tray_result_type_from_code(reader.read_byte()?)?;
+    let modifier = unzigzag(reader.read_varint()?);
+    let label = reader.read_string()?;
+
+    let mut dice = Vec::new();
+    let run_count = reader.read_varint()?;
+    if run_count > MAX_CODE_DICE {
+        return Err(too_many_dice());
+    }
+    let mut total: u64 = 0;
+    for _ in 0..run_count {
+        let count = reader.read_varint()?;
+        total = total.saturating_add(count);
+        if total > MAX_CODE_DICE {
+            return Err(too_many_dice());
+        }
+        let faces = u32::try_from(reader.read_varint()?).map_err(|_| "A die in the share code has too many faces.".to_string())?;
+        if faces < 1 {
+            return Err("A die in the share code has no faces.".to_string());
+        }
+        let die_flags = reader.read_byte()?;
+        let die_label = if die_flags & FLAG_CUSTOM_LABEL != 0 {
+            reader.read_string()?
+        } else {
+            format!("d{}", faces)
+        };
+        let die_result_type = die_result_type_from_code(die_flags & RESULT_TYPE_MASK);
+        for _ in 0..count {
+            let seed = if flags & FLAG_SEEDS != 0 {
+                Some(reader.read_u64()?)
+            } else {
+                None
+            };
+            dice.push(CodeDie {
+                label: die_label.clone(),
+                faces,
+                result_type: die_result_type,
+                seed,
+            });
+        }
+    }
+    if reader.position != reader.bytes.len() {
+        return Err("The share code has unexpected data at the end.".to_string());
+    }
+
+    Ok(TrayCode {
+        label,
+        modifier,
+        result_type,
+        dice,
+    })
+}
+
+fn too_many_dice() -> String {
+    format!("The share code has more than {} dice.", MAX_CODE_DICE)
+}
+
+fn die_result_type_code(result_type: DieResultType) -> u8 {
+    match result_type {
+        DieResultType::Face => 0,
+        DieResultType::Best => 1,
+        DieResultType::Worst => 2,
+        DieResultType::Sum => 3,
+    }
+}
+
+fn die_result_type_from_code(code: u8) -> DieResultType {
+    match code {
+        1 => DieResultType::Best,
+        2 => DieResultType::Worst,
+        3 => DieResultType::Sum,
+        _ => DieResultType::Face,
+    }
+}
+
+fn tray_result_type_code(result_type: TrayResultType) -> u8 {
+    match result_type {
+        TrayResultType::Sum => 0,
+        TrayResultType::Best => 1,
+        TrayResultType::Worst => 2,
+        TrayResultType::Pattern => 3,
+    }
+}
+
+fn tray_result_type_from_code(code: u8) -> Result<TrayResultType, String> {
+    match code {
+        0 => Ok(TrayResultType::Sum),
+        1 => Ok(TrayResultType::Best),
+        2 => Ok(TrayResultType::Worst),
+        3 => Ok(TrayResultType::Pattern),
+        _ => Err(format!("Unknown tray result type {} in the share code.", code)),
+    }
+}
+
+///Reads the fields of a share code in order, failing on truncated codes instead of panicking.
+struct CodeReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl CodeReader<'_> {
+    fn read_byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or("The share code ended early. Check it was copied in full.".to_string())?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, String> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("The share code has a number that is too large.".to_string())
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0u8; 8];
+        for byte in bytes.iter_mut() {
+            *byte = self.read_byte()?;
+        }
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_varint()? as usize;
+        let end = self.position.saturating_add(length);
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or("The share code ended early. Check it was copied in full.".to_string())?;
+        self.position = end;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "The share code has a label that isn't valid text.".to_string())
+    }
+}
+
+///Writes an unsigned LEB128 number: 7 bits per byte, with the high bit set on every byte but the last.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+///Maps signed numbers to unsigned ones so small negative modifiers stay short as varints.
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+///The CRC-32 used by zip and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+///Base64 with the URL-safe alphabet and no padding, so codes can be pasted into chat and links unchanged.
+fn base64url_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64URL[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+fn base64url_decode(code: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(code.len() * 3 / 4);
+    let mut group: u32 = 0;
+    let mut bits = 0;
+    for c in code.bytes() {
+        let value = BASE64URL
+            .iter()
+            .position(|b| *b == c)
+            .ok_or(format!("The share code contains {}, which isn't part of a share code.", c as char))?;
+        group = (group << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_dice_tray::CliTray;
+    use rust_dice::dice::Die32;
+    use rust_dice::tray::Tray;
+
+    fn test_tray() -> CliTrayData {
+        let mut tray = CliTray::new("Main".to_string());
+        tray.add_die(Box::new(Die32::from_seed(0, None, 20, None, 1)));
+        tray.add_die(Box::new(Die32::from_seed(1, None, 6, None, 2)));
+        tray.add_die(Box::new(Die32::from_seed(2, None, 6, None, 3)));
+        tray.add_die(Box::new(Die32::from_seed(3, Some("Fire".to_string()), 8, Some(DieResultType::Face), 4)));
+        tray.set_modifier(-3);
+        CliTrayData::from(&tray as &dyn Tray)
+    }
+
+    ///Builds a v1 code body by hand, with a valid checksum, for codes encode_tray would never write.
+    fn code_from_body(body: &[u8]) -> String {
+        let mut bytes = body.to_vec();
+        bytes.extend_from_slice(&crc32(body).to_le_bytes());
+        base64url_encode(&bytes)
+    }
+
+    fn decode_error(code: &str) -> String {
+        match decode_tray(code) {
+            Ok(_) => panic!("{} should not decode", code),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_tray_definition() {
+        let tray = test_tray();
+        let decoded = decode_tray(&encode_tray(&tray, false)).unwrap();
+        assert_eq!(decoded.get_label(), "Main");
+        assert_eq!(decoded.get_modifier(), -3);
+        assert!(!decoded.has_seeds());
+        let dice: Vec<(&str, u32)> = decoded.get_dice().iter().map(|die| (die.get_label(), die.get_faces())).collect();
+        assert_eq!(dice, vec![("d20", 20), ("d6", 6), ("d6", 6), ("Fire", 8)]);
+    }
+
+    #[test]
+    fn round_trip_keeps_seeds() {
+        let tray = test_tray();
+        let seeds: Vec<u64> = tray
+            .get_dice_data()
+            .iter()
+            .map(|data| match data {
+                TypedDieData::Die32(die) => die.get_seed(),
+            })
+            .collect();
+        let decoded = decode_tray(&encode_tray(&tray, true)).unwrap();
+        let decoded_seeds: Vec<u64> = decoded.get_dice().iter().filter_map(|die| die.get_seed()).collect();
+        assert_eq!(decoded_seeds, seeds);
+    }
+
+    #[test]
+    fn bad_checksum_is_refused() {
+        let mut code: Vec<char> = encode_tray(&test_tray(), false).chars().collect();
+        let middle = code.len() / 2;
+        code[middle] = if code[middle] == 'A' { 'B' } else { 'A' };
+        let error = decode_error(&code.into_iter().collect::<String>());
+        assert!(error.contains("checksum"));
+    }
+
+    #[test]
+    fn oversized_dice_counts_are_refused() {
+        //Version 1, no flags, sum, modifier 0, empty label, then one run of MAX_CODE_DICE + 1 d6.
+        let mut body = vec![1, 0, 0, 0, 0];
+        write_varint(&mut body, 1);
+        write_varint(&mut body, MAX_CODE_DICE + 1);
+        write_varint(&mut body, 6);
+        body.push(0);
+        assert!(decode_error(&code_from_body(&body)).contains("more than"));
+
+        //Runs that are each small but add up to too many dice.
+        let mut body = vec![1, 0, 0, 0, 0];
+        write_varint(&mut body, 2);
+        for _ in 0..2 {
+            write_varint(&mut body, u64::MAX / 2 + 1);
+            write_varint(&mut body, 6);
+            body.push(0);
+        }
+        assert!(decode_error(&code_from_body(&body)).contains("more than"));
+
+        let mut body = vec![1, 0, 0, 0, 0];
+        write_varint(&mut body, u64::MAX);
+        assert!(decode_error(&code_from_body(&body)).contains("more than"));
+    }
+
+    #[test]
+    fn truncated_code_is_refused() {
+        let body = vec![1, 0, 0, 0];
+        assert!(decode_error(&code_from_body(&body)).contains("ended early"));
+    }
+}
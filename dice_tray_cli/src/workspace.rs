@@ -0,0 +1,248 @@
+use dirs::data_local_dir;
+
+use std::error::Error;
+use std::fs::{self, create_dir_all};
+use std::path::{Path, PathBuf};
+
+use crate::save_file::write_atomic;
+
+///Environment variable that overrides the dice-tray data directory, i.e. to point scripts and tests at a temp directory.
+pub const DATA_DIR_ENV: &str = "DICE_TRAY_DATA_DIR";
+///Name of the workspace saved in the data directory itself. It always exists and can't be deleted.
+pub const DEFAULT_WORKSPACE: &str = "default";
+const SAVE_FILE_NAME: &str = "dice_tray_save.json";
+
+///The workspace used when none is given with --workspace. Stored in dice_tray_workspace.json.
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct WorkspaceConfig {
+    active: Option<String>,
+}
+
+///Gets the dice-tray data directory, creating it if required. The DICE_TRAY_DATA_DIR environment variable overrides the default location.
+pub fn data_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let data_dir = match std::env::var_os(DATA_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match data_local_dir() {
+            Some(dir) => dir.join("dice-tray"),
+            None => {
+                eprintln!("Warning: Could not get local data directory, using current directory");
+                std::env::current_dir()?.join("dice-tray")
+            }
+        },
+    };
+
+    if !data_dir.exists() {
+        create_dir_all(&data_dir)?;
+    }
+    Ok(data_dir)
+}
+
+///Picks the save file for this run. An explicit --save-file wins, then --workspace, then the active workspace.
+pub fn resolve_save_file(workspace: Option<&str>, save_file: Option<&str>) -> Result<PathBuf, String> {
+    if let Some(save_file) = save_file {
+        return Ok(PathBuf::from(save_file));
+    }
+    resolve_workspace_save(&data_dir().map_err(|e| e.to_string())?, workspace)
+}
+
+///Picks the save file of the workspace given, or of the active workspace, within the data directory.
+fn resolve_workspace_save(data_dir: &Path, workspace: Option<&str>) -> Result<PathBuf, String> {
+    let workspace = match workspace {
+        Some(workspace) => workspace.to_string(),
+        None => active_in(data_dir)?,
+    };
+    let dir = workspace_dir(data_dir, &workspace)?;
+    if !dir.exists() {
+        return Err(format!(
+            "No workspace named {}. Create it with: dicetray workspace new {}",
+            workspace, workspace
+        ));
+    }
+    Ok(dir.join(SAVE_FILE_NAME))
+}
+
+///Gets the name of the active workspace.
+pub fn active_workspace() -> Result<String, String> {
+    active_in(&data_dir().map_err(|e| e.to_string())?)
+}
+
+fn active_in(data_dir: &Path) -> Result<String, String> {
+    Ok(load_config(data_dir)?
+        .active
+        .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string()))
+}
+
+///Lists every workspace, the default workspace first.
+pub fn list_workspaces() -> Result<Vec<String>, String> {
+    let mut workspaces = Vec::new();
+    if let Ok(entries) = fs::read_dir(workspaces_dir(&data_dir().map_err(|e| e.to_string())?)) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if entry.path().is_dir()
+                && let Some(name) = entry.file_name().to_str()
+            {
+                workspaces.push(name.to_string());
+            }
+        }
+    }
+    workspaces.sort();
+    workspaces.insert(0, DEFAULT_WORKSPACE.to_string());
+    Ok(workspaces)
+}
+
+///Creates a new, empty workspace.
+pub fn new_workspace(name: &str) -> Result<(), String> {
+    check_workspace_name(name)?;
+    let dir = workspace_dir(&data_dir().map_err(|e| e.to_string())?, name)?;
+    if dir.exists() {
+        return Err(format!("A workspace named {} already exists.", name));
+    }
+    create_dir_all(&dir).map_err(|e| format!("Failed to create workspace {}: {}", name, e))
+}
+
+///Makes the workspace the active one, used whenever --workspace isn't given.
+pub fn switch_workspace(name: &str) -> Result<(), String> {
+    let data_dir = data_dir().map_err(|e| e.to_string())?;
+    if !workspace_dir(&data_dir, name)?.exists() {
+        return Err(format!("No workspace named {}.", name));
+    }
+    let active = if name == DEFAULT_WORKSPACE {
+        None
+    } else {
+        Some(name.to_string())
+    };
+    save_config(&data_dir, &WorkspaceConfig { active })
+}
+
+///Deletes a workspace and everything saved in it. The default and active workspaces can't be deleted.
+pub fn delete_workspace(name: &str) -> Result<(), String> {
+    if name == DEFAULT_WORKSPACE {
+        return Err("The default workspace can't be deleted.".to_string());
+    }
+    if active_workspace()? == name {
+        return Err(format!(
+            "Workspace {} is active. Switch to another workspace before deleting it.",
+            name
+        ));
+    }
+    let dir = workspace_dir(&data_dir().map_err(|e| e.to_string())?, name)?;
+    if !dir.exists() {
+        return Err(format!("No workspace named {}.", name));
+    }
+    fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete workspace {}: {}", name, e))
+}
+
+///The default workspace lives in the data directory itself, so saves from before workspaces existed are its save.
+fn workspace_dir(data_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_WORKSPACE {
+        return Ok(data_dir.to_path_buf());
+    }
+    check_workspace_name(name)?;
+    Ok(workspaces_dir(data_dir).join(name))
+}
+
+fn workspaces_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("workspaces")
+}
+
+///Workspace names become folder names, so they're limited to letters, numbers, dashes and underscores.
+fn check_workspace_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid && name != DEFAULT_WORKSPACE {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} can't be used as a workspace name. Use letters, numbers, dashes and underscores.",
+            name
+        ))
+    }
+}
+
+fn config_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("dice_tray_workspace.json")
+}
+
+fn load_config(data_dir: &Path) -> Result<WorkspaceConfig, String> {
+    match fs::read_to_string(config_file(data_dir)) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to read the workspace config: {}", e)),
+        Err(_) => Ok(WorkspaceConfig::default()),
+    }
+}
+
+fn save_config(data_dir: &Path, config: &WorkspaceConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    write_atomic(&config_file(data_dir), &content).map_err(|e| format!("Failed to save the workspace config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///A data directory of its own for a test, removed when the test ends.
+    struct TestDir {
+        dir: PathBuf,
+    }
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("dicetray-workspace-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            create_dir_all(workspaces_dir(&dir)).unwrap();
+            TestDir { dir }
+        }
+
+        fn add_workspace(&self, name: &str) -> PathBuf {
+            let dir = workspace_dir(&self.dir, name).unwrap();
+            create_dir_all(&dir).unwrap();
+            dir.join(SAVE_FILE_NAME)
+        }
+
+        fn set_active(&self, name: &str) {
+            save_config(&self.dir, &WorkspaceConfig { active: Some(name.to_string()) }).unwrap();
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn save_file_wins_over_every_workspace() {
+        let save_file = resolve_save_file(Some("no-such-workspace"), Some("elsewhere/save.json")).unwrap();
+        assert_eq!(save_file, PathBuf::from("elsewhere/save.json"));
+    }
+
+    #[test]
+    fn workspace_wins_over_the_active_workspace() {
+        let test = TestDir::new("given");
+        let given = test.add_workspace("given");
+        test.add_workspace("active");
+        test.set_active("active");
+        assert_eq!(resolve_workspace_save(&test.dir, Some("given")).unwrap(), given);
+        assert_eq!(resolve_workspace_save(&test.dir, Some(DEFAULT_WORKSPACE)).unwrap(), test.dir.join(SAVE_FILE_NAME));
+    }
+
+    #[test]
+    fn active_workspace_is_used_otherwise() {
+        let test = TestDir::new("active");
+        assert_eq!(resolve_workspace_save(&test.dir, None).unwrap(), test.dir.join(SAVE_FILE_NAME));
+        let active = test.add_workspace("active");
+        test.set_active("active");
+        assert_eq!(resolve_workspace_save(&test.dir, None).unwrap(), active);
+        assert!(!config_file(&test.dir).with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn missing_workspaces_are_refused() {
+        let test = TestDir::new("missing");
+        assert!(resolve_workspace_save(&test.dir, Some("missing")).is_err());
+        test.set_active("deleted");
+        assert!(resolve_workspace_save(&test.dir, None).is_err());
+        assert!(resolve_workspace_save(&test.dir, Some("../escape")).is_err());
+    }
+}
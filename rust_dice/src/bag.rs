@@ -0,0 +1,277 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+///A kind of token in a bag, with how many are still in the bag and how many have been drawn out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenCount {
+    label: String,
+    in_bag: u32,
+    drawn: u32,
+}
+
+impl TokenCount {
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    ///Gets the number of these tokens still in the bag.
+    pub fn get_in_bag(&self) -> u32 {
+        self.in_bag
+    }
+
+    ///Gets the number of these tokens drawn out of the bag and not yet returned.
+    pub fn get_drawn(&self) -> u32 {
+        self.drawn
+    }
+}
+
+/// Represents a physical bag of labelled tokens, like a chaos bag or a cup of chits. Tokens are drawn without replacement until returned.
+/// Like dice, each bag has its own SmallRng so draws persist between sessions.
+#[derive(Debug, Clone)]
+pub struct Bag {
+    label: String,
+    rng: SmallRng,
+    tokens: Vec<TokenCount>,
+}
+
+impl Bag {
+    ///Creates a new bag holding the provided tokens, given as (label, count). Counts for repeated labels are added together.
+    pub fn new(label: String, tokens: Vec<(String, u32)>) -> Self {
+        let mut new_bag = Bag {
+            label,
+            rng: SmallRng::from_rng(&mut rand::rng()),
+            tokens: Vec::new(),
+        };
+        for (token, count) in tokens {
+            new_bag.add_tokens(&token, count);
+        }
+        new_bag
+    }
+
+    ///Creates a Bag from bag data - the RNG is reseeded from the saved seed, as SmallRng can't be serialized with serde.
+    pub fn from_data(data: &BagData) -> Self {
+        Bag {
+            label: data.label.clone(),
+            rng: SmallRng::seed_from_u64(data.rng_seed),
+            tokens: data.tokens.clone(),
+        }
+    }
+
+    ///Gets the label used to identify the bag.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    ///Returns a u64 that can be used to generate new RNG the next time the bag is instantiated.
+    pub fn get_rng_seed(&self) -> u64 {
+        self.rng.clone().next_u64()
+    }
+
+    ///Gets the composition of the bag: every kind of token, with how many are in the bag and how many are drawn.
+    pub fn get_tokens(&self) -> &Vec<TokenCount> {
+        &self.tokens
+    }
+
+    ///Gets the number of tokens left in the bag.
+    pub fn get_in_bag_count(&self) -> u32 {
+        self.tokens.iter().map(|token| token.in_bag).sum()
+    }
+
+    ///Puts count new tokens with the label into the bag.
+    pub fn add_tokens(&mut self, label: &str, count: u32) {
+        match self.tokens.iter_mut().find(|token| token.label.eq_ignore_ascii_case(label)) {
+            Some(token) => token.in_bag = token.in_bag.saturating_add(count),
+            None => self.tokens.push(TokenCount {
+                label: label.to_string(),
+                in_bag: count,
+                drawn: 0,
+            }),
+        }
+    }
+
+    ///Takes count tokens with the label out of the bag for good. Only tokens still in the bag can be removed.
+    pub fn remove_tokens(&mut self, label: &str, count: u32) -> Result<(), String> {
+        let index = self.token_index(label)?;
+        let token = &mut self.tokens[index];
+        if token.in_bag < count {
+            return Err(format!(
+                "Only {} {} token(s) are in bag {}, can't remove {}.",
+                token.in_bag, label, self.label, count
+            ));
+        }
+        token.in_bag -= count;
+        if token.in_bag == 0 && token.drawn == 0 {
+            self.tokens.remove(index);
+        }
+        Ok(())
+    }
+
+    ///Draws count tokens without replacement. Each draw picks a token weighted by how many of each kind are left.
+    ///Returns an error without drawing anything if the bag doesn't hold enough tokens.
+    pub fn draw(&mut self, count: u32) -> Result<Vec<String>, String> {
+        let available = self.get_in_bag_count();
+        if count > available {
+            return Err(format!(
+                "Only {} token(s) left in bag {}, can't draw {}.",
+                available, self.label, count
+            ));
+        }
+
+        let mut drawn = Vec::new();
+        for _i in 0..count {
+            let mut pick = self.rng.random_range(0..self.get_in_bag_count());
+            for token in self.tokens.iter_mut() {
+                if pick < token.in_bag {
+                    token.in_bag -= 1;
+                    token.drawn += 1;
+                    drawn.push(token.label.clone());
+                    break;
+                }
+                pick -= token.in_bag;
+            }
+        }
+        Ok(drawn)
+    }
+
+    ///Returns count drawn tokens with the label to the bag.
+    pub fn return_tokens(&mut self, label: &str, count: u32) -> Result<(), String> {
+        let index = self.token_index(label)?;
+        let token = &mut self.tokens[index];
+        if token.drawn < count {
+            return Err(format!(
+                "Only {} {} token(s) are drawn from bag {}, can't return {}.",
+                token.drawn, label, self.label, count
+            ));
+        }
+        token.drawn -= count;
+        token.in_bag += count;
+        Ok(())
+    }
+
+    ///Returns every drawn token to the bag, returning the number of tokens put back.
+    pub fn return_all(&mut self) -> u32 {
+        let mut returned = 0;
+        for token in self.tokens.iter_mut() {
+            returned += token.drawn;
+            token.in_bag += token.drawn;
+            token.drawn = 0;
+        }
+        returned
+    }
+
+    fn token_index(&self, label: &str) -> Result<usize, String> {
+        self.tokens
+            .iter()
+            .position(|token| token.label.eq_ignore_ascii_case(label))
+            .ok_or(format!("No {} tokens in bag {}.", label, self.label))
+    }
+}
+
+///Bag data is used to save and load bags with serde, the same way DieData32 is used for dice.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BagData {
+    rng_seed: u64,
+    label: String,
+    tokens: Vec<TokenCount>,
+}
+
+impl From<&Bag> for BagData {
+    fn from(bag: &Bag) -> Self {
+        BagData {
+            rng_seed: bag.get_rng_seed(),
+            label: bag.get_label().to_string(),
+            tokens: bag.get_tokens().clone(),
+        }
+    }
+}
+
+impl BagData {
+    ///Gets the bag data label as a string slice.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_bag(tokens: Vec<(&str, u32)>, seed: u64) -> Bag {
+        let bag = Bag::new("Chaos".to_string(), tokens.into_iter().map(|(label, count)| (label.to_string(), count)).collect());
+        let mut data = BagData::from(&bag);
+        data.rng_seed = seed;
+        Bag::from_data(&data)
+    }
+
+    fn counts(bag: &Bag) -> Vec<(&str, u32, u32)> {
+        bag.get_tokens().iter().map(|token| (token.get_label(), token.get_in_bag(), token.get_drawn())).collect()
+    }
+
+    #[test]
+    fn draws_until_the_bag_is_empty() {
+        let mut bag = seeded_bag(vec![("Skull", 3), ("Star", 1), ("Tentacle", 2)], 1);
+        let mut drawn = Vec::new();
+        for _ in 0..6 {
+            drawn.extend(bag.draw(1).unwrap());
+        }
+        drawn.sort();
+        assert_eq!(drawn, vec!["Skull", "Skull", "Skull", "Star", "Tentacle", "Tentacle"]);
+        assert_eq!(counts(&bag), vec![("Skull", 0, 3), ("Star", 0, 1), ("Tentacle", 0, 2)]);
+        assert!(bag.draw(1).unwrap_err().contains("Only 0 token(s)"));
+    }
+
+    #[test]
+    fn drawing_too_many_takes_nothing() {
+        let mut bag = seeded_bag(vec![("Skull", 2)], 1);
+        assert!(bag.draw(3).is_err());
+        assert_eq!(counts(&bag), vec![("Skull", 2, 0)]);
+    }
+
+    #[test]
+    fn draws_are_weighted_by_tokens_left() {
+        let mut bag = seeded_bag(vec![("Common", 99), ("Rare", 1)], 1);
+        let mut rare = 0;
+        for _ in 0..1000 {
+            if bag.draw(1).unwrap() == vec!["Rare".to_string()] {
+                rare += 1;
+            }
+            bag.return_all();
+        }
+        assert!(rare < 50, "Rare came up {} times in 1000 draws", rare);
+
+        //Kinds with none left are never picked.
+        let mut bag = seeded_bag(vec![("Left", 3)], 2);
+        bag.tokens.insert(
+            0,
+            TokenCount {
+                label: "Gone".to_string(),
+                in_bag: 0,
+                drawn: 1,
+            },
+        );
+        assert_eq!(bag.draw(3).unwrap(), vec!["Left", "Left", "Left"]);
+    }
+
+    #[test]
+    fn returns_count_the_tokens_put_back() {
+        let mut bag = seeded_bag(vec![("Skull", 2), ("Star", 2)], 1);
+        bag.draw(4).unwrap();
+        bag.return_tokens("skull", 1).unwrap();
+        assert!(bag.return_tokens("Skull", 2).is_err());
+        assert_eq!(bag.return_all(), 3);
+        assert_eq!(bag.return_all(), 0);
+        assert_eq!(counts(&bag), vec![("Skull", 2, 0), ("Star", 2, 0)]);
+    }
+
+    #[test]
+    fn reloaded_bags_draw_the_same_tokens() {
+        let bag = seeded_bag(vec![("Skull", 5), ("Star", 5)], 3);
+        let json = serde_json::to_string(&BagData::from(&bag)).unwrap();
+        let data: BagData = serde_json::from_str(&json).unwrap();
+        let mut first = Bag::from_data(&data);
+        let mut second = Bag::from_data(&data);
+        assert_eq!(first.draw(10).unwrap(), second.draw(10).unwrap());
+        assert_eq!(counts(&first), counts(&second));
+    }
+}
@@ -0,0 +1,263 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fmt::Write;
+
+///A hash commitment to the seed a die's RNG was restarted from, published before rolling so the rolls can be checked afterwards.
+///Every roll made while committed is recorded as (face count, face), since dice can change size between rolls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedCommitment {
+    label: String,
+    seed: u64,
+    ///Random hex added to the seed before hashing, so the seed can't be found by hashing every u64.
+    salt: String,
+    hash: String,
+    rolls: Vec<(u32, u32)>,
+    ///The generator the committed die rolls with. Commitments saved before it was recorded used SmallRng.
+    #[serde(default)]
+    generator: RollGenerator,
+}
+
+impl SeedCommitment {
+    ///Creates a commitment to a new random seed. The die must restart its RNG from get_seed.
+    pub fn new(label: &str) -> Self {
+        let mut rng = rand::rng();
+        let seed = rng.next_u64();
+        let salt = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+        SeedCommitment {
+            label: label.to_string(),
+            seed,
+            hash: commitment_hash(seed, &salt, RollGenerator::ChaCha8),
+            salt,
+            rolls: Vec::new(),
+            generator: RollGenerator::ChaCha8,
+        }
+    }
+
+    ///Gets the label of the committed die.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    ///Gets the committed seed. Only share it when revealing.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_salt(&self) -> &str {
+        &self.salt
+    }
+
+    ///Gets the SHA-256 hash of the seed and salt, as hex. This is what gets published before rolling.
+    pub fn get_hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn get_generator(&self) -> RollGenerator {
+        self.generator
+    }
+
+    ///Gets the (face count, face) of each roll made since the commitment.
+    pub fn get_rolls(&self) -> &Vec<(u32, u32)> {
+        &self.rolls
+    }
+
+    ///Records a roll made by the committed die.
+    pub fn record_roll(&mut self, faces: u32, face: u32) {
+        self.rolls.push((faces, face));
+    }
+
+    ///Rebuilds the committed die's RNG as it is after the recorded rolls, by replaying them from the seed.
+    pub fn replay_rng(&self) -> DieRng {
+        let mut rng = DieRng::from_seed(self.seed, self.generator);
+        for (faces, _) in self.rolls.iter() {
+            roll_face(&mut rng, *faces);
+        }
+        rng
+    }
+
+    ///Checks a revealed commitment: the seed and salt must hash to the committed hash,
+    ///and replaying the rolls from the seed must give the recorded faces. Returns the number of rolls checked.
+    pub fn verify(&self) -> Result<usize, String> {
+        if commitment_hash(self.seed, &self.salt, self.generator) != self.hash.to_ascii_lowercase() {
+            return Err(format!(
+                "The seed and salt don't match the commitment hash {}. This isn't the seed that was committed to.",
+                self.hash
+            ));
+        }
+        let mut rng = DieRng::from_seed(self.seed, self.generator);
+        for (i, (faces, face)) in self.rolls.iter().enumerate() {
+            if *faces < 1 {
+                return Err(format!("Roll {} is on a die with no faces.", i + 1));
+            }
+            let expected = roll_face(&mut rng, *faces);
+            if expected != *face {
+                return Err(format!(
+                    "Roll {} of {} claims a d{} rolled {}, but the seed rolls {}.",
+                    i + 1,
+                    self.label,
+                    faces,
+                    face,
+                    expected
+                ));
+            }
+        }
+        Ok(self.rolls.len())
+    }
+}
+
+///The random number generator a die rolls with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RollGenerator {
+    ///rand's SmallRng, which earlier versions rolled with. Its output differs between targets and rand versions,
+    ///so it's only kept to replay commitments made before ChaCha8.
+    #[default]
+    SmallRng,
+    ///ChaCha with 8 rounds, keyed with the seed. Its output is the same on every target, so anyone can replay it.
+    ChaCha8,
+}
+
+///A die's RNG. Dice roll with ChaCha8, apart from dice still committed from before it, which keep rolling with SmallRng until revealed.
+#[derive(Debug, Clone)]
+pub enum DieRng {
+    SmallRng(SmallRng),
+    ChaCha8(Box<ChaCha8Rng>),
+}
+
+impl DieRng {
+    ///Starts a generator from a seed. The same seed always gives the same ChaCha8 rolls: its key is the seed's
+    ///8 little-endian bytes followed by 24 zero bytes.
+    pub fn from_seed(seed: u64, generator: RollGenerator) -> Self {
+        match generator {
+            RollGenerator::SmallRng => DieRng::SmallRng(SmallRng::seed_from_u64(seed)),
+            RollGenerator::ChaCha8 => {
+                let mut key = [0u8; 32];
+                key[..8].copy_from_slice(&seed.to_le_bytes());
+                DieRng::ChaCha8(Box::new(ChaCha8Rng::from_seed(key)))
+            }
+        }
+    }
+
+    ///Starts a ChaCha8 generator from a new random seed.
+    pub fn random() -> Self {
+        DieRng::from_seed(rand::rng().next_u64(), RollGenerator::ChaCha8)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        match self {
+            DieRng::SmallRng(rng) => rng.next_u64(),
+            DieRng::ChaCha8(rng) => rng.next_u64(),
+        }
+    }
+}
+
+///Rolls a face the way Die32 does. Commitments replay rolls with this, so it must stay in step with Die32::roll.
+///ChaCha8 faces are pinned: each roll takes the next u32 from the generator, rejects it if it's at or above the largest
+///multiple of faces that fits in a u32, and otherwise rolls the u32 modulo faces, plus 1.
+pub fn roll_face(rng: &mut DieRng, faces: u32) -> u32 {
+    match rng {
+        DieRng::SmallRng(rng) => rng.random_range(1..=faces),
+        DieRng::ChaCha8(rng) => {
+            let span = 1u64 << 32;
+            let limit = span - span % faces as u64;
+            loop {
+                let value = rng.next_u32() as u64;
+                if value < limit {
+                    return (value % faces as u64) as u32 + 1;
+                }
+            }
+        }
+    }
+}
+
+///The SHA-256 hash, as hex, that commits to a seed and salt. The hash also covers the generator, so a commitment can't be replayed with another one.
+pub fn commitment_hash(seed: u64, salt: &str, generator: RollGenerator) -> String {
+    let preimage = match generator {
+        RollGenerator::SmallRng => format!("dicetray-seed-commitment:v1:{}:{}", seed, salt),
+        RollGenerator::ChaCha8 => format!("dicetray-seed-commitment:v2:chacha8:{}:{}", seed, salt),
+    };
+    let digest = Sha256::digest(preimage.as_bytes());
+    digest.iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn committed(seed: u64, rolls: &[(u32, u32)]) -> SeedCommitment {
+        let salt = "00112233445566778899aabbccddeeff".to_string();
+        SeedCommitment {
+            label: "d20".to_string(),
+            seed,
+            hash: commitment_hash(seed, &salt, RollGenerator::ChaCha8),
+            salt,
+            rolls: rolls.to_vec(),
+            generator: RollGenerator::ChaCha8,
+        }
+    }
+
+    #[test]
+    fn fixed_seed_rolls_pinned_faces() {
+        //These faces must never change: published commitments and seeded share codes replay them.
+        let mut rng = DieRng::from_seed(42, RollGenerator::ChaCha8);
+        let d20: Vec<u32> = (0..10).map(|_| roll_face(&mut rng, 20)).collect();
+        assert_eq!(d20, vec![12, 14, 20, 1, 3, 16, 19, 15, 3, 6]);
+
+        let mut rng = DieRng::from_seed(42, RollGenerator::ChaCha8);
+        let d6: Vec<u32> = (0..10).map(|_| roll_face(&mut rng, 6)).collect();
+        assert_eq!(d6, vec![4, 6, 2, 3, 5, 2, 1, 1, 1, 4]);
+    }
+
+    #[test]
+    fn faces_stay_in_range() {
+        let mut rng = DieRng::from_seed(7, RollGenerator::ChaCha8);
+        for faces in [1, 2, 3, 7, 100, u32::MAX] {
+            for _ in 0..100 {
+                assert!((1..=faces).contains(&roll_face(&mut rng, faces)));
+            }
+        }
+    }
+
+    #[test]
+    fn verify_accepts_replayed_rolls() {
+        let commitment = committed(42, &[(20, 12), (20, 14), (6, 2), (20, 1)]);
+        assert_eq!(commitment.verify(), Ok(4));
+    }
+
+    #[test]
+    fn verify_rejects_wrong_face() {
+        let commitment = committed(42, &[(20, 12), (20, 13)]);
+        assert!(commitment.verify().unwrap_err().contains("Roll 2"));
+    }
+
+    #[test]
+    fn verify_rejects_wrong_seed_or_generator() {
+        let mut commitment = committed(42, &[]);
+        commitment.seed = 43;
+        assert!(commitment.verify().is_err());
+
+        let mut commitment = committed(42, &[]);
+        commitment.generator = RollGenerator::SmallRng;
+        assert!(commitment.verify().is_err());
+    }
+
+    #[test]
+    fn replay_continues_after_recorded_rolls() {
+        let commitment = committed(42, &[(20, 12), (20, 14)]);
+        let mut rng = commitment.replay_rng();
+        assert_eq!(roll_face(&mut rng, 20), 20);
+    }
+
+    #[test]
+    fn commitments_without_generator_load_as_small_rng() {
+        let json = r#"{"label":"d6","seed":1,"salt":"ab","hash":"cd","rolls":[]}"#;
+        let commitment: SeedCommitment = serde_json::from_str(json).unwrap();
+        assert_eq!(commitment.get_generator(), RollGenerator::SmallRng);
+    }
+}
@@ -0,0 +1,388 @@
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+///Name of the pile cards are drawn from. Every deck has one.
+pub const DRAW_PILE: &str = "draw";
+///Name of the pile cards are discarded to. Every deck has one.
+pub const DISCARD_PILE: &str = "discard";
+
+///A named pile of cards. The top of the pile is the last card in the vector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pile {
+    name: String,
+    cards: Vec<String>,
+}
+
+impl Pile {
+    ///Creates a new, empty pile.
+    pub fn new(name: String) -> Self {
+        Pile {
+            name,
+            cards: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    ///Gets the cards in the pile, bottom card first.
+    pub fn get_cards(&self) -> &Vec<String> {
+        &self.cards
+    }
+
+    ///Gets the top card of the pile, if there is one.
+    pub fn top(&self) -> Option<&str> {
+        self.cards.last().map(|card| card.as_str())
+    }
+}
+
+/// Represents a physical deck of cards. Like dice, each deck has its own SmallRng used to shuffle and cut.
+/// Cards are moved between named piles, so drawn, discarded and dealt cards stay part of the deck.
+#[derive(Debug, Clone)]
+pub struct Deck {
+    label: String,
+    rng: SmallRng,
+    piles: Vec<Pile>,
+}
+
+impl Deck {
+    /// Creates a new deck with the provided cards in the draw pile and an empty discard pile.
+    /// The new deck is shuffled on creation, just like a new die is rolled.
+    pub fn new(label: String, cards: Vec<String>) -> Self {
+        let mut new_deck = Deck {
+            label,
+            rng: SmallRng::from_rng(&mut rand::rng()),
+            piles: vec![
+                Pile {
+                    name: DRAW_PILE.to_string(),
+                    cards,
+                },
+                Pile::new(DISCARD_PILE.to_string()),
+            ],
+        };
+
+        let _ = new_deck.shuffle(DRAW_PILE);
+        new_deck
+    }
+
+    /// Creates a standard 52 card deck, optionally with two jokers. Cards are named by rank and suit i.e. "AS", "10H", "QD", "JK1".
+    pub fn standard(label: String, jokers: bool) -> Self {
+        let mut cards = Vec::new();
+        for suit in ["C", "D", "H", "S"] {
+            for rank in ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K"] {
+                cards.push(format!("{}{}", rank, suit));
+            }
+        }
+        if jokers {
+            cards.push("JK1".to_string());
+            cards.push("JK2".to_string());
+        }
+        Deck::new(label, cards)
+    }
+
+    /// Creates a 78 card tarot deck, with the 22 major arcana and four suits of minor arcana.
+    pub fn tarot(label: String) -> Self {
+        let mut cards: Vec<String> = [
+            "The Fool", "The Magician", "The High Priestess", "The Empress", "The Emperor",
+            "The Hierophant", "The Lovers", "The Chariot", "Strength", "The Hermit",
+            "Wheel of Fortune", "Justice", "The Hanged Man", "Death", "Temperance", "The Devil",
+            "The Tower", "The Star", "The Moon", "The Sun", "Judgement", "The World",
+        ]
+        .iter()
+        .map(|card| card.to_string())
+        .collect();
+        for suit in ["Wands", "Cups", "Swords", "Pentacles"] {
+            for rank in [
+                "Ace", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten",
+                "Page", "Knight", "Queen", "King",
+            ] {
+                cards.push(format!("{} of {}", rank, suit));
+            }
+        }
+        Deck::new(label, cards)
+    }
+
+    ///Creates a Deck from deck data - the RNG is reseeded from the saved seed, as SmallRng can't be serialized with serde.
+    pub fn from_data(data: &DeckData) -> Self {
+        let mut piles = data.piles.clone();
+        for required in [DRAW_PILE, DISCARD_PILE] {
+            if !piles.iter().any(|pile| pile.name == required) {
+                piles.push(Pile::new(required.to_string()));
+            }
+        }
+
+        Deck {
+            label: data.label.clone(),
+            rng: SmallRng::seed_from_u64(data.rng_seed),
+            piles,
+        }
+    }
+
+    ///Gets the label used to identify the deck.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    ///Returns a u64 that can be used to generate new RNG the next time the deck is instantiated.
+    pub fn get_rng_seed(&self) -> u64 {
+        self.rng.clone().next_u64()
+    }
+
+    ///Gets every pile in the deck, the draw and discard piles first.
+    pub fn get_piles(&self) -> &Vec<Pile> {
+        &self.piles
+    }
+
+    ///Gets the pile with the given name, if it exists.
+    pub fn get_pile(&self, name: &str) -> Option<&Pile> {
+        self.piles.iter().find(|pile| pile.name == name)
+    }
+
+    ///Gets the total number of cards in every pile of the deck.
+    pub fn get_card_count(&self) -> usize {
+        self.piles.iter().map(|pile| pile.cards.len()).sum()
+    }
+
+    ///Shuffles the named pile using the deck's own RNG.
+    pub fn shuffle(&mut self, pile: &str) -> Result<(), String> {
+        let index = self.pile_index(pile)?;
+        let Deck { rng, piles, .. } = self;
+        piles[index].cards.shuffle(rng);
+        Ok(())
+    }
+
+    ///Draws count cards from the top of the draw pile onto the named pile, creating it if needed.
+    ///Returns an error without drawing anything if the draw pile doesn't hold enough cards.
+    pub fn draw(&mut self, count: usize, to_pile: &str) -> Result<Vec<String>, String> {
+        self.move_top(DRAW_PILE, to_pile, count)
+    }
+
+    ///Moves count cards from the top of one pile onto another, keeping the order they were dealt in.
+    ///The target pile is created if it doesn't exist yet.
+    pub fn move_top(&mut self, from_pile: &str, to_pile: &str, count: usize) -> Result<Vec<String>, String> {
+        let from = self.pile_index(from_pile)?;
+        let available = self.piles[from].cards.len();
+        if count > available {
+            return Err(format!(
+                "Only {} card(s) left in pile {} of deck {}, can't take {}.",
+                available, from_pile, self.label, count
+            ));
+        }
+
+        let mut moved: Vec<String> = Vec::new();
+        for _i in 0..count {
+            if let Some(card) = self.piles[from].cards.pop() {
+                moved.push(card);
+            }
+        }
+        let to = self.pile_index_or_create(to_pile);
+        self.piles[to].cards.extend(moved.iter().cloned());
+        Ok(moved)
+    }
+
+    ///Moves the named cards from one pile to the discard pile. Card names are matched ignoring case.
+    ///Returns an error without moving anything if any card isn't in the pile.
+    pub fn discard(&mut self, from_pile: &str, cards: &[String]) -> Result<(), String> {
+        self.move_cards(from_pile, DISCARD_PILE, cards)
+    }
+
+    ///Moves the named cards from one pile onto another, creating the target pile if needed.
+    pub fn move_cards(&mut self, from_pile: &str, to_pile: &str, cards: &[String]) -> Result<(), String> {
+        let from = self.pile_index(from_pile)?;
+        let mut remaining = self.piles[from].cards.clone();
+        let mut moved = Vec::new();
+        for card in cards {
+            let position = remaining
+                .iter()
+                .rposition(|c| c.eq_ignore_ascii_case(card))
+                .ok_or(format!("No card {} found in pile {}.", card, from_pile))?;
+            moved.push(remaining.remove(position));
+        }
+
+        self.piles[from].cards = remaining;
+        let to = self.pile_index_or_create(to_pile);
+        self.piles[to].cards.extend(moved);
+        Ok(())
+    }
+
+    ///Returns the top count cards of the draw pile without moving them, top card first.
+    pub fn peek(&self, count: usize) -> Vec<&str> {
+        match self.get_pile(DRAW_PILE) {
+            Some(pile) => pile.cards.iter().rev().take(count).map(|c| c.as_str()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    ///Cuts the draw pile, moving the top cards to the bottom. If no position is given the deck is cut at a random position.
+    ///Returns the number of cards moved.
+    pub fn cut(&mut self, position: Option<usize>) -> Result<usize, String> {
+        let index = self.pile_index(DRAW_PILE)?;
+        let pile_size = self.piles[index].cards.len();
+        if pile_size < 2 {
+            return Err("There aren't enough cards in the draw pile to cut.".to_string());
+        }
+
+        let position = match position {
+            Some(position) if position == 0 || position >= pile_size => {
+                return Err(format!(
+                    "The deck can only be cut between 1 and {} cards from the top.",
+                    pile_size - 1
+                ));
+            }
+            Some(position) => position,
+            None => self.rng.random_range(1..pile_size),
+        };
+
+        //The top of the pile is the end of the vector, so the top cards move to the front.
+        self.piles[index].cards.rotate_right(position);
+        Ok(position)
+    }
+
+    ///Returns every card in the named pile to the bottom of the draw pile.
+    pub fn return_pile(&mut self, pile: &str) -> Result<usize, String> {
+        if pile == DRAW_PILE {
+            return Ok(0);
+        }
+        let from = self.pile_index(pile)?;
+        let returned: Vec<String> = self.piles[from].cards.drain(..).collect();
+        let count = returned.len();
+        let draw = self.pile_index(DRAW_PILE)?;
+        self.piles[draw].cards.splice(0..0, returned);
+        Ok(count)
+    }
+
+    ///Returns every card in every pile to the draw pile and shuffles it.
+    pub fn return_all(&mut self) -> Result<usize, String> {
+        let pile_names: Vec<String> = self.piles.iter().map(|pile| pile.name.clone()).collect();
+        let mut count = 0;
+        for name in pile_names {
+            count += self.return_pile(&name)?;
+        }
+        self.shuffle(DRAW_PILE)?;
+        Ok(count)
+    }
+
+    ///Removes an empty pile. The draw and discard piles can't be removed.
+    pub fn remove_pile(&mut self, pile: &str) -> Result<(), String> {
+        if pile == DRAW_PILE || pile == DISCARD_PILE {
+            return Err(format!("The {} pile can't be removed.", pile));
+        }
+        let index = self.pile_index(pile)?;
+        if !self.piles[index].cards.is_empty() {
+            return Err(format!("Pile {} still holds cards. Return them first.", pile));
+        }
+        self.piles.remove(index);
+        Ok(())
+    }
+
+    fn pile_index(&self, pile: &str) -> Result<usize, String> {
+        self.piles
+            .iter()
+            .position(|p| p.name == pile)
+            .ok_or(format!("No pile named {} in deck {}.", pile, self.label))
+    }
+
+    fn pile_index_or_create(&mut self, pile: &str) -> usize {
+        match self.piles.iter().position(|p| p.name == pile) {
+            Some(index) => index,
+            None => {
+                self.piles.push(Pile::new(pile.to_string()));
+                self.piles.len() - 1
+            }
+        }
+    }
+}
+
+///Deck data is used to save and load decks with serde, the same way DieData32 is used for dice.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeckData {
+    rng_seed: u64,
+    label: String,
+    piles: Vec<Pile>,
+}
+
+impl From<&Deck> for DeckData {
+    fn from(deck: &Deck) -> Self {
+        DeckData {
+            rng_seed: deck.get_rng_seed(),
+            label: deck.get_label().to_string(),
+            piles: deck.get_piles().clone(),
+        }
+    }
+}
+
+impl DeckData {
+    ///Gets the deck data label as a string slice.
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pile_len(deck: &Deck, pile: &str) -> usize {
+        deck.get_pile(pile).map_or(0, |pile| pile.get_cards().len())
+    }
+
+    #[test]
+    fn draws_without_replacement() {
+        let mut deck = Deck::standard("Poker".to_string(), false);
+        let mut drawn = deck.draw(52, "hand").unwrap();
+        drawn.sort();
+        drawn.dedup();
+        assert_eq!(drawn.len(), 52);
+        assert_eq!(pile_len(&deck, DRAW_PILE), 0);
+        assert_eq!(deck.get_card_count(), 52);
+    }
+
+    #[test]
+    fn drawing_too_many_takes_nothing() {
+        let mut deck = Deck::new("Small".to_string(), vec!["A".to_string(), "B".to_string()]);
+        assert!(deck.draw(3, "hand").is_err());
+        assert_eq!(pile_len(&deck, DRAW_PILE), 2);
+        assert!(deck.get_pile("hand").is_none());
+        deck.draw(2, "hand").unwrap();
+        assert!(deck.draw(1, "hand").unwrap_err().contains("Only 0 card(s)"));
+    }
+
+    #[test]
+    fn return_counts_every_card_put_back() {
+        let mut deck = Deck::standard("Poker".to_string(), true);
+        let hand = deck.draw(5, "hand").unwrap();
+        deck.discard("hand", &hand[..2]).unwrap();
+        assert_eq!(deck.return_pile("hand").unwrap(), 3);
+        assert_eq!(deck.return_pile(DRAW_PILE).unwrap(), 0);
+        deck.draw(4, "hand").unwrap();
+        assert_eq!(deck.return_all().unwrap(), 6);
+        assert_eq!(pile_len(&deck, DRAW_PILE), 54);
+        assert!(deck.return_pile("missing").is_err());
+    }
+
+    #[test]
+    fn reloaded_decks_shuffle_the_same_way() {
+        let deck = Deck::standard("Poker".to_string(), false);
+        let json = serde_json::to_string(&DeckData::from(&deck)).unwrap();
+        let data: DeckData = serde_json::from_str(&json).unwrap();
+        let mut first = Deck::from_data(&data);
+        let mut second = Deck::from_data(&data);
+        assert_eq!(first.get_piles()[0].get_cards(), deck.get_piles()[0].get_cards());
+
+        first.shuffle(DRAW_PILE).unwrap();
+        second.shuffle(DRAW_PILE).unwrap();
+        assert_eq!(first.peek(52), second.peek(52));
+        assert_eq!(first.cut(None).unwrap(), second.cut(None).unwrap());
+        assert_eq!(first.get_rng_seed(), second.get_rng_seed());
+    }
+
+    #[test]
+    fn reloaded_decks_keep_required_piles() {
+        let data: DeckData = serde_json::from_str(r#"{"rng_seed":1,"label":"Old","piles":[{"name":"draw","cards":["A"]}]}"#).unwrap();
+        let deck = Deck::from_data(&data);
+        assert!(deck.get_pile(DISCARD_PILE).is_some());
+    }
+}
@@ -1,10 +1,9 @@
-use rand::rngs::SmallRng;
-use rand::{Rng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::fmt::write;
+use std::fmt;
 use std::mem::discriminant;
 
+use crate::commitment::{DieRng, RollGenerator, SeedCommitment, roll_face};
 use crate::dice_data::DieData32;
 
 /// Used to type dice for serilization/deserilization.
@@ -43,7 +42,33 @@ pub trait Die{
     ///Used to get a reffrence to the current result type of the die.
     fn get_result_type(&self) -> &DieResultType;
 
-    ///Returns true if the die's current face is the face with the highest value.m
+    ///Returns true if the last Sum update saturated the die's result. Saturated results are held at i64::MAX rather than wrapping.
+    fn has_overflowed(&self) -> bool;
+
+    ///Returns true if the die is held. Held dice keep their face when the tray they're in is rolled.
+    fn is_held(&self) -> bool;
+
+    ///Gets the mode of the die, which changes how it behaves when rolled.
+    fn get_mode(&self) -> &DieMode;
+
+    ///Returns true once a usage die has stepped down past the bottom of its ladder. Exhausted dice can't be rolled.
+    fn is_exhausted(&self) -> bool;
+
+    ///Returns true if a tray may roll the die: it isn't held, exhausted or a locked counter.
+    fn can_roll(&self) -> bool {
+        !self.is_held() && !self.is_exhausted() && self.get_mode().allows_roll()
+    }
+
+    ///Returns true if the current face was set by hand with record_face rather than rolled.
+    fn is_manual(&self) -> bool;
+
+    ///Gets the faces the die has rolled or had set by hand as (face count, face), oldest first. Only the last HISTORY_LIMIT are kept.
+    fn get_history(&self) -> &Vec<(u32, u32)>;
+
+    ///Gets the open seed commitment of the die, if it has one.
+    fn get_commitment(&self) -> Option<&SeedCommitment>;
+
+    ///Returns true if the die's current face is the face with the highest value.
     fn is_max(&self) -> bool;
 
     ///Returns true if thr die's current face is the face with the lowest value.
@@ -56,6 +81,39 @@ pub trait Die{
     ///Rolls the die.
     fn roll(&mut self, result_type: Option<DieResultType>);
 
+    ///Holds or releases the die. Trays skip held dice when rolling, but the die itself can still be rolled directly.
+    fn set_held(&mut self, held: bool);
+
+    ///Sets the mode of the die. Changing the mode clears the exhausted flag.
+    fn set_mode(&mut self, mode: DieMode);
+
+    ///Steps the die up to the next size on the ladder i.e. d6 -> d8, keeping its identity, label and RNG.
+    ///Dice already at the top of the ladder stay the same size. Returns the new face count.
+    fn step_up(&mut self, ladder: &StepLadder) -> u32;
+
+    ///Steps the die down to the next size on the ladder i.e. d8 -> d6. The current face is clamped to the new size.
+    ///Dice already at the bottom of the ladder stay the same size. Returns the new face count.
+    fn step_down(&mut self, ladder: &StepLadder) -> u32;
+
+    ///Records a face by hand, i.e. read from a physical die or correcting a misroll. The face counts as a roll:
+    ///the result is updated for the die's result type and usage dice step down, but the face is flagged as manual.
+    ///Throws an error if the face isn't on the die or the die is exhausted.
+    fn record_face(&mut self, face: u32) -> Result<(), String>;
+
+    ///Checks the face could be recorded on the die by hand, without recording it. Throws the error record_face would.
+    fn check_face(&self, face: u32) -> Result<(), String>;
+
+    ///Restarts the die's RNG from a new random seed and commits to it. Rolls are recorded until the commitment is revealed.
+    ///Throws an error if the die already has an open commitment.
+    fn commit_seed(&mut self) -> Result<&SeedCommitment, String>;
+
+    ///Ends the die's commitment and returns it, with the seed and rolls needed to verify it. The die keeps rolling from the same RNG.
+    fn reveal_seed(&mut self) -> Option<SeedCommitment>;
+
+    ///Turns the die by amount faces, up for positive amounts and down for negative ones.
+    ///Counter dice wrap or clamp at their ends as configured, other dice wrap like increment and decrement.
+    fn count(&mut self, amount: i64);
+
     ///Increments the face on the die by one, if face is maxed wrap the die around to one.
     fn increment(&mut self);
 
@@ -66,17 +124,27 @@ pub trait Die{
     fn set_face(&mut self, new_face: i32);
 }
 
-/// Represents a physical dice. Includes a string identifier, it's own SmallRng seed, ability to roll and compare rolls.
+///The number of rolls each die keeps in its history for statistics. Older rolls are dropped first.
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Represents a physical dice. Includes a string identifier, it's own RNG seed, ability to roll and compare rolls.
 #[derive(Debug, Clone)]
 pub struct Die32 {
     die_type: DieType,
     id: usize,
-    rng: SmallRng,
+    rng: DieRng,
     label: String,
     faces: u32,
     current_face: u32,
     current_result: DieResult,
     result_type: DieResultType,
+    overflowed: bool,
+    held: bool,
+    mode: DieMode,
+    exhausted: bool,
+    manual: bool,
+    commitment: Option<SeedCommitment>,
+    history: Vec<(u32, u32)>,
 }
 
 impl Die for Die32 {
@@ -116,16 +184,77 @@ impl Die for Die32 {
         &self.result_type
     }
 
+    fn has_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    fn is_held(&self) -> bool {
+        self.held
+    }
+
+    fn get_mode(&self) -> &DieMode {
+        &self.mode
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn is_manual(&self) -> bool {
+        self.manual
+    }
+
     fn get_summary(&self) -> String {
-        format!("&{} = {} ", self.label, self.get_result().to_string())
+        if self.held {
+            format!("&{} = {} (held) ", self.label, self.get_result())
+        } else {
+            format!("&{} = {} ", self.label, self.get_result())
+        }
     }
 
     fn roll(&mut self, result_type: Option<DieResultType>) {
+        if self.exhausted || !self.mode.allows_roll() {
+            return;
+        }
         if let Some(result_type) = result_type {
             self.set_result_type(result_type);
         }
-        self.current_face = self.rng.random_range(1..=self.faces);
+        self.current_face = roll_face(&mut self.rng, self.faces);
+        if let Some(commitment) = &mut self.commitment {
+            commitment.record_roll(self.faces, self.current_face);
+        }
+        self.push_history();
+        self.manual = false;
         self.update_result();
+        self.apply_usage();
+    }
+
+    fn record_face(&mut self, face: u32) -> Result<(), String> {
+        self.check_face(face)?;
+        self.current_face = face;
+        self.push_history();
+        self.manual = true;
+        self.update_result();
+        self.apply_usage();
+        Ok(())
+    }
+
+    fn check_face(&self, face: u32) -> Result<(), String> {
+        if face < 1 || face > self.faces {
+            return Err(format!("{} is not a face on a d{}.", face, self.faces));
+        }
+        if self.exhausted {
+            return Err(format!("{} is an exhausted usage die.", self.label));
+        }
+        Ok(())
+    }
+
+    fn get_history(&self) -> &Vec<(u32, u32)> {
+        &self.history
+    }
+
+    fn get_commitment(&self) -> Option<&SeedCommitment> {
+        self.commitment.as_ref()
     }
 
     fn is_max(&self) -> bool {
@@ -136,6 +265,57 @@ impl Die for Die32 {
         self.current_face == 1
     }
 
+    fn set_held(&mut self, held: bool) {
+        self.held = held;
+    }
+
+    fn set_mode(&mut self, mode: DieMode) {
+        self.mode = mode;
+        self.exhausted = false;
+    }
+
+    fn commit_seed(&mut self) -> Result<&SeedCommitment, String> {
+        if self.commitment.is_some() {
+            return Err(format!("{} is already committed. Reveal it before committing again.", self.label));
+        }
+        let commitment = SeedCommitment::new(&self.label);
+        self.rng = DieRng::from_seed(commitment.get_seed(), commitment.get_generator());
+        Ok(self.commitment.insert(commitment))
+    }
+
+    fn reveal_seed(&mut self) -> Option<SeedCommitment> {
+        self.commitment.take()
+    }
+
+    fn count(&mut self, amount: i64) {
+        let wrap = match self.mode {
+            DieMode::Counter { wrap, .. } => wrap,
+            _ => true,
+        };
+        let faces = self.faces as i64;
+        let current = self.current_face as i64;
+        //Reduce the amount before adding it, so counting by any i64 can't overflow.
+        let new_face = if wrap {
+            (current - 1 + amount.rem_euclid(faces)) % faces + 1
+        } else {
+            current.saturating_add(amount).clamp(1, faces)
+        };
+        self.current_face = new_face as u32;
+        if self.result_type == DieResultType::Face {
+            self.current_result = DieResult::Number(new_face);
+        }
+    }
+
+    fn step_up(&mut self, ladder: &StepLadder) -> u32 {
+        self.set_face_count(ladder.step_up(self.faces));
+        self.faces
+    }
+
+    fn step_down(&mut self, ladder: &StepLadder) -> u32 {
+        self.set_face_count(ladder.step_down(self.faces));
+        self.faces
+    }
+
     fn increment(&mut self) {
         self.current_face += 1;
         if self.current_face > self.faces {
@@ -172,6 +352,36 @@ impl Die32 {
         label: Option<String>,
         faces: u32,
         result_type: Option<DieResultType>,
+    ) -> Self {
+        Die32::with_rng(id, label, faces, result_type, DieRng::random())
+    }
+
+    ///Creates a new Die32 whose RNG starts from the provided seed, so every die made with the same seed rolls the same faces.
+    ///Like new, the die is rolled on creation.
+    pub fn from_seed(
+        id: usize,
+        label: Option<String>,
+        faces: u32,
+        result_type: Option<DieResultType>,
+        seed: u64,
+    ) -> Self {
+        Die32::with_rng(id, label, faces, result_type, DieRng::from_seed(seed, RollGenerator::ChaCha8))
+    }
+
+    ///Adds the current face to the roll history, dropping the oldest roll once HISTORY_LIMIT is reached.
+    fn push_history(&mut self) {
+        if self.history.len() >= HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push((self.faces, self.current_face));
+    }
+
+    fn with_rng(
+        id: usize,
+        label: Option<String>,
+        faces: u32,
+        result_type: Option<DieResultType>,
+        rng: DieRng,
     ) -> Self {
         let new_result_type = match result_type {
             Some(r) => r,
@@ -181,12 +391,19 @@ impl Die32 {
         let mut new_die = Die32 {
             die_type: DieType::Die32,
             id,
-            rng: SmallRng::from_rng(&mut rand::rng()),
+            rng,
             label: label.unwrap_or_else(|| "d".to_string() + &faces.to_string()),
             faces,
             current_face: 1,
             current_result: DieResult::Number(1),
             result_type: new_result_type,
+            overflowed: false,
+            held: false,
+            mode: DieMode::Standard,
+            exhausted: false,
+            manual: false,
+            commitment: None,
+            history: Vec::new(),
         };
 
         new_die.roll(None);
@@ -195,16 +412,55 @@ impl Die32 {
 
     ///Creates a new Die32 from Die32 data - allows for saving dice between sessions as certian fields (i.e. RNG) can't be serialized with serde.
     ///ID must be provided by the dice allocator and the die will get a new RNG seed.
+    ///Committed dice rebuild their RNG from the committed seed instead, so their rolls stay replayable from it.
     pub fn from_data(id: usize, data: &DieData32) -> Self {
+        let rng = match data.get_commitment() {
+            Some(commitment) => commitment.replay_rng(),
+            None => DieRng::from_seed(data.get_seed(), RollGenerator::ChaCha8),
+        };
         Die32 {
             die_type: DieType::Die32,
             id,
-            rng: SmallRng::seed_from_u64(data.get_seed()),
+            rng,
             label: data.get_label().to_string(),
             faces: data.get_faces(),
             current_face: data.get_current_face(),
             current_result: data.get_current_result().clone(),
             result_type: *data.get_current_result_type(),
+            overflowed: data.get_overflowed(),
+            held: data.get_held(),
+            mode: data.get_mode().clone(),
+            exhausted: data.get_exhausted(),
+            manual: data.get_manual(),
+            commitment: data.get_commitment().cloned(),
+            history: data.get_history().clone(),
+        }
+    }
+
+    /// Usage dice step down a size when they roll at or under their threshold, and are exhausted when there's no size left.
+    fn apply_usage(&mut self) {
+        if let DieMode::Usage { threshold, ladder } = &self.mode
+            && self.current_face <= *threshold
+        {
+            let smaller = ladder.step_down(self.faces);
+            if smaller == self.faces {
+                self.exhausted = true;
+            } else {
+                self.set_face_count(smaller);
+            }
+        }
+    }
+
+    /// Changes the number of faces on the die, clamping the current face to the new size.
+    /// Face results follow the clamped face, while Best, Worst and Sum results keep their history.
+    fn set_face_count(&mut self, faces: u32) {
+        if faces == self.faces {
+            return;
+        }
+        self.faces = faces;
+        self.current_face = self.clamp_to_bounds(self.current_face);
+        if self.result_type == DieResultType::Face {
+            self.current_result = DieResult::Number(self.current_face as i64);
         }
     }
 
@@ -232,35 +488,44 @@ impl Die32 {
 
         self.current_result = match new_result_type {
             DieResultType::Best => DieResult::Number(1),
-            DieResultType::Worst => DieResult::Number(self.faces),
+            DieResultType::Worst => DieResult::Number(self.faces as i64),
             DieResultType::Sum => DieResult::Number(0),
             DieResultType::Face => DieResult::Number(0),
         };
 
+        self.overflowed = false;
         self.result_type = new_result_type;
         self.update_result();
     }
 
     fn update_result(&mut self) {
+        let face = self.current_face as i64;
         match self.result_type {
             DieResultType::Face => {
-                self.current_result = DieResult::Number(self.current_face);
+                self.current_result = DieResult::Number(face);
             }
             DieResultType::Best => {
                 let last_result = self.current_result.is_num_or(1);
-                if self.current_face > last_result {
-                    self.current_result = DieResult::Number(self.current_face);
+                if face > last_result {
+                    self.current_result = DieResult::Number(face);
                 }
             }
             DieResultType::Worst => {
-                let last_result = self.current_result.is_num_or(self.faces);
-                if self.current_face < last_result {
-                    self.current_result = DieResult::Number(self.current_face);
+                let last_result = self.current_result.is_num_or(self.faces as i64);
+                if face < last_result {
+                    self.current_result = DieResult::Number(face);
                 }
             }
             DieResultType::Sum => {
-                self.current_result =
-                    DieResult::Number(self.current_result.is_num_or(0) + self.current_face);
+                //Sums saturate instead of wrapping so a long running die never flips to a nonsense value.
+                let sum = match self.current_result.is_num_or(0).checked_add(face) {
+                    Some(sum) => sum,
+                    None => {
+                        self.overflowed = true;
+                        i64::MAX
+                    }
+                };
+                self.current_result = DieResult::Number(sum);
             }
         }
     }
@@ -292,6 +557,100 @@ impl Ord for Die32 {
     }
 }
 
+/// The die sizes a die can step through, smallest first. The standard ladder is d4, d6, d8, d10, d12.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepLadder {
+    rungs: Vec<u32>,
+}
+
+impl StepLadder {
+    /// Creates a ladder from die sizes. Sizes must be at least 2 and strictly increasing.
+    pub fn new(rungs: Vec<u32>) -> Result<Self, String> {
+        if rungs.is_empty() {
+            return Err("A step ladder needs at least one die size.".to_string());
+        }
+        if rungs.iter().any(|faces| *faces < 2) {
+            return Err("Dice on a step ladder need at least 2 faces.".to_string());
+        }
+        if rungs.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err("Die sizes on a step ladder must go from smallest to largest, without repeats.".to_string());
+        }
+        Ok(StepLadder { rungs })
+    }
+
+    /// Gets the die sizes on the ladder, smallest first.
+    pub fn get_rungs(&self) -> &Vec<u32> {
+        &self.rungs
+    }
+
+    /// Returns the next size up from faces, or faces if there is no bigger size on the ladder.
+    /// Dice between rungs step up to the next rung.
+    pub fn step_up(&self, faces: u32) -> u32 {
+        self.rungs.iter().copied().find(|rung| *rung > faces).unwrap_or(faces)
+    }
+
+    /// Returns the next size down from faces, or faces if there is no smaller size on the ladder.
+    /// Dice between rungs step down to the next rung.
+    pub fn step_down(&self, faces: u32) -> u32 {
+        self.rungs.iter().copied().rev().find(|rung| *rung < faces).unwrap_or(faces)
+    }
+}
+
+impl Default for StepLadder {
+    fn default() -> Self {
+        StepLadder {
+            rungs: vec![4, 6, 8, 10, 12],
+        }
+    }
+}
+
+/// Modes change how a die behaves when it's rolled.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DieMode {
+    /// A normal die.
+    #[default]
+    Standard,
+    /// A usage die, as in The Black Hack. Rolling threshold or under steps the die down the ladder,
+    /// and rolling it at the bottom of the ladder exhausts it.
+    Usage { threshold: u32, ladder: StepLadder },
+    /// A counter die, like a spindown life counter or a countdown clock. Counters are turned with Die::count rather than rolled,
+    /// and either wrap around or stop at their ends. Rolling is disabled unless the counter is unlocked.
+    Counter { wrap: bool, unlocked: bool },
+}
+
+impl DieMode {
+    /// Creates a usage mode, stepping down on rolls of threshold or under. Black Hack usage dice use a threshold of 2.
+    pub fn usage(threshold: u32, ladder: StepLadder) -> Result<Self, String> {
+        if threshold == 0 {
+            return Err("A usage die's threshold must be at least 1.".to_string());
+        }
+        Ok(DieMode::Usage { threshold, ladder })
+    }
+
+    /// Gets the number of times a usage die of this size can step down before it's exhausted, counting the final step.
+    /// Returns None for dice that aren't usage dice.
+    pub fn steps_remaining(&self, faces: u32) -> Option<usize> {
+        match self {
+            DieMode::Usage { ladder, .. } => {
+                Some(ladder.get_rungs().iter().filter(|rung| **rung < faces).count() + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns false for locked counters, which can only be turned.
+    pub fn allows_roll(&self) -> bool {
+        !matches!(self, DieMode::Counter { unlocked: false, .. })
+    }
+}
+
+/// Which way to step a die along a StepLadder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepDirection {
+    Up,
+    Down,
+}
+
 /// Used to request specific result types from a Die roll.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum DieResultType {
@@ -301,13 +660,13 @@ pub enum DieResultType {
     Sum,
 }
 
-impl ToString for DieResultType {
-    fn to_string(&self) -> String {
+impl fmt::Display for DieResultType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            DieResultType::Face => "Face".to_string(),
-            DieResultType::Best => "Best".to_string(),
-            DieResultType::Worst => "Worst".to_string(),
-            DieResultType::Sum => "Sum".to_string(),
+            DieResultType::Face => write!(f, "Face"),
+            DieResultType::Best => write!(f, "Best"),
+            DieResultType::Worst => write!(f, "Worst"),
+            DieResultType::Sum => write!(f, "Sum"),
         }
     }
 }
@@ -319,27 +678,274 @@ impl PartialEq for DieResultType {
 }
 
 /// Used to return specific result types from a Die roll and wraps the returned value.
+/// Numbers are signed 64-bit so results can go negative and sums have plenty of headroom.
+/// Saves written when results were u32 still load, as serde reads those values straight into an i64.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DieResult {
-    Number(u32),
+    Number(i64),
     String(String),
     None,
 }
 
 impl DieResult {
     /// Checks if the DieResult is a number type, otherwise defaults the die result to the provided default.
-    pub fn is_num_or(&self, default_num: u32) -> u32 {
+    pub fn is_num_or(&self, default_num: i64) -> i64 {
         match self {
             DieResult::Number(x) => *x,
             _ => default_num,
         }
     }
+}
 
-    pub fn to_string(&self) -> String{
-        match self{
-            DieResult::Number(num) => num.to_string(),
-            DieResult::String(string) => string.to_string(),
-            DieResult::None => "".to_string()
+impl fmt::Display for DieResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DieResult::Number(num) => write!(f, "{}", num),
+            DieResult::String(string) => write!(f, "{}", string),
+            DieResult::None => write!(f, "None"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_data::DieData32;
+
+    fn die(faces: u32) -> Die32 {
+        Die32::from_seed(0, None, faces, None, 1)
+    }
+
+    fn die_with_mode(faces: u32, mode: DieMode) -> Die32 {
+        let mut die = die(faces);
+        die.set_mode(mode);
+        die
+    }
+
+    #[test]
+    fn sum_saturates_at_i64_max() {
+        let mut die = die(6);
+        die.set_result_type(DieResultType::Sum);
+        die.current_result = DieResult::Number(i64::MAX - 1);
+        assert!(!die.has_overflowed());
+        die.roll(None);
+        assert_eq!(die.get_result().is_num_or(0), i64::MAX);
+        assert!(die.has_overflowed());
+        die.roll(None);
+        assert_eq!(die.get_result().is_num_or(0), i64::MAX);
+    }
+
+    #[test]
+    fn changing_result_type_clears_overflow() {
+        let mut die = die(6);
+        die.set_result_type(DieResultType::Sum);
+        die.current_result = DieResult::Number(i64::MAX);
+        die.roll(None);
+        assert!(die.has_overflowed());
+        die.set_result_type(DieResultType::Face);
+        assert!(!die.has_overflowed());
+    }
+
+    #[test]
+    fn u32_era_results_load_as_i64() {
+        let json = r#"{"rng_seed":1,"label":"d6","faces":6,"current_face":6,"current_result":{"Number":4294967295},"current_result_type":"Sum"}"#;
+        let data: DieData32 = serde_json::from_str(json).unwrap();
+        let die = Die32::from_data(0, &data);
+        assert_eq!(die.get_result().is_num_or(0), u32::MAX as i64);
+        assert!(!die.has_overflowed());
+    }
+
+    #[test]
+    fn stepping_stops_at_the_ladder_ends() {
+        let ladder = StepLadder::default();
+        let mut die = die(12);
+        assert_eq!(die.step_up(&ladder), 12);
+        let mut small = Die32::from_seed(0, None, 4, None, 1);
+        assert_eq!(small.step_down(&ladder), 4);
+        assert_eq!(die.step_down(&ladder), 10);
+    }
+
+    #[test]
+    fn off_ladder_sizes_step_onto_the_next_rung() {
+        let ladder = StepLadder::default();
+        let mut d20 = die(20);
+        assert_eq!(d20.step_up(&ladder), 20);
+        assert_eq!(d20.step_down(&ladder), 12);
+        let mut d5 = die(5);
+        assert_eq!(d5.step_up(&ladder), 6);
+        let mut d5 = die(5);
+        assert_eq!(d5.step_down(&ladder), 4);
+        let mut d2 = die(2);
+        assert_eq!(d2.step_up(&ladder), 4);
+    }
+
+    #[test]
+    fn stepping_down_clamps_the_face() {
+        let ladder = StepLadder::default();
+        let mut die = die(8);
+        die.set_current_face(8);
+        die.update_result();
+        die.step_down(&ladder);
+        assert_eq!(die.get_current_face(), 6);
+        assert_eq!(die.get_result().is_num_or(0), 6);
+    }
+
+    #[test]
+    fn ladders_must_be_increasing_sizes() {
+        assert!(StepLadder::new(vec![]).is_err());
+        assert!(StepLadder::new(vec![1, 4]).is_err());
+        assert!(StepLadder::new(vec![6, 4]).is_err());
+        assert!(StepLadder::new(vec![6, 6]).is_err());
+        assert_eq!(StepLadder::new(vec![4, 20]).unwrap().step_up(6), 20);
+    }
+
+    fn usage_die(faces: u32) -> Die32 {
+        die_with_mode(faces, DieMode::usage(2, StepLadder::default()).unwrap())
+    }
+
+    #[test]
+    fn low_usage_rolls_step_down() {
+        let mut die = usage_die(8);
+        die.record_face(3).unwrap();
+        assert_eq!(die.get_face_count(), 8);
+        die.record_face(2).unwrap();
+        assert_eq!(die.get_face_count(), 6);
+        die.record_face(1).unwrap();
+        assert_eq!(die.get_face_count(), 4);
+        assert!(!die.is_exhausted());
+
+        //A threshold as high as the die steps down on every roll.
+        let mut die = die_with_mode(6, DieMode::usage(6, StepLadder::default()).unwrap());
+        die.roll(None);
+        assert_eq!(die.get_face_count(), 4);
+    }
+
+    #[test]
+    fn usage_dice_exhaust_below_the_ladder() {
+        let mut die = usage_die(4);
+        assert_eq!(die.get_mode().steps_remaining(4), Some(1));
+        die.record_face(2).unwrap();
+        assert!(die.is_exhausted());
+        assert_eq!(die.get_face_count(), 4);
+
+        let history = die.get_history().len();
+        die.roll(None);
+        assert_eq!(die.get_history().len(), history);
+        assert!(die.record_face(3).unwrap_err().contains("exhausted"));
+
+        die.set_mode(DieMode::Standard);
+        assert!(!die.is_exhausted());
+    }
+
+    #[test]
+    fn usage_steps_remaining_count_the_final_step() {
+        assert_eq!(usage_die(8).get_mode().steps_remaining(8), Some(3));
+        assert_eq!(usage_die(20).get_mode().steps_remaining(20), Some(6));
+        assert_eq!(DieMode::Standard.steps_remaining(8), None);
+        assert!(DieMode::usage(0, StepLadder::default()).is_err());
+    }
+
+    #[test]
+    fn wrapping_counters_wrap_at_both_ends() {
+        let mut die = die_with_mode(20, DieMode::Counter { wrap: true, unlocked: false });
+        die.set_current_face(19);
+        die.count(3);
+        assert_eq!(die.get_current_face(), 2);
+        die.count(-2);
+        assert_eq!(die.get_current_face(), 20);
+        die.count(-45);
+        assert_eq!(die.get_current_face(), 15);
+        assert_eq!(die.get_result().is_num_or(0), 15);
+    }
+
+    #[test]
+    fn clamping_counters_stop_at_their_ends() {
+        let mut die = die_with_mode(10, DieMode::Counter { wrap: false, unlocked: false });
+        die.set_current_face(8);
+        die.count(5);
+        assert_eq!(die.get_current_face(), 10);
+        die.count(-100);
+        assert_eq!(die.get_current_face(), 1);
+        die.count(i64::MIN);
+        assert_eq!(die.get_current_face(), 1);
+        die.count(i64::MAX);
+        assert_eq!(die.get_current_face(), 10);
+
+        let mut die = die_with_mode(6, DieMode::Counter { wrap: true, unlocked: false });
+        die.set_current_face(1);
+        die.count(i64::MAX);
+        assert_eq!(die.get_current_face(), (i64::MAX % 6) as i32 + 1);
+    }
+
+    #[test]
+    fn locked_counters_refuse_to_roll() {
+        let mut die = die_with_mode(20, DieMode::Counter { wrap: false, unlocked: false });
+        die.set_current_face(20);
+        let history = die.get_history().len();
+        for _ in 0..20 {
+            die.roll(None);
+        }
+        assert_eq!(die.get_current_face(), 20);
+        assert_eq!(die.get_history().len(), history);
+
+        die.set_mode(DieMode::Counter { wrap: false, unlocked: true });
+        die.roll(None);
+        assert_eq!(die.get_history().len(), history + 1);
+    }
+
+    #[test]
+    fn set_faces_must_be_on_the_die() {
+        let mut die = die(6);
+        let face = die.get_current_face();
+        let history = die.get_history().len();
+        assert!(die.record_face(0).is_err());
+        assert!(die.record_face(7).is_err());
+        assert!(die.check_face(6).is_ok());
+        assert_eq!(die.get_current_face(), face);
+        assert_eq!(die.get_history().len(), history);
+        assert!(!die.is_manual());
+    }
+
+    #[test]
+    fn set_faces_update_best_worst_and_sum_results() {
+        let mut best = die(20);
+        best.set_result_type(DieResultType::Best);
+        best.record_face(3).unwrap();
+        best.record_face(17).unwrap();
+        best.record_face(5).unwrap();
+        //The result also covers the roll the die was created with.
+        let highest = best.get_history().iter().map(|(_, face)| *face as i64).max();
+        assert_eq!(Some(best.get_result().is_num_or(0)), highest);
+        assert!(best.get_result().is_num_or(0) >= 17);
+
+        let mut worst = die(20);
+        worst.set_result_type(DieResultType::Worst);
+        worst.record_face(19).unwrap();
+        worst.record_face(2).unwrap();
+        worst.record_face(8).unwrap();
+        let lowest = worst.get_history().iter().map(|(_, face)| *face as i64).min();
+        assert_eq!(Some(worst.get_result().is_num_or(0)), lowest);
+        assert!(worst.get_result().is_num_or(0) <= 2);
+
+        let mut sum = die(6);
+        sum.set_result_type(DieResultType::Sum);
+        let start = sum.get_result().is_num_or(0);
+        sum.record_face(4).unwrap();
+        sum.record_face(6).unwrap();
+        assert_eq!(sum.get_result().is_num_or(0), start + 10);
+
+        let mut face = die(6);
+        face.record_face(5).unwrap();
+        assert_eq!(face.get_result().is_num_or(0), 5);
+    }
+
+    #[test]
+    fn set_faces_are_manual_until_the_next_roll() {
+        let mut die = die(6);
+        die.record_face(4).unwrap();
+        assert!(die.is_manual());
+        assert_eq!(die.get_history().last(), Some(&(6, 4)));
+        die.roll(None);
+        assert!(!die.is_manual());
+    }
+}
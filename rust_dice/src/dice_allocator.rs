@@ -1,5 +1,3 @@
-use std::usize;
-
 use crate::dice::{Die, Die32};
 use crate::dice_data::TypedDieData;
 use crate::dice_profile::{DieProfile, DieProfileType};
@@ -21,6 +19,12 @@ pub struct DieIdGenerator {
     next_die_id: usize,
 }
 
+impl Default for DieIdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DieIdGenerator {
     pub fn new() -> Self {
         DieIdGenerator { next_die_id: 0 }
@@ -40,14 +44,20 @@ impl DieIdGenerator {
     }
 }
 
-///Creates a new die from a die profile.
-pub fn new_die(id: usize, profile: &DieProfile) -> impl Die + use<> {
+///Creates a new die from a die profile. Returns an error for invalid profiles and for custom dice, which aren't implemented yet.
+pub fn new_die(id: usize, profile: &DieProfile) -> Result<impl Die + use<>, String> {
+    profile.validate()?;
     match profile.die_type {
-        DieProfileType::Numerical(faces) => {
-            Die32::new(id, profile.label.clone(), faces, profile.result_type)
-        }
-        DieProfileType::Custom => {
-            todo!("Implement custom dice later.")
-        }
+        DieProfileType::Numerical(faces) => Ok(Die32::new(id, profile.label.clone(), faces, profile.result_type)),
+        DieProfileType::Custom => Err("Custom dice are not supported yet.".to_string()),
+    }
+}
+
+///Creates a new die from a die profile, with its RNG started from the provided seed. Fails like new_die.
+pub fn new_seeded_die(id: usize, profile: &DieProfile, seed: u64) -> Result<impl Die + use<>, String> {
+    profile.validate()?;
+    match profile.die_type {
+        DieProfileType::Numerical(faces) => Ok(Die32::from_seed(id, profile.label.clone(), faces, profile.result_type, seed)),
+        DieProfileType::Custom => Err("Custom dice are not supported yet.".to_string()),
     }
 }
@@ -1,4 +1,5 @@
-use crate::dice::{Die, Die32, DieResult, DieResultType};
+use crate::commitment::SeedCommitment;
+use crate::dice::{Die, Die32, DieMode, DieResult, DieResultType};
 use crate::tray::Tray;
 use serde::{Deserialize, Serialize};
 
@@ -30,6 +31,20 @@ pub struct DieData32 {
     current_face: u32,
     current_result: DieResult,
     current_result_type: DieResultType,
+    #[serde(default)]
+    overflowed: bool,
+    #[serde(default)]
+    held: bool,
+    #[serde(default)]
+    mode: DieMode,
+    #[serde(default)]
+    exhausted: bool,
+    #[serde(default)]
+    manual: bool,
+    #[serde(default)]
+    commitment: Option<SeedCommitment>,
+    #[serde(default)]
+    history: Vec<(u32, u32)>,
 }
 
 impl DieData for DieData32 {
@@ -40,7 +55,14 @@ impl DieData for DieData32 {
             faces: die.get_face_count(),
             current_face: die.get_current_face() as u32,
             current_result: die.get_result().clone(),
-            current_result_type: die.get_result_type().clone(),
+            current_result_type: *die.get_result_type(),
+            overflowed: die.has_overflowed(),
+            held: die.is_held(),
+            mode: die.get_mode().clone(),
+            exhausted: die.is_exhausted(),
+            manual: die.is_manual(),
+            commitment: die.get_commitment().cloned(),
+            history: die.get_history().clone(),
         })
     }
 }
@@ -75,6 +97,41 @@ impl DieData32 {
     pub fn get_current_result_type(&self) -> &DieResultType {
         &self.current_result_type
     }
+
+    ///Returns true if the saved die's Sum result had saturated. Older saves without the flag load as false.
+    pub fn get_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    ///Returns true if the saved die was held. Older saves without the flag load as released.
+    pub fn get_held(&self) -> bool {
+        self.held
+    }
+
+    ///Gets the saved die mode. Older saves load as standard dice.
+    pub fn get_mode(&self) -> &DieMode {
+        &self.mode
+    }
+
+    ///Returns true if the saved die was an exhausted usage die.
+    pub fn get_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    ///Returns true if the saved die's face was set by hand.
+    pub fn get_manual(&self) -> bool {
+        self.manual
+    }
+
+    ///Gets the saved die's roll history. Saves from before histories were kept load with an empty history.
+    pub fn get_history(&self) -> &Vec<(u32, u32)> {
+        &self.history
+    }
+
+    ///Gets the saved die's open seed commitment, if it had one.
+    pub fn get_commitment(&self) -> Option<&SeedCommitment> {
+        self.commitment.as_ref()
+    }
 }
 
 /// Tray data is used to save/load dice trays using the serde crate.
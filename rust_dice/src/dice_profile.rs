@@ -1,6 +1,11 @@
 use crate::dice::DieResultType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 
-///A dice_profile is a template that can be passed to Die::new() in order to create a die. To goal is for dice_profiles to support saving and laodind form JSON.
+///A dice_profile is a template that can be passed to Die::new() in order to create a die. Profiles can be shared as JSON or TOML files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DieProfile {
     pub label: Option<String>,
     pub die_type: DieProfileType,
@@ -8,6 +13,7 @@ pub struct DieProfile {
 }
 
 /// DieType is used to specify what kind of die the die_profile should build. Can be extended with more die types later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DieProfileType {
     Numerical(u32),
     Custom,
@@ -26,4 +32,101 @@ impl DieProfile {
             result_type,
         }
     }
+
+    ///Checks the profile can build a die. Numerical dice need at least one face, since a die with no faces can't be rolled.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.die_type {
+            DieProfileType::Numerical(0) => Err("Numerical dice need at least 1 face.".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+///A profile file maps profile names to profiles. Names are kept sorted so saved files diff cleanly.
+pub type DieProfileSet = BTreeMap<String, DieProfile>;
+
+///File formats supported for profile files. The format is picked from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Json,
+    Toml,
+}
+
+impl ProfileFormat {
+    ///Returns the profile format matching the extension of the path, or None if the file isn't a profile file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ProfileFormat::Json),
+            "toml" => Some(ProfileFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+///Loads every profile in a single JSON or TOML profile file.
+pub fn load_profiles_from_file(path: &Path) -> Result<DieProfileSet, String> {
+    let format = ProfileFormat::from_path(path).ok_or(format!(
+        "{} is not a profile file. Profile files must end in .json or .toml",
+        path.display()
+    ))?;
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read profile file {}: {}", path.display(), e))?;
+
+    let profiles: DieProfileSet = match format {
+        ProfileFormat::Json => serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse profile file {}: {}", path.display(), e))?,
+        ProfileFormat::Toml => toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse profile file {}: {}", path.display(), e))?,
+    };
+    //Profile files are user input, so bad profiles are rejected here rather than panicking when a die is built.
+    for (name, profile) in profiles.iter() {
+        profile
+            .validate()
+            .map_err(|e| format!("Profile '{}' in {} is invalid: {}", name, path.display(), e))?;
+    }
+    Ok(profiles)
+}
+
+///Loads the profiles from every JSON and TOML file in a directory. Other files are ignored.
+///Returns an error if two files define a profile with the same name, so shared profiles never silently shadow each other.
+pub fn load_profiles_from_dir(dir: &Path) -> Result<DieProfileSet, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read profile directory {}: {}", dir.display(), e))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && ProfileFormat::from_path(path).is_some())
+        .collect();
+    paths.sort();
+
+    let mut profiles = DieProfileSet::new();
+    for path in paths {
+        for (name, profile) in load_profiles_from_file(&path)? {
+            if profiles.contains_key(&name) {
+                return Err(format!(
+                    "Profile '{}' in {} is already defined by another profile file.",
+                    name,
+                    path.display()
+                ));
+            }
+            profiles.insert(name, profile);
+        }
+    }
+    Ok(profiles)
+}
+
+///Saves a set of profiles to a JSON or TOML file, based on the file extension.
+pub fn save_profiles_to_file(path: &Path, profiles: &DieProfileSet) -> Result<(), String> {
+    let format = ProfileFormat::from_path(path).ok_or(format!(
+        "{} is not a profile file. Profile files must end in .json or .toml",
+        path.display()
+    ))?;
+
+    let content = match format {
+        ProfileFormat::Json => serde_json::to_string_pretty(profiles).map_err(|e| e.to_string())?,
+        ProfileFormat::Toml => toml::to_string_pretty(profiles).map_err(|e| e.to_string())?,
+    };
+
+    fs::write(path, content)
+        .map_err(|e| format!("Failed to write profile file {}: {}", path.display(), e))
 }
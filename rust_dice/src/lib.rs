@@ -10,14 +10,32 @@ pub mod dice;
 ///And because the dice/tray ids need to be reassinged by the dice allocator at runtime.    
 pub mod dice_data;
 
+///Module for seed commitments: a die publishes a hash of a fresh RNG seed before rolling, then reveals the seed so anyone can replay and check its rolls.
+pub mod commitment;
+
 ///Module  used for allocating dice to an app. The DiceAllocator trait is used to assign dice unique ID numbers and connect settings to the dice tray. Also contains helper function for building dice.
 pub mod dice_allocator;
 
-///Dice profiles are used to build new dice. Dice profiles can be saved/loaded from JSON (not yet implemented)
+///Dice profiles are used to build new dice. Dice profiles can be saved/loaded from JSON or TOML files, and a whole directory of profile files can be loaded at once.
 pub mod dice_profile;
 
 ///Module  for managing a tray of dice. A tray can hold multiple dice, roll them all, remove them, clear itself, and more.
 pub mod tray;
 
-///Module  for creating roll tables and looking up dice results on said tables.
+///Module for statistics over the rolls dice have made: counts, mean against the expected mean, face histograms, streaks and the longest run without a max.
+pub mod statistics;
+
+///Module for finding patterns in the faces of a group of dice: N of a kind, One-Roll Engine sets, straights, full houses and all distinct faces.
+pub mod patterns;
+
+///Module for playing Yahtzee with a tray of five dice: scoring categories, scorecards with the upper and Yahtzee bonuses, and turns of three rolls.
+pub mod yahtzee;
+
+///Module for decks of cards. Like dice, decks have their own rng seed and persist between sessions. Cards are moved between named piles.
+pub mod deck;
+
+///Module for bags of labelled tokens that are drawn without replacement, like a chaos bag. Bags have their own rng seed and persist between sessions.
+pub mod bag;
+
+///Module  for creating roll tables and looking up dice results on said tables. Tables support ranged and weighted entries, embedded dice expressions and references to other tables.
 pub mod tables;
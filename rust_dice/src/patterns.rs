@@ -0,0 +1,310 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::dice::Die;
+
+///A One-Roll Engine set: width matching dice all showing height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OreSet {
+    width: usize,
+    height: u32,
+}
+
+impl OreSet {
+    ///Gets the number of matching dice in the set.
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    ///Gets the face every die in the set shows.
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+
+///ORE sets are written width x height, i.e. "3x7" is three sevens.
+impl fmt::Display for OreSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+///A pattern found in the faces of a group of dice. Faces are the numbers the dice show, not their results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FacePattern {
+    ///count dice showing the same face. A pair is 2 of a kind.
+    OfAKind { count: usize, face: u32 },
+    ///Two different pairs, the higher pair first.
+    TwoPair { high: u32, low: u32 },
+    ///Three of one face and two of another.
+    FullHouse { three: u32, two: u32 },
+    ///Four faces in a row, ending with high.
+    SmallStraight { high: u32 },
+    ///Five or more faces in a row, ending with high.
+    LargeStraight { length: usize, high: u32 },
+    ///Every die shows a different face.
+    AllDistinct { count: usize },
+    ///No pattern at all, i.e. an empty tray or a single die.
+    Nothing,
+}
+
+impl FacePattern {
+    ///Ranks the pattern for picking the best one, following the usual dice poker order. Higher is better.
+    ///Larger sets rank above smaller ones of the same kind, then higher faces above lower ones.
+    pub fn rank(&self) -> (u8, usize, u32) {
+        match self {
+            FacePattern::OfAKind { count, face } if *count >= 4 => (8, *count, *face),
+            FacePattern::FullHouse { three, .. } => (7, 5, *three),
+            FacePattern::LargeStraight { length, high } => (6, *length, *high),
+            FacePattern::SmallStraight { high } => (5, 4, *high),
+            FacePattern::OfAKind { count: 3, face } => (4, 3, *face),
+            FacePattern::TwoPair { high, .. } => (3, 4, *high),
+            FacePattern::OfAKind { count, face } => (2, *count, *face),
+            FacePattern::AllDistinct { count } => (1, *count, 0),
+            FacePattern::Nothing => (0, 0, 0),
+        }
+    }
+}
+
+impl fmt::Display for FacePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FacePattern::OfAKind { count: 2, face } => write!(f, "Pair of {}s", face),
+            FacePattern::OfAKind { count: 3, face } => write!(f, "Three {}s", face),
+            FacePattern::OfAKind { count: 4, face } => write!(f, "Four {}s", face),
+            FacePattern::OfAKind { count: 5, face } => write!(f, "Five {}s", face),
+            FacePattern::OfAKind { count, face } => write!(f, "{} of a kind ({}s)", count, face),
+            FacePattern::TwoPair { high, low } => write!(f, "Two pair ({}s and {}s)", high, low),
+            FacePattern::FullHouse { three, two } => write!(f, "Full house ({}s over {}s)", three, two),
+            FacePattern::SmallStraight { high } => write!(f, "Small straight ({}-{})", high - 3, high),
+            FacePattern::LargeStraight { length, high } => {
+                write!(f, "Large straight ({}-{})", *high as usize + 1 - length, high)
+            }
+            FacePattern::AllDistinct { count } => write!(f, "All {} distinct", count),
+            FacePattern::Nothing => write!(f, "Nothing"),
+        }
+    }
+}
+
+///Analyses the faces shown by a group of dice. Built from faces directly or from the dice in a tray.
+#[derive(Debug, Clone)]
+pub struct FaceAnalysis {
+    faces: Vec<u32>,
+    counts: BTreeMap<u32, usize>,
+}
+
+impl FaceAnalysis {
+    ///Creates an analysis of the provided faces. Order doesn't matter.
+    pub fn new(faces: &[u32]) -> Self {
+        let mut sorted = faces.to_vec();
+        sorted.sort_unstable();
+        let mut counts = BTreeMap::new();
+        for face in sorted.iter() {
+            *counts.entry(*face).or_insert(0) += 1;
+        }
+        FaceAnalysis {
+            faces: sorted,
+            counts,
+        }
+    }
+
+    ///Creates an analysis of the current faces of the provided dice.
+    pub fn from_dice(dice: &[Box<dyn Die>]) -> Self {
+        let faces: Vec<u32> = dice.iter().map(|die| die.get_current_face().max(0) as u32).collect();
+        FaceAnalysis::new(&faces)
+    }
+
+    ///Gets the analysed faces, lowest first.
+    pub fn get_faces(&self) -> &Vec<u32> {
+        &self.faces
+    }
+
+    ///Gets the number of dice showing the face.
+    pub fn count_of(&self, face: u32) -> usize {
+        self.counts.get(&face).copied().unwrap_or(0)
+    }
+
+    ///Returns the highest face shown by at least count dice, if any.
+    pub fn of_a_kind(&self, count: usize) -> Option<u32> {
+        self.counts
+            .iter()
+            .rev()
+            .find(|(_, c)| **c >= count)
+            .map(|(face, _)| *face)
+    }
+
+    ///Returns every One-Roll Engine set (faces shown by two or more dice), widest first, then highest.
+    pub fn sets(&self) -> Vec<OreSet> {
+        let mut sets: Vec<OreSet> = self
+            .counts
+            .iter()
+            .filter(|(_, count)| **count >= 2)
+            .map(|(face, count)| OreSet {
+                width: *count,
+                height: *face,
+            })
+            .collect();
+        sets.sort_by_key(|set| Reverse((set.width, set.height)));
+        sets
+    }
+
+    ///Returns the faces shown by a single die, the "waste" dice in One-Roll Engine terms, lowest first.
+    pub fn waste(&self) -> Vec<u32> {
+        self.counts
+            .iter()
+            .filter(|(_, count)| **count == 1)
+            .map(|(face, _)| *face)
+            .collect()
+    }
+
+    ///Returns the length and highest face of the longest run of consecutive faces. Ties go to the higher run.
+    pub fn longest_straight(&self) -> Option<(usize, u32)> {
+        let mut best: Option<(usize, u32)> = None;
+        let mut run = 0;
+        let mut last: Option<u32> = None;
+        for face in self.counts.keys() {
+            run = match last {
+                Some(last) if last + 1 == *face => run + 1,
+                _ => 1,
+            };
+            last = Some(*face);
+            if best.is_none_or(|(length, _)| run >= length) {
+                best = Some((run, *face));
+            }
+        }
+        best
+    }
+
+    ///Returns true if at least length faces are in a row.
+    pub fn has_straight(&self, length: usize) -> bool {
+        self.longest_straight().is_some_and(|(run, _)| run >= length)
+    }
+
+    ///Returns (three, two) if exactly five dice show three of one face and two of another.
+    pub fn full_house(&self) -> Option<(u32, u32)> {
+        if self.faces.len() != 5 || self.counts.len() != 2 {
+            return None;
+        }
+        let three = self.counts.iter().find(|(_, count)| **count == 3)?;
+        let two = self.counts.iter().find(|(_, count)| **count == 2)?;
+        Some((*three.0, *two.0))
+    }
+
+    ///Returns true if there are at least two dice and every one shows a different face.
+    pub fn is_all_distinct(&self) -> bool {
+        self.faces.len() >= 2 && self.counts.len() == self.faces.len()
+    }
+
+    ///Returns every pattern found in the faces, best first.
+    pub fn patterns(&self) -> Vec<FacePattern> {
+        let mut patterns = Vec::new();
+
+        for set in self.sets() {
+            patterns.push(FacePattern::OfAKind {
+                count: set.width,
+                face: set.height,
+            });
+        }
+        let pairs: Vec<u32> = self
+            .counts
+            .iter()
+            .rev()
+            .filter(|(_, count)| **count >= 2)
+            .map(|(face, _)| *face)
+            .collect();
+        if pairs.len() >= 2 {
+            patterns.push(FacePattern::TwoPair {
+                high: pairs[0],
+                low: pairs[1],
+            });
+        }
+        if let Some((three, two)) = self.full_house() {
+            patterns.push(FacePattern::FullHouse { three, two });
+        }
+        if let Some((length, high)) = self.longest_straight() {
+            if length >= 5 {
+                patterns.push(FacePattern::LargeStraight { length, high });
+            }
+            if length >= 4 {
+                patterns.push(FacePattern::SmallStraight { high });
+            }
+        }
+        if self.is_all_distinct() {
+            patterns.push(FacePattern::AllDistinct {
+                count: self.faces.len(),
+            });
+        }
+
+        patterns.sort_by_key(|pattern| Reverse(pattern.rank()));
+        patterns
+    }
+
+    ///Returns the best pattern found in the faces, or FacePattern::Nothing.
+    pub fn best_pattern(&self) -> FacePattern {
+        self.patterns()
+            .into_iter()
+            .next()
+            .unwrap_or(FacePattern::Nothing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_ore_sets() {
+        let analysis = FaceAnalysis::new(&[7, 3, 7, 3, 7, 1]);
+        assert_eq!(analysis.get_faces(), &vec![1, 3, 3, 7, 7, 7]);
+        assert_eq!(analysis.count_of(7), 3);
+        assert_eq!(analysis.of_a_kind(2), Some(7));
+        assert_eq!(analysis.of_a_kind(4), None);
+        let sets: Vec<String> = analysis.sets().iter().map(|set| set.to_string()).collect();
+        assert_eq!(sets, vec!["3x7", "2x3"]);
+        assert_eq!(analysis.waste(), vec![1]);
+    }
+
+    #[test]
+    fn full_house_beats_three_of_a_kind() {
+        let analysis = FaceAnalysis::new(&[2, 5, 2, 5, 5]);
+        assert_eq!(analysis.full_house(), Some((5, 2)));
+        assert_eq!(analysis.best_pattern(), FacePattern::FullHouse { three: 5, two: 2 });
+    }
+
+    #[test]
+    fn straights() {
+        let large = FaceAnalysis::new(&[3, 1, 4, 5, 2]);
+        assert_eq!(large.longest_straight(), Some((5, 5)));
+        assert_eq!(large.best_pattern(), FacePattern::LargeStraight { length: 5, high: 5 });
+
+        let small = FaceAnalysis::new(&[6, 3, 4, 5, 5]);
+        assert!(small.has_straight(4));
+        assert!(!small.has_straight(5));
+        assert_eq!(small.best_pattern(), FacePattern::SmallStraight { high: 6 });
+    }
+
+    #[test]
+    fn ranking_order() {
+        let four = FacePattern::OfAKind { count: 4, face: 1 };
+        let full_house = FacePattern::FullHouse { three: 6, two: 5 };
+        let three = FacePattern::OfAKind { count: 3, face: 6 };
+        let two_pair = FacePattern::TwoPair { high: 6, low: 5 };
+        let pair = FacePattern::OfAKind { count: 2, face: 6 };
+        assert!(four.rank() > full_house.rank());
+        assert!(full_house.rank() > FacePattern::LargeStraight { length: 5, high: 6 }.rank());
+        assert!(FacePattern::SmallStraight { high: 6 }.rank() > three.rank());
+        assert!(three.rank() > two_pair.rank());
+        assert!(two_pair.rank() > pair.rank());
+        assert!(pair.rank() > FacePattern::AllDistinct { count: 5 }.rank());
+    }
+
+    #[test]
+    fn two_pair_and_nothing() {
+        let analysis = FaceAnalysis::new(&[4, 4, 1, 6, 6]);
+        assert!(analysis.patterns().contains(&FacePattern::TwoPair { high: 6, low: 4 }));
+        assert_eq!(analysis.best_pattern(), FacePattern::TwoPair { high: 6, low: 4 });
+        assert_eq!(FaceAnalysis::new(&[3]).best_pattern(), FacePattern::Nothing);
+        assert_eq!(FaceAnalysis::new(&[1, 3, 6]).best_pattern(), FacePattern::AllDistinct { count: 3 });
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+///Statistics for the rolls of one die size, over one die's history or several dice merged together.
+///Only rolls made at the provided size are counted, since dice can step up and down between rolls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DieStats {
+    faces: u32,
+    ///Only faces that were rolled are kept, so large dice don't need a count for every face.
+    histogram: BTreeMap<u32, usize>,
+    max_streak: usize,
+    min_streak: usize,
+    longest_without_max: usize,
+    ///None for merged statistics, since rolls of different dice don't form one sequence.
+    current_without_max: Option<usize>,
+}
+
+impl DieStats {
+    ///Works out the statistics of the rolls in a die's history that were made at faces. History is (face count, face), oldest first.
+    pub fn new(history: &[(u32, u32)], faces: u32) -> Self {
+        let mut stats = DieStats::empty(faces);
+        let mut max_run = 0;
+        let mut min_run = 0;
+        let mut without_max = 0;
+        for face in history
+            .iter()
+            .filter(|(roll_faces, face)| *roll_faces == faces && (1..=faces).contains(face))
+            .map(|(_, face)| *face)
+        {
+            *stats.histogram.entry(face).or_insert(0) += 1;
+
+            max_run = if face == faces { max_run + 1 } else { 0 };
+            min_run = if face == 1 { min_run + 1 } else { 0 };
+            without_max = if face == faces { 0 } else { without_max + 1 };
+            stats.max_streak = stats.max_streak.max(max_run);
+            stats.min_streak = stats.min_streak.max(min_run);
+            stats.longest_without_max = stats.longest_without_max.max(without_max);
+        }
+        stats.current_without_max = Some(without_max);
+        stats
+    }
+
+    ///Creates statistics with no rolls for dice with the provided number of faces.
+    pub fn empty(faces: u32) -> Self {
+        DieStats {
+            faces,
+            histogram: BTreeMap::new(),
+            max_streak: 0,
+            min_streak: 0,
+            longest_without_max: 0,
+            current_without_max: Some(0),
+        }
+    }
+
+    ///Adds another die's statistics of the same size, i.e. to get the statistics of every d6 in a tray.
+    ///Counts are added together. Streaks and runs can't cross between dice, so the longest of each is kept.
+    ///Merged statistics have no current run without the maximum face, as there's no single latest roll.
+    pub fn merge(&mut self, other: &DieStats) -> Result<(), String> {
+        if other.faces != self.faces {
+            return Err(format!(
+                "Can't merge statistics of a d{} into statistics of a d{}.",
+                other.faces, self.faces
+            ));
+        }
+        for (face, count) in other.histogram.iter() {
+            *self.histogram.entry(*face).or_insert(0) += count;
+        }
+        self.max_streak = self.max_streak.max(other.max_streak);
+        self.min_streak = self.min_streak.max(other.min_streak);
+        self.longest_without_max = self.longest_without_max.max(other.longest_without_max);
+        self.current_without_max = None;
+        Ok(())
+    }
+
+    pub fn get_faces(&self) -> u32 {
+        self.faces
+    }
+
+    ///Gets the number of rolls counted.
+    pub fn get_roll_count(&self) -> usize {
+        self.histogram.values().sum()
+    }
+
+    ///Gets the number of times each rolled face was rolled. Faces that were never rolled aren't included.
+    pub fn get_histogram(&self) -> &BTreeMap<u32, usize> {
+        &self.histogram
+    }
+
+    ///Gets the number of times a face was rolled.
+    pub fn get_count(&self, face: u32) -> usize {
+        self.histogram.get(&face).copied().unwrap_or(0)
+    }
+
+    ///Groups the faces into at most max_buckets ranges of equal width, the last possibly shorter, and counts the rolls in each.
+    ///Returns (lowest face, highest face, rolls) for every range, including ones with no rolls.
+    pub fn bucket_counts(&self, max_buckets: u32) -> Vec<(u32, u32, usize)> {
+        let width = self.faces.div_ceil(max_buckets.max(1)).max(1);
+        let mut buckets: Vec<(u32, u32, usize)> = (0..self.faces.div_ceil(width))
+            .map(|i| (i * width + 1, (i + 1).saturating_mul(width).min(self.faces), 0))
+            .collect();
+        for (face, count) in self.histogram.iter() {
+            buckets[((face - 1) / width) as usize].2 += count;
+        }
+        buckets
+    }
+
+    ///Gets the mean face rolled, or None if there are no rolls.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.get_roll_count();
+        if count == 0 {
+            return None;
+        }
+        let total: f64 = self.histogram.iter().map(|(face, rolled)| *face as f64 * *rolled as f64).sum();
+        Some(total / count as f64)
+    }
+
+    ///Gets the mean face of a fair die of this size, i.e. 3.5 for a d6.
+    pub fn expected_mean(&self) -> f64 {
+        (self.faces as f64 + 1.0) / 2.0
+    }
+
+    ///Gets the most maximum faces rolled in a row.
+    pub fn get_max_streak(&self) -> usize {
+        self.max_streak
+    }
+
+    ///Gets the most 1s rolled in a row.
+    pub fn get_min_streak(&self) -> usize {
+        self.min_streak
+    }
+
+    ///Gets the most rolls in a row without rolling the maximum face.
+    pub fn get_longest_without_max(&self) -> usize {
+        self.longest_without_max
+    }
+
+    ///Gets the number of rolls since the maximum face was last rolled, or None for merged statistics.
+    pub fn get_current_without_max(&self) -> Option<usize> {
+        self.current_without_max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_streaks_and_runs() {
+        let history: Vec<(u32, u32)> = [6, 6, 1, 1, 1, 3, 2, 6, 4].iter().map(|face| (6, *face)).collect();
+        let stats = DieStats::new(&history, 6);
+        assert_eq!(stats.get_roll_count(), 9);
+        assert_eq!(stats.get_count(6), 3);
+        assert_eq!(stats.get_count(5), 0);
+        assert_eq!(stats.get_max_streak(), 2);
+        assert_eq!(stats.get_min_streak(), 3);
+        assert_eq!(stats.get_longest_without_max(), 5);
+        assert_eq!(stats.get_current_without_max(), Some(1));
+        assert_eq!(stats.mean(), Some(30.0 / 9.0));
+        assert_eq!(stats.expected_mean(), 3.5);
+    }
+
+    #[test]
+    fn only_counts_rolls_at_the_size() {
+        let history = vec![(6, 6), (8, 8), (8, 2), (6, 1), (6, 9)];
+        let stats = DieStats::new(&history, 6);
+        assert_eq!(stats.get_roll_count(), 2);
+        assert_eq!(DieStats::new(&history, 8).get_roll_count(), 2);
+        assert_eq!(DieStats::new(&history, 20).mean(), None);
+    }
+
+    #[test]
+    fn large_dice_keep_only_rolled_faces() {
+        let stats = DieStats::new(&[(1_000_000, 5), (1_000_000, 999_999), (1_000_000, 5)], 1_000_000);
+        assert_eq!(stats.get_histogram().len(), 2);
+        assert_eq!(stats.get_count(5), 2);
+    }
+
+    #[test]
+    fn merge_adds_counts_and_drops_current_run() {
+        let mut total = DieStats::empty(6);
+        total.merge(&DieStats::new(&[(6, 1), (6, 2), (6, 3)], 6)).unwrap();
+        total.merge(&DieStats::new(&[(6, 6), (6, 2)], 6)).unwrap();
+        assert_eq!(total.get_roll_count(), 5);
+        assert_eq!(total.get_count(2), 2);
+        assert_eq!(total.get_longest_without_max(), 3);
+        assert_eq!(total.get_current_without_max(), None);
+        assert!(total.merge(&DieStats::empty(8)).is_err());
+    }
+
+    #[test]
+    fn buckets_group_faces_into_ranges() {
+        let stats = DieStats::new(&[(1_000_000, 1), (1_000_000, 50_000), (1_000_000, 50_001), (1_000_000, 1_000_000)], 1_000_000);
+        let buckets = stats.bucket_counts(20);
+        assert_eq!(buckets.len(), 20);
+        assert_eq!(buckets[0], (1, 50_000, 2));
+        assert_eq!(buckets[1], (50_001, 100_000, 1));
+        assert_eq!(buckets[19], (950_001, 1_000_000, 1));
+
+        let buckets = DieStats::new(&[(10, 10)], 10).bucket_counts(4);
+        assert_eq!(buckets, vec![(1, 3, 0), (4, 6, 0), (7, 9, 0), (10, 10, 1)]);
+
+        let buckets = DieStats::empty(u32::MAX).bucket_counts(20);
+        assert_eq!(buckets.last().map(|bucket| bucket.1), Some(u32::MAX));
+    }
+}
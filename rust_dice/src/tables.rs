@@ -1,69 +1,777 @@
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Clone)]
+///Loaders for importing tables from CSV, Markdown, JSON and YAML files.
+pub mod loaders;
+
+pub use loaders::{TableFormat, load_tables_from_dir, load_tables_from_file};
+
+///A single entry in a result table. The entry is selected by any face from low to high (inclusive).
+///The text can embed dice expressions ("2d4 goblins") which are rolled when the entry is selected,
+///and the entry can reference another table that is rolled on after this one ("roll on [[Treasure B]]").
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableEntry {
+    low: u32,
+    high: u32,
+    text: String,
+    #[serde(default)]
+    reference: Option<String>,
+}
+
+impl TableEntry {
+    ///Creates a new entry covering the faces from low to high. A table name in double brackets, i.e. "[[Treasure B]]", becomes a reference to that table.
+    pub fn new(low: u32, high: u32, text: &str) -> Self {
+        let (text, reference) = split_reference(text);
+        TableEntry {
+            low: low.min(high),
+            high: low.max(high),
+            text,
+            reference,
+        }
+    }
+
+    ///Gets the lowest face that selects this entry.
+    pub fn get_low(&self) -> u32 {
+        self.low
+    }
+
+    ///Gets the highest face that selects this entry.
+    pub fn get_high(&self) -> u32 {
+        self.high
+    }
+
+    ///Gets the entry text, without any table reference.
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    ///Gets the name of the table this entry rolls on next, if any.
+    pub fn get_reference(&self) -> Option<&str> {
+        self.reference.as_deref()
+    }
+
+    ///Returns the number of faces that select this entry, which is the entry's weight.
+    pub fn get_weight(&self) -> u32 {
+        self.high - self.low + 1
+    }
+
+    ///Gets the face range as a string i.e. "1-15", or "7" for single face entries.
+    pub fn get_range_string(&self) -> String {
+        if self.low == self.high {
+            self.low.to_string()
+        } else {
+            format!("{}-{}", self.low, self.high)
+        }
+    }
+}
+
+///The number of draws and reshuffles a table deck keeps in its history. Older events are dropped first.
+pub const DECK_HISTORY_LIMIT: usize = 1000;
+
+///The draw state of a table rolled without replacement. Entries that have been drawn can't come up again until the table is reshuffled.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TableDeck {
+    drawn: Vec<usize>,
+    auto_reshuffle: bool,
+    history: Vec<TableDeckEvent>,
+}
+
+impl TableDeck {
+    ///Returns true if the table reshuffles itself when a draw finds it exhausted.
+    pub fn is_auto_reshuffle(&self) -> bool {
+        self.auto_reshuffle
+    }
+
+    ///Returns the number of entries drawn since the last reshuffle.
+    pub fn get_drawn_count(&self) -> usize {
+        self.drawn.len()
+    }
+
+    ///Returns the last DECK_HISTORY_LIMIT draws and reshuffles made on the table, oldest first.
+    pub fn get_history(&self) -> &Vec<TableDeckEvent> {
+        &self.history
+    }
+
+    ///Adds an event to the history, dropping the oldest event once DECK_HISTORY_LIMIT is reached.
+    fn push_event(&mut self, event: TableDeckEvent) {
+        if self.history.len() >= DECK_HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push(event);
+    }
+}
+
+///An event in the draw history of a table deck.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum TableDeckEvent {
+    Drawn { face: u32, text: String },
+    Reshuffled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DiceResultTable {
     name: String,
-    table: HashMap<u32, String>,
+    entries: Vec<TableEntry>,
+    #[serde(default)]
+    deck: Option<TableDeck>,
 }
 
 impl DiceResultTable {
     ///Creates a new DiceResultTable from a Vector of Strings.
-    ///The Strings are moved into the the new table.
+    ///The Strings are moved into the the new table, each taking a single face starting at 1.
     pub fn new(name: String, table_results: Vec<String>) -> DiceResultTable {
-        let mut index = 1;
-        let table: HashMap<u32, String> = table_results
+        let entries = table_results
+            .iter()
+            .zip(1..)
+            .map(|(text, face)| TableEntry::new(face, face, text))
+            .collect();
+        Self {
+            name,
+            entries,
+            deck: None,
+        }
+    }
+
+    ///Creates a new table from weighted results. Each result takes as many consecutive faces as its weight, starting at 1.
+    ///i.e. weights of 3, 1 and 2 give the ranges 1-3, 4 and 5-6.
+    pub fn from_weighted(
+        name: String,
+        weighted_results: Vec<(u32, String)>,
+    ) -> Result<DiceResultTable, String> {
+        let mut entries = Vec::new();
+        let mut next_face: u32 = 1;
+        for (weight, text) in weighted_results {
+            if weight == 0 {
+                return Err(format!("Entry '{}' in table {} has a weight of 0.", text, name));
+            }
+            let high = next_face
+                .checked_add(weight - 1)
+                .ok_or(format!("Weights in table {} are too large.", name))?;
+            entries.push(TableEntry::new(next_face, high, &text));
+            next_face = high.saturating_add(1);
+        }
+        Self::from_entries(name, entries)
+    }
+
+    ///Creates a new table from ranged entries i.e. (1, 15, "Goblins"). Returns an error if any ranges overlap.
+    pub fn from_ranges(
+        name: String,
+        ranged_results: Vec<(u32, u32, String)>,
+    ) -> Result<DiceResultTable, String> {
+        let entries = ranged_results
             .into_iter()
-            .map(|s| {
-                let kvp = (index, s);
-                index += 1;
-                kvp
-            })
+            .map(|(low, high, text)| TableEntry::new(low, high, &text))
             .collect();
-        Self { name, table }
+        Self::from_entries(name, entries)
+    }
+
+    ///Creates a new table from entries, sorting them by face and checking that no ranges overlap.
+    pub fn from_entries(name: String, mut entries: Vec<TableEntry>) -> Result<DiceResultTable, String> {
+        if entries.is_empty() {
+            return Err(format!("Table {} has no entries.", name));
+        }
+        entries.sort_by_key(|entry| entry.low);
+        for pair in entries.windows(2) {
+            if pair[1].low <= pair[0].high {
+                return Err(format!(
+                    "Entries {} and {} in table {} overlap.",
+                    pair[0].get_range_string(),
+                    pair[1].get_range_string(),
+                    name
+                ));
+            }
+        }
+        Ok(Self {
+            name,
+            entries,
+            deck: None,
+        })
+    }
+
+    ///Looks up the result for the face. Values that fall out of the table range are wrapped around the range in order to always return a value.
+    ///Returns an error if the face lands in a gap between ranged entries.
+    pub fn lookup(&self, face: u32) -> Result<&str, String> {
+        Ok(self.lookup_entry(face)?.get_text())
     }
 
-    ///Looks up the result of the table. Values that fall out of the table range are "wraped" in order to always return a value.
-    pub fn lookup(&self, mut face: u32) -> Result<&str, String> {
-        let table_length: u32 = match self.table.len().try_into() {
-            Ok(len) => len,
-            Err(_) => {
-                return Err(format![
-                    "Table length is larger than the max supported by dice-tray. Are you sure you need a table this big?"
-                ]);
+    ///Looks up the entry for the face, with its range and any table reference. Faces are wrapped like lookup.
+    pub fn lookup_entry(&self, face: u32) -> Result<&TableEntry, String> {
+        Ok(&self.entries[self.lookup_index(face)?])
+    }
+
+    fn lookup_index(&self, face: u32) -> Result<usize, String> {
+        let (min, max) = match (self.entries.first(), self.entries.last()) {
+            (Some(first), Some(last)) => (first.low as u64, last.high as u64),
+            _ => return Err(format!("Table {} has no entries.", self.name)),
+        };
+
+        let span = max - min + 1;
+        let wrapped = (face as u64 + span - min % span) % span + min;
+
+        self.entries
+            .iter()
+            .position(|entry| entry.low as u64 <= wrapped && wrapped <= entry.high as u64)
+            .ok_or(format![
+                "No result found at {} in table {} ",
+                wrapped, self.name
+            ])
+    }
+
+    ///Draws an entry from the table. If no face is given an entry is picked by weight.
+    ///Tables in deck mode never return an entry twice before a reshuffle: a face landing on a drawn entry moves on to the next undrawn entry,
+    ///whose lowest face becomes the face drawn (as it does for faces wrapped around the table), and a rolled draw picks between the undrawn entries by weight. Other tables look up a given face.
+    pub fn draw<R: Rng + ?Sized>(&mut self, face: Option<u32>, rng: &mut R) -> Result<&TableEntry, String> {
+        let (_, index) = self.draw_index(face, rng)?;
+        Ok(&self.entries[index])
+    }
+
+    ///Draws an entry, returning the face used and the index of the entry drawn.
+    fn draw_index<R: Rng + ?Sized>(&mut self, face: Option<u32>, rng: &mut R) -> Result<(u32, usize), String> {
+        let Some(deck) = &self.deck else {
+            return match face {
+                Some(face) => Ok((face, self.lookup_index(face)?)),
+                None => self.pick_weighted(&(0..self.entries.len()).collect::<Vec<usize>>(), rng),
+            };
+        };
+
+        if deck.drawn.len() >= self.entries.len() {
+            if !deck.auto_reshuffle {
+                return Err(format!(
+                    "Table {} has been drawn out. Reshuffle it to draw again.",
+                    self.name
+                ));
             }
+            self.reshuffle()?;
+        }
+
+        let deck = self.deck.as_ref().ok_or("Table deck went missing.".to_string())?;
+        let undrawn: Vec<usize> = (0..self.entries.len())
+            .filter(|i| !deck.drawn.contains(i))
+            .collect();
+        let (face, index) = match face {
+            Some(face) => {
+                let looked_up = self.lookup_index(face)?;
+                let index = (0..self.entries.len())
+                    .map(|offset| (looked_up + offset) % self.entries.len())
+                    .find(|i| undrawn.contains(i))
+                    .unwrap_or(looked_up);
+                let entry = &self.entries[index];
+                if entry.low <= face && face <= entry.high {
+                    (face, index)
+                } else {
+                    (entry.low, index)
+                }
+            }
+            None => self.pick_weighted(&undrawn, rng)?,
         };
 
-        if face > table_length {
-            let leftovers = face - table_length;
-            face = leftovers;
+        let text = self.entries[index].text.clone();
+        if let Some(deck) = self.deck.as_mut() {
+            deck.drawn.push(index);
+            deck.push_event(TableDeckEvent::Drawn { face, text });
+        }
+        Ok((face, index))
+    }
+
+    ///Picks one of the entries at the indices by weight, returning a face within the picked entry and its index.
+    ///Picking by entry rather than rolling across the table's range means gaps between ranges are never rolled.
+    fn pick_weighted<R: Rng + ?Sized>(&self, indices: &[usize], rng: &mut R) -> Result<(u32, usize), String> {
+        let total_weight: u64 = indices.iter().map(|i| self.entries[*i].get_weight() as u64).sum();
+        if total_weight == 0 {
+            return Err(format!("Table {} has no entries to pick from.", self.name));
+        }
+        let mut pick = rng.random_range(0..total_weight);
+        for i in indices.iter() {
+            let entry = &self.entries[*i];
+            let weight = entry.get_weight() as u64;
+            if pick < weight {
+                return Ok((entry.low + pick as u32, *i));
+            }
+            pick -= weight;
         }
+        Err(format!("Failed to pick an entry in table {}.", self.name))
+    }
 
-        match self.table.get(&face) {
-            Some(s) => Ok(&s),
-            None => Err(format![
-                "No result found at {} in table {} ",
-                face, self.name
-            ]),
+    ///Puts the table into deck mode, where entries are drawn without replacement. Keeps the draw state if the table is already a deck.
+    pub fn enable_deck(&mut self, auto_reshuffle: bool) {
+        match self.deck.as_mut() {
+            Some(deck) => deck.auto_reshuffle = auto_reshuffle,
+            None => {
+                self.deck = Some(TableDeck {
+                    auto_reshuffle,
+                    ..Default::default()
+                })
+            }
         }
     }
 
+    ///Takes the table out of deck mode, discarding its draw state and history.
+    pub fn disable_deck(&mut self) {
+        self.deck = None;
+    }
+
+    ///Gets the draw state of the table, if it is in deck mode.
+    pub fn get_deck(&self) -> Option<&TableDeck> {
+        self.deck.as_ref()
+    }
+
+    ///Returns true if the entry at the index has been drawn since the last reshuffle.
+    pub fn is_drawn(&self, index: usize) -> bool {
+        self.deck.as_ref().is_some_and(|deck| deck.drawn.contains(&index))
+    }
+
+    ///Returns every drawn entry to the table. Returns an error if the table isn't in deck mode.
+    pub fn reshuffle(&mut self) -> Result<(), String> {
+        let deck = self
+            .deck
+            .as_mut()
+            .ok_or(format!("Table {} is not a deck, so it can't be reshuffled.", self.name))?;
+        deck.drawn.clear();
+        deck.push_event(TableDeckEvent::Reshuffled);
+        Ok(())
+    }
+
     ///Returns the name of the table.
     pub fn get_name(&self) -> &str {
         &self.name
     }
+
+    ///Returns the entries of the table, sorted by face.
+    pub fn get_entries(&self) -> &Vec<TableEntry> {
+        &self.entries
+    }
+
+    ///Returns the lowest face on the table.
+    pub fn get_min_face(&self) -> u32 {
+        self.entries.first().map_or(1, |entry| entry.low)
+    }
+
+    ///Returns the highest face on the table, which is the size of die the table is built for.
+    pub fn get_max_face(&self) -> u32 {
+        self.entries.last().map_or(1, |entry| entry.high)
+    }
+}
+
+///A dice expression rolled while looking up a table entry i.e. the 2d4 in "2d4 goblins".
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableDiceRoll {
+    pub expression: String,
+    pub faces: Vec<u32>,
+    pub total: i64,
+}
+
+///The structured result of rolling on a table. Lists the face used, the entry text with its dice expressions replaced by their totals,
+///every dice expression rolled, and the result of any table this entry referenced.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableRoll {
+    pub table: String,
+    pub face: u32,
+    pub text: String,
+    pub dice_rolls: Vec<TableDiceRoll>,
+    pub nested: Option<Box<TableRoll>>,
+}
+
+impl TableRoll {
+    ///Returns this roll and every nested roll, outermost first.
+    pub fn chain(&self) -> Vec<&TableRoll> {
+        let mut chain = vec![self];
+        let mut current = self;
+        while let Some(nested) = &current.nested {
+            chain.push(nested);
+            current = nested;
+        }
+        chain
+    }
+}
+
+///A named collection of tables that entries can reference. Names are matched exactly first, then ignoring case.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TableSet {
+    tables: BTreeMap<String, DiceResultTable>,
+}
+
+impl TableSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Adds a table to the set, returning any table it replaced.
+    pub fn insert(&mut self, table: DiceResultTable) -> Option<DiceResultTable> {
+        self.tables.insert(table.get_name().to_string(), table)
+    }
+
+    ///Gets a table by name.
+    pub fn get(&self, name: &str) -> Option<&DiceResultTable> {
+        self.tables.get(name).or_else(|| {
+            self.tables
+                .values()
+                .find(|table| table.get_name().eq_ignore_ascii_case(name))
+        })
+    }
+
+    ///Gets a mutable table by name.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut DiceResultTable> {
+        let key = self.get(name)?.get_name().to_string();
+        self.tables.get_mut(&key)
+    }
+
+    ///Rolls on the named table, following any references into the other tables in the set and rolling any embedded dice expressions.
+    ///If a face is provided it is used for the named table instead of rolling. Nested tables are always rolled.
+    ///Tables in deck mode are drawn from, so the set should be saved afterwards to keep their draw state.
+    ///Returns an error if the references loop back on a table that is already being rolled.
+    pub fn roll_on<R: Rng + ?Sized>(
+        &mut self,
+        name: &str,
+        face: Option<u32>,
+        rng: &mut R,
+    ) -> Result<TableRoll, String> {
+        self.roll_on_path(name, face, rng, &mut Vec::new())
+    }
+
+    fn roll_on_path<R: Rng + ?Sized>(
+        &mut self,
+        name: &str,
+        face: Option<u32>,
+        rng: &mut R,
+        path: &mut Vec<String>,
+    ) -> Result<TableRoll, String> {
+        let table = self
+            .get_mut(name)
+            .ok_or(format!("No table named {} found.", name))?;
+        let table_name = table.get_name().to_string();
+        if path.contains(&table_name) {
+            path.push(table_name);
+            return Err(format!("Table references form a cycle: {}", path.join(" -> ")));
+        }
+
+        let (face, index) = table.draw_index(face, rng)?;
+        let entry = table.entries[index].clone();
+
+        let mut dice_rolls = Vec::new();
+        let text = entry
+            .text
+            .split(' ')
+            .map(|word| match roll_dice_word(word, rng) {
+                Some((dice_roll, rolled_word)) => {
+                    dice_rolls.push(dice_roll);
+                    rolled_word
+                }
+                None => word.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        path.push(table_name.clone());
+        let nested = match &entry.reference {
+            Some(reference) => {
+                if self.get(reference).is_none() {
+                    return Err(format!(
+                        "Table {} references table {}, which doesn't exist.",
+                        table_name, reference
+                    ));
+                }
+                Some(Box::new(self.roll_on_path(reference, None, rng, path)?))
+            }
+            None => None,
+        };
+        path.pop();
+
+        Ok(TableRoll {
+            table: table_name,
+            face,
+            text,
+            dice_rolls,
+            nested,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DiceResultTable> {
+        self.tables.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut DiceResultTable> {
+        self.tables.values_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+///Finds the first table reference in double brackets, i.e. "roll on [[Treasure B]]", returning the text with the brackets removed and the table name.
+///References must be bracketed so prose like "they roll on the floor" is never mistaken for a table.
+fn split_reference(text: &str) -> (String, Option<String>) {
+    if let Some(start) = text.find("[[")
+        && let Some(length) = text[start + 2..].find("]]")
+    {
+        let name = text[start + 2..start + 2 + length].trim();
+        if !name.is_empty() {
+            let rest = format!("{}{}{}", &text[..start], name, &text[start + 2 + length + 2..]);
+            return (rest.trim().to_string(), Some(name.to_string()));
+        }
+    }
+    (text.trim().to_string(), None)
+}
+
+///The most dice a single dice expression can roll. Larger counts in table text are left as plain text.
+pub const MAX_EXPRESSION_DICE: u32 = 1000;
+
+///Parses a dice expression like "2d4", "d6" or "3d6+2" into (count, faces, modifier).
+///Returns None if there are no dice, no faces, or more than MAX_EXPRESSION_DICE dice.
+pub fn parse_dice_expression(expression: &str) -> Option<(u32, u32, i64)> {
+    let lower = expression.to_ascii_lowercase();
+    let (count, rest) = lower.split_once('d')?;
+    let count = if count.is_empty() {
+        1
+    } else {
+        count.parse::<u32>().ok()?
+    };
+    let (faces, modifier) = match rest.find(['+', '-']) {
+        Some(i) => (&rest[..i], rest[i..].parse::<i64>().ok()?),
+        None => (rest, 0),
+    };
+    let faces = faces.parse::<u32>().ok()?;
+    if count == 0 || faces == 0 || count > MAX_EXPRESSION_DICE {
+        return None;
+    }
+    Some((count, faces, modifier))
+}
+
+///Rolls a word of entry text if it is a dice expression, keeping any surrounding punctuation.
+fn roll_dice_word<R: Rng + ?Sized>(word: &str, rng: &mut R) -> Option<(TableDiceRoll, String)> {
+    let start = word.find(|c: char| c.is_ascii_alphanumeric())?;
+    let end = word.rfind(|c: char| c.is_ascii_alphanumeric())? + 1;
+    let expression = &word[start..end];
+    let (count, faces, modifier) = parse_dice_expression(expression)?;
+
+    let rolled_faces: Vec<u32> = (0..count).map(|_| rng.random_range(1..=faces)).collect();
+    let total = rolled_faces
+        .iter()
+        .fold(modifier, |total, face| total.saturating_add(*face as i64));
+    let rolled_word = format!("{}{}{}", &word[..start], total, &word[end..]);
+    Some((
+        TableDiceRoll {
+            expression: expression.to_string(),
+            faces: rolled_faces,
+            total,
+        },
+        rolled_word,
+    ))
 }
 
 pub fn implement_test_table() -> DiceResultTable {
-    DiceResultTable {
-        name: "test".to_string(),
-        table: HashMap::from([
-            (1, "Critical Fail".to_string()),
-            (2, "Fail".to_string()),
-            (3, "Neutral".to_string()),
-            (4, "Success".to_string()),
-            (5, "Critical Success".to_string()),
-            (6, "Unexpected Result".to_string()),
-        ]),
+    DiceResultTable::new(
+        "test".to_string(),
+        vec![
+            "Critical Fail".to_string(),
+            "Fail".to_string(),
+            "Neutral".to_string(),
+            "Success".to_string(),
+            "Critical Success".to_string(),
+            "Unexpected Result".to_string(),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+
+    fn ranged(name: &str, entries: &[(u32, u32, &str)]) -> DiceResultTable {
+        DiceResultTable::from_ranges(
+            name.to_string(),
+            entries.iter().map(|(low, high, text)| (*low, *high, text.to_string())).collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bracketed_names_are_references() {
+        let entry = TableEntry::new(1, 1, "Gold, roll on [[Treasure B]]");
+        assert_eq!(entry.get_text(), "Gold, roll on Treasure B");
+        assert_eq!(entry.get_reference(), Some("Treasure B"));
+    }
+
+    #[test]
+    fn prose_is_not_a_reference() {
+        let entry = TableEntry::new(1, 1, "The goblins roll on the floor laughing");
+        assert_eq!(entry.get_text(), "The goblins roll on the floor laughing");
+        assert_eq!(entry.get_reference(), None);
+    }
+
+    #[test]
+    fn lookup_finds_ranges_and_wraps() {
+        let table = ranged("Encounters", &[(1, 3, "Goblins"), (4, 5, "Wolves"), (6, 6, "Dragon")]);
+        assert_eq!(table.lookup(2).unwrap(), "Goblins");
+        assert_eq!(table.lookup(5).unwrap(), "Wolves");
+        assert_eq!(table.lookup(6).unwrap(), "Dragon");
+        assert_eq!(table.lookup(7).unwrap(), "Goblins");
+        assert_eq!(table.lookup_entry(4).unwrap().get_range_string(), "4-5");
+    }
+
+    #[test]
+    fn gaps_and_overlaps_are_errors() {
+        let table = ranged("Gappy", &[(1, 2, "Low"), (5, 6, "High")]);
+        assert!(table.lookup(3).is_err());
+        let overlapping = DiceResultTable::from_ranges(
+            "Overlap".to_string(),
+            vec![(1, 3, "A".to_string()), (3, 4, "B".to_string())],
+        );
+        assert!(overlapping.is_err());
+    }
+
+    #[test]
+    fn weights_take_consecutive_faces() {
+        let table = DiceResultTable::from_weighted(
+            "Weighted".to_string(),
+            vec![(3, "A".to_string()), (1, "B".to_string()), (2, "C".to_string())],
+        )
+        .unwrap();
+        let ranges: Vec<String> = table.get_entries().iter().map(|e| e.get_range_string()).collect();
+        assert_eq!(ranges, vec!["1-3", "4", "5-6"]);
+        assert!(DiceResultTable::from_weighted("Zero".to_string(), vec![(0, "A".to_string())]).is_err());
+    }
+
+    #[test]
+    fn references_are_rolled_and_cycles_are_errors() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut tables = TableSet::new();
+        tables.insert(ranged("Loot", &[(1, 1, "Coins, roll on [[Gems]]")]));
+        tables.insert(ranged("Gems", &[(1, 1, "A ruby")]));
+        let roll = tables.roll_on("Loot", None, &mut rng).unwrap();
+        let chain: Vec<&str> = roll.chain().iter().map(|roll| roll.table.as_str()).collect();
+        assert_eq!(chain, vec!["Loot", "Gems"]);
+
+        tables.insert(ranged("Gems", &[(1, 1, "Back to [[Loot]]")]));
+        assert!(tables.roll_on("Loot", None, &mut rng).is_err());
+    }
+
+    #[test]
+    fn dice_expressions_are_parsed_and_capped() {
+        assert_eq!(parse_dice_expression("2d4"), Some((2, 4, 0)));
+        assert_eq!(parse_dice_expression("d6"), Some((1, 6, 0)));
+        assert_eq!(parse_dice_expression("3d6-2"), Some((3, 6, -2)));
+        assert_eq!(parse_dice_expression("d0"), None);
+        assert_eq!(parse_dice_expression("4000000000d6"), None);
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut tables = TableSet::new();
+        tables.insert(ranged("Horde", &[(1, 1, "4000000000d6 goblins")]));
+        let roll = tables.roll_on("Horde", None, &mut rng).unwrap();
+        assert_eq!(roll.text, "4000000000d6 goblins");
+        assert!(roll.dice_rolls.is_empty());
+    }
+
+    fn deck(auto_reshuffle: bool) -> DiceResultTable {
+        let mut table = ranged("Deck", &[(1, 2, "A"), (3, 3, "B"), (4, 6, "C")]);
+        table.enable_deck(auto_reshuffle);
+        table
+    }
+
+    #[test]
+    fn deck_draws_every_entry_once() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = deck(false);
+        let mut drawn: Vec<String> = (0..3)
+            .map(|_| {
+                let (face, index) = table.draw_index(None, &mut rng).unwrap();
+                let entry = &table.get_entries()[index];
+                assert!(entry.get_low() <= face && face <= entry.get_high());
+                entry.get_text().to_string()
+            })
+            .collect();
+        drawn.sort();
+        assert_eq!(drawn, vec!["A", "B", "C"]);
+        assert_eq!(table.get_deck().unwrap().get_drawn_count(), 3);
+    }
+
+    #[test]
+    fn drawn_out_deck_is_an_error_without_auto_reshuffle() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = deck(false);
+        for _ in 0..3 {
+            table.draw(None, &mut rng).unwrap();
+        }
+        assert!(table.draw(None, &mut rng).unwrap_err().contains("drawn out"));
+        table.reshuffle().unwrap();
+        assert!(table.draw(None, &mut rng).is_ok());
+    }
+
+    #[test]
+    fn drawn_out_deck_reshuffles_itself() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = deck(true);
+        for _ in 0..4 {
+            table.draw(None, &mut rng).unwrap();
+        }
+        let deck = table.get_deck().unwrap();
+        assert_eq!(deck.get_drawn_count(), 1);
+        assert!(matches!(deck.get_history()[3], TableDeckEvent::Reshuffled));
+    }
+
+    #[test]
+    fn face_on_a_drawn_entry_moves_to_the_next_entry() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = deck(false);
+        assert_eq!(table.draw_index(Some(2), &mut rng).unwrap(), (2, 0));
+        assert_eq!(table.draw_index(Some(1), &mut rng).unwrap(), (3, 1));
+        assert_eq!(table.draw_index(Some(3), &mut rng).unwrap(), (4, 2));
+        match &table.get_deck().unwrap().get_history()[1] {
+            TableDeckEvent::Drawn { face, text } => assert_eq!((*face, text.as_str()), (3, "B")),
+            TableDeckEvent::Reshuffled => panic!("expected a draw"),
+        }
+    }
+
+    #[test]
+    fn deck_history_is_capped() {
+        let mut table = deck(false);
+        for _ in 0..DECK_HISTORY_LIMIT + 5 {
+            table.reshuffle().unwrap();
+        }
+        assert_eq!(table.get_deck().unwrap().get_history().len(), DECK_HISTORY_LIMIT);
+    }
+
+    #[test]
+    fn drawn_state_survives_save_and_reload() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = deck(false);
+        let (_, index) = table.draw_index(None, &mut rng).unwrap();
+        let json = serde_json::to_string(&table).unwrap();
+        let mut reloaded = loaders::parse_json_table("file", &json).unwrap();
+        assert!(reloaded.is_drawn(index));
+        assert_eq!(reloaded.get_deck().unwrap().get_history().len(), 1);
+        for _ in 0..2 {
+            let (_, next) = reloaded.draw_index(None, &mut rng).unwrap();
+            assert_ne!(next, index);
+        }
+        assert!(reloaded.draw(None, &mut rng).is_err());
+    }
+
+    #[test]
+    fn rolls_never_land_in_gaps() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = ranged("Gappy", &[(1, 2, "Low"), (50, 51, "High")]);
+        for _ in 0..200 {
+            let (face, index) = table.draw_index(None, &mut rng).unwrap();
+            let entry = &table.get_entries()[index];
+            assert!(entry.get_low() <= face && face <= entry.get_high());
+        }
+    }
+
+    #[test]
+    fn rolls_pick_entries_by_weight() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut table = DiceResultTable::from_weighted(
+            "Weighted".to_string(),
+            vec![(99, "Common".to_string()), (1, "Rare".to_string())],
+        )
+        .unwrap();
+        let rare = (0..1000)
+            .filter(|_| table.draw(None, &mut rng).unwrap().get_text() == "Rare")
+            .count();
+        assert!(rare < 50, "Rare came up {} times in 1000 rolls", rare);
     }
 }
@@ -0,0 +1,447 @@
+use super::{DiceResultTable, TableEntry, TableSet};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///File formats result tables can be loaded from. The format is picked from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Markdown,
+    Json,
+    Yaml,
+}
+
+impl TableFormat {
+    ///Returns the table format matching the extension of the path, or None if the file isn't a table file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(TableFormat::Csv),
+            "md" | "markdown" => Some(TableFormat::Markdown),
+            "json" => Some(TableFormat::Json),
+            "yaml" | "yml" => Some(TableFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+///Loads the tables in a CSV, Markdown, JSON or YAML file. Tables without a name in the file are named after the file.
+///Markdown files can hold several tables, each named after the heading above it.
+pub fn load_tables_from_file(path: &Path) -> Result<Vec<DiceResultTable>, String> {
+    let format = TableFormat::from_path(path).ok_or(format!(
+        "{} is not a table file. Table files must end in .csv, .md, .json, .yaml or .yml",
+        path.display()
+    ))?;
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read table file {}: {}", path.display(), e))?;
+    let default_name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("table");
+
+    let tables = match format {
+        TableFormat::Csv => vec![parse_csv_table(default_name, &content)?],
+        TableFormat::Markdown => parse_markdown_tables(default_name, &content)?,
+        TableFormat::Json => vec![parse_json_table(default_name, &content)?],
+        TableFormat::Yaml => vec![parse_yaml_table(default_name, &content)?],
+    };
+    Ok(tables)
+}
+
+///Loads every table file in a directory into a table set. Files that aren't table files are ignored.
+///Returns an error if two tables have the same name, so which one is rolled never depends on the order of the files.
+pub fn load_tables_from_dir(dir: &Path) -> Result<TableSet, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read table directory {}: {}", dir.display(), e))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && TableFormat::from_path(path).is_some())
+        .collect();
+    paths.sort();
+
+    let mut tables = TableSet::new();
+    let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for path in paths {
+        for table in load_tables_from_file(&path)? {
+            if let Some(source) = sources.get(table.get_name()) {
+                return Err(format!(
+                    "Table '{}' is defined in both {} and {}. Rename or remove one of them.",
+                    table.get_name(),
+                    source.display(),
+                    path.display()
+                ));
+            }
+            sources.insert(table.get_name().to_string(), path.clone());
+            tables.insert(table);
+        }
+    }
+    Ok(tables)
+}
+
+///Parses a CSV table. The first column holds the roll (i.e. "1-3", "01–15" or "7") and the remaining columns the result.
+///A header row is detected automatically, and a first column titled "weight" is read as weights instead of rolls.
+///If the first column isn't rolls at all, each row becomes a single face entry in order. The first row of such a list is only read as a header
+///when its first cell is a roll or weight title, i.e. "d6", "Roll" or "Weight", so no entries of a plain list are lost.
+pub fn parse_csv_table(name: &str, content: &str) -> Result<DiceResultTable, String> {
+    let mut rows: Vec<Vec<String>> = content
+        .lines()
+        .map(split_csv_line)
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .collect();
+
+    let first_is_key = rows.first().is_some_and(|row| is_keyed_row(row));
+    let rest_keyed = rows.iter().skip(1).all(|row| is_keyed_row(row));
+    let none_keyed = !rows.iter().any(|row| is_keyed_row(row));
+    let first_is_title = rows.first().is_some_and(|row| is_roll_title(&row[0]));
+    let header = if rows.len() > 1 && !first_is_key && (rest_keyed || (none_keyed && first_is_title)) {
+        Some(rows.remove(0))
+    } else {
+        None
+    };
+    table_from_rows(name, header, rows)
+}
+
+///Parses every pipe table in a Markdown document. Each table is named after the closest heading above it,
+///or after the file if there is no heading. The row above the separator line is the header, and the other rows are read the same way as CSV rows.
+pub fn parse_markdown_tables(default_name: &str, content: &str) -> Result<Vec<DiceResultTable>, String> {
+    let mut tables = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut header: Option<Vec<String>> = None;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+
+    let mut finish_table = |header: &mut Option<Vec<String>>, rows: &mut Vec<Vec<String>>, heading: &Option<String>| {
+        if rows.is_empty() {
+            *header = None;
+            return Ok(());
+        }
+        let name = heading.clone().unwrap_or_else(|| default_name.to_string());
+        tables.push(table_from_rows(&name, header.take(), std::mem::take(rows))?);
+        Ok::<(), String>(())
+    };
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('|') {
+            let cells: Vec<String> = line
+                .trim_matches('|')
+                .split('|')
+                .map(|cell| cell.trim().to_string())
+                .collect();
+            let is_separator = cells
+                .iter()
+                .all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':' | ' ')));
+            if is_separator {
+                if rows.len() == 1 && header.is_none() {
+                    header = rows.pop();
+                }
+            } else {
+                rows.push(cells);
+            }
+        } else {
+            finish_table(&mut header, &mut rows, &heading)?;
+            if let Some(title) = line.strip_prefix('#') {
+                heading = Some(title.trim_start_matches('#').trim().to_string());
+            }
+        }
+    }
+    finish_table(&mut header, &mut rows, &heading)?;
+
+    if tables.is_empty() {
+        return Err(format!("No pipe tables found in {}.", default_name));
+    }
+    Ok(tables)
+}
+
+///Parses a JSON table. See TableSource for the layouts that are accepted.
+pub fn parse_json_table(default_name: &str, content: &str) -> Result<DiceResultTable, String> {
+    let source: TableSource = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse table {}: {}", default_name, e))?;
+    source.into_table(default_name)
+}
+
+///Parses a YAML table. See TableSource for the layouts that are accepted.
+pub fn parse_yaml_table(default_name: &str, content: &str) -> Result<DiceResultTable, String> {
+    let source: TableSource = serde_yaml_ng::from_str(content)
+        .map_err(|e| format!("Failed to parse table {}: {}", default_name, e))?;
+    source.into_table(default_name)
+}
+
+///Parses a roll key like "7", "0-5", "1-3", "01–15" or "96-00" into a face range. "00" is read as 100 (and "000" as 1000), as on percentile tables, while "0" is just 0.
+pub fn parse_range_key(key: &str) -> Option<(u32, u32)> {
+    let key = key.trim().replace(['–', '—'], "-");
+    let (low, high) = match key.split_once('-') {
+        Some((low, high)) => (parse_face(low)?, parse_face(high)?),
+        None => {
+            let face = parse_face(&key)?;
+            (face, face)
+        }
+    };
+    if low > high {
+        return None;
+    }
+    Some((low, high))
+}
+
+fn parse_face(face: &str) -> Option<u32> {
+    let face = face.trim();
+    if face.is_empty() || !face.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if face.len() > 1 && face.chars().all(|c| c == '0') {
+        return 10u32.checked_pow(face.len() as u32);
+    }
+    face.parse::<u32>().ok()
+}
+
+///Returns true if the cell is a title for a column of rolls or weights, i.e. "d6", "2d6", "d%", "Roll" or "Weight".
+fn is_roll_title(cell: &str) -> bool {
+    let cell = cell.trim().to_ascii_lowercase();
+    if matches!(cell.as_str(), "roll" | "rolls" | "die" | "dice" | "weight" | "d%") {
+        return true;
+    }
+    match cell.split_once('d') {
+        Some((count, faces)) => {
+            count.chars().all(|c| c.is_ascii_digit()) && !faces.is_empty() && faces.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+///Returns true if the row has a roll key in its first column and a result after it.
+fn is_keyed_row(row: &[String]) -> bool {
+    row.len() > 1 && parse_range_key(&row[0]).is_some()
+}
+
+///Builds a table from its header and rows of cells, detecting whether the first column holds rolls, weights or nothing at all.
+fn table_from_rows(name: &str, header: Option<Vec<String>>, rows: Vec<Vec<String>>) -> Result<DiceResultTable, String> {
+    let first_cell = |row: &Vec<String>| row.first().cloned().unwrap_or_default();
+    let weighted = header
+        .as_ref()
+        .is_some_and(|header| first_cell(header).trim().eq_ignore_ascii_case("weight"));
+
+    let keyed = !rows.is_empty() && rows.iter().all(|row| is_keyed_row(row));
+    if !keyed {
+        let results = rows
+            .into_iter()
+            .map(|row| join_cells(&row))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<String>>();
+        if results.is_empty() {
+            return Err(format!("Table {} has no entries.", name));
+        }
+        return Ok(DiceResultTable::new(name.to_string(), results));
+    }
+
+    if weighted {
+        let weighted_results = rows
+            .iter()
+            .map(|row| {
+                let weight = first_cell(row).trim().parse::<u32>().map_err(|_| {
+                    format!("Weight '{}' in table {} is not a whole number.", first_cell(row), name)
+                })?;
+                Ok((weight, join_cells(&row[1..])))
+            })
+            .collect::<Result<Vec<(u32, String)>, String>>()?;
+        DiceResultTable::from_weighted(name.to_string(), weighted_results)
+    } else {
+        let ranged_results = rows
+            .iter()
+            .filter_map(|row| {
+                parse_range_key(&first_cell(row)).map(|(low, high)| (low, high, join_cells(&row[1..])))
+            })
+            .collect();
+        DiceResultTable::from_ranges(name.to_string(), ranged_results)
+    }
+}
+
+///Joins the non empty cells of a row into a single result.
+fn join_cells(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.trim())
+        .filter(|cell| !cell.is_empty())
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+///Splits a CSV line into cells. Cells can be quoted to hold commas, and quotes inside quoted cells are doubled ("").
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+///The layouts accepted for JSON and YAML tables:
+///a saved DiceResultTable, an object with a name and entries, or the entries on their own.
+///Entries are either a map of roll keys to results ({"1-3": "Goblins"}), a list of weighted results ([{"weight": 3, "text": "Goblins"}]),
+///or a plain list of results that take one face each.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TableSource {
+    Table(DiceResultTable),
+    Named { name: String, entries: EntrySource },
+    Entries(EntrySource),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EntrySource {
+    Keyed(BTreeMap<String, String>),
+    Weighted(Vec<WeightedEntry>),
+    Listed(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct WeightedEntry {
+    weight: u32,
+    text: String,
+}
+
+impl TableSource {
+    fn into_table(self, default_name: &str) -> Result<DiceResultTable, String> {
+        let (name, entries) = match self {
+            TableSource::Table(table) => {
+                //Saved tables keep their deck state, but are still checked for overlapping entries.
+                let deck = table.deck;
+                let mut checked = DiceResultTable::from_entries(table.name, table.entries)?;
+                checked.deck = deck;
+                return Ok(checked);
+            }
+            TableSource::Named { name, entries } => (name, entries),
+            TableSource::Entries(entries) => (default_name.to_string(), entries),
+        };
+
+        match entries {
+            EntrySource::Keyed(keyed) => {
+                let entries = keyed
+                    .iter()
+                    .map(|(key, text)| {
+                        let (low, high) = parse_range_key(key)
+                            .ok_or(format!("'{}' in table {} is not a roll or range of rolls.", key, name))?;
+                        Ok(TableEntry::new(low, high, text))
+                    })
+                    .collect::<Result<Vec<TableEntry>, String>>()?;
+                DiceResultTable::from_entries(name, entries)
+            }
+            EntrySource::Weighted(weighted) => DiceResultTable::from_weighted(
+                name,
+                weighted.into_iter().map(|entry| (entry.weight, entry.text)).collect(),
+            ),
+            EntrySource::Listed(listed) => Ok(DiceResultTable::new(name, listed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(table: &DiceResultTable) -> Vec<(String, &str)> {
+        table
+            .get_entries()
+            .iter()
+            .map(|entry| (entry.get_range_string(), entry.get_text()))
+            .collect()
+    }
+
+    #[test]
+    fn range_keys() {
+        assert_eq!(parse_range_key("7"), Some((7, 7)));
+        assert_eq!(parse_range_key("1-3"), Some((1, 3)));
+        assert_eq!(parse_range_key("01–15"), Some((1, 15)));
+        assert_eq!(parse_range_key("96-00"), Some((96, 100)));
+        assert_eq!(parse_range_key("0-5"), Some((0, 5)));
+        assert_eq!(parse_range_key("000"), Some((1000, 1000)));
+        assert_eq!(parse_range_key("5-1"), None);
+        assert_eq!(parse_range_key("Roll"), None);
+    }
+
+    #[test]
+    fn csv_with_header_and_ranges() {
+        let table = parse_csv_table("Loot", "d6,Result\n1-3,Copper\n4-5,\"Silver, lots\"\n6,Gold\n").unwrap();
+        assert_eq!(
+            texts(&table),
+            vec![("1-3".to_string(), "Copper"), ("4-5".to_string(), "Silver, lots"), ("6".to_string(), "Gold")]
+        );
+    }
+
+    #[test]
+    fn csv_weights() {
+        let table = parse_csv_table("Weighted", "Weight,Result\n3,Goblins\n1,Dragon\n").unwrap();
+        assert_eq!(texts(&table), vec![("1-3".to_string(), "Goblins"), ("4".to_string(), "Dragon")]);
+    }
+
+    #[test]
+    fn headerless_list_keeps_every_row() {
+        let table = parse_csv_table("Monsters", "Goblin\nOrc\nTroll\n").unwrap();
+        assert_eq!(
+            texts(&table),
+            vec![("1".to_string(), "Goblin"), ("2".to_string(), "Orc"), ("3".to_string(), "Troll")]
+        );
+    }
+
+    #[test]
+    fn list_with_roll_title_drops_header() {
+        let table = parse_csv_table("Encounters", "d6,Encounter\nGoblins\nWolves\n").unwrap();
+        assert_eq!(texts(&table), vec![("1".to_string(), "Goblins"), ("2".to_string(), "Wolves")]);
+    }
+
+    #[test]
+    fn markdown_tables_are_named_by_heading() {
+        let content = "# Encounters\n\n| Encounter |\n|---|\n| Goblins |\n| Wolves |\n\n## Loot\n| d4 | Loot |\n| :-: | --- |\n| 1-3 | Copper |\n| 4 | Gold |\n";
+        let tables = parse_markdown_tables("file", content).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].get_name(), "Encounters");
+        assert_eq!(texts(&tables[0]), vec![("1".to_string(), "Goblins"), ("2".to_string(), "Wolves")]);
+        assert_eq!(tables[1].get_name(), "Loot");
+        assert_eq!(texts(&tables[1]), vec![("1-3".to_string(), "Copper"), ("4".to_string(), "Gold")]);
+    }
+
+    #[test]
+    fn duplicate_tables_in_a_directory_are_errors() {
+        let dir = std::env::temp_dir().join(format!("dicetray-tables-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.json"), r#"{"name": "Loot", "entries": ["Copper"]}"#).unwrap();
+        fs::write(dir.join("b.yaml"), "name: Gems\nentries:\n  - Ruby\n").unwrap();
+        assert!(load_tables_from_dir(&dir).is_ok());
+
+        fs::write(dir.join("c.md"), "# Loot\n| Loot |\n|---|\n| Gold |\n").unwrap();
+        let result = load_tables_from_dir(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        let error = result.err().unwrap();
+        assert!(error.contains("a.json") && error.contains("c.md"), "{}", error);
+    }
+
+    #[test]
+    fn json_and_yaml_layouts() {
+        let keyed = parse_json_table("file", r#"{"name": "Loot", "entries": {"1-3": "Copper", "4": "Gold"}}"#).unwrap();
+        assert_eq!(keyed.get_name(), "Loot");
+        assert_eq!(texts(&keyed), vec![("1-3".to_string(), "Copper"), ("4".to_string(), "Gold")]);
+
+        let weighted = parse_yaml_table("Weighted", "- weight: 2\n  text: Goblins\n- weight: 1\n  text: Dragon\n").unwrap();
+        assert_eq!(texts(&weighted), vec![("1-2".to_string(), "Goblins"), ("3".to_string(), "Dragon")]);
+
+        let listed = parse_yaml_table("Listed", "- Goblins\n- Wolves\n").unwrap();
+        assert_eq!(texts(&listed), vec![("1".to_string(), "Goblins"), ("2".to_string(), "Wolves")]);
+
+        assert!(parse_json_table("Bad", r#"{"x": "Copper"}"#).is_err());
+    }
+}
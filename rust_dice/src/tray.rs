@@ -1,34 +1,44 @@
-use super::dice::{Die, DieResultType};
+use super::commitment::SeedCommitment;
+use super::dice::{Die, DieMode, DieResultType, StepDirection, StepLadder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
-///Result type for a dice tray.
+///Result type for a dice tray. Pattern reports the best pattern in the faces of the tray's dice, see the patterns module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum TrayResultType {
+    #[default]
     Sum,
     Best,
     Worst,
+    Pattern,
 }
 
-impl TrayResultType {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for TrayResultType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TrayResultType::Sum => "Tray sum".to_string(),
-            TrayResultType::Best => "High roll in tray".to_string(),
-            TrayResultType::Worst => "Worst roll in tray".to_string(),
+            TrayResultType::Sum => write!(f, "Tray sum"),
+            TrayResultType::Best => write!(f, "High roll in tray"),
+            TrayResultType::Worst => write!(f, "Worst roll in tray"),
+            TrayResultType::Pattern => write!(f, "Best pattern in tray"),
         }
     }
 }
 
+///The result of a dice tray. Overflow is returned when the tray's arithmetic would not fit in an i64.
 pub enum TrayResult {
-    Number(u32),
+    Number(i64),
     String(String),
+    Overflow,
     None,
 }
 
-impl TrayResult {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for TrayResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TrayResult::Number(n) => n.to_string(),
-            TrayResult::String(s) => s.clone(),
-            TrayResult::None => "None".to_string(),
+            TrayResult::Number(n) => write!(f, "{}", n),
+            TrayResult::String(s) => write!(f, "{}", s),
+            TrayResult::Overflow => write!(f, "Overflow"),
+            TrayResult::None => write!(f, "None"),
         }
     }
 }
@@ -38,11 +48,11 @@ pub enum TraySortType {
     Face,
 }
 
-impl TraySortType {
-    ///Returns a string explaining how the tray was sorted. Used by the cli logger.
-    pub fn to_string(&self) -> String {
+///Explains how the tray was sorted. Used by the cli logger.
+impl fmt::Display for TraySortType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TraySortType::Face => "Sorting tray by die face.".to_string(),
+            TraySortType::Face => write!(f, "Sorting tray by die face."),
         }
     }
 }
@@ -74,21 +84,59 @@ pub trait Tray {
     ///Clears the tray of all dice.
     fn clear(&mut self);
 
-    ///Applies the provided result type then rolls all the dice in the tray.
+    ///Applies the provided result type then rolls all the dice in the tray that can be rolled, skipping held and exhausted dice.
     fn roll_all(&mut self, result_type: Option<DieResultType>);
 
     ///Rolls the dice at the provided index, using the provided result type.
-    ///Throws an error if no die is present at the index.
+    ///Throws an error if no die is present at the index or the die can't be rolled.
     fn roll_at(&mut self, index: usize, result_type: Option<DieResultType>) -> Result<(), String>;
 
-    /// Rolls all dice in the tray with the specified label.
-    /// Throws an error if no die has the label provided.
+    /// Rolls all dice in the tray with the specified label that can be rolled.
+    /// Throws an error if no die has the label provided, or none of the dice with the label can be rolled.
     fn roll_by_label(
         &mut self,
         label: &str,
         result_type: Option<DieResultType>,
     ) -> Result<(), String>;
 
+    ///Holds or releases the die at the provided index. Throws an error if no die is present at the index.
+    fn set_held_at(&mut self, index: usize, held: bool) -> Result<(), String>;
+
+    ///Holds or releases all dice in the tray with the specified label. Throws an error if no die has the label provided.
+    fn set_held_by_label(&mut self, label: &str, held: bool) -> Result<(), String>;
+
+    ///Steps the die at the provided index up or down the ladder, returning its new face count.
+    ///Throws an error if no die is present at the index.
+    fn step_at(&mut self, index: usize, direction: StepDirection, ladder: &StepLadder) -> Result<u32, String>;
+
+    ///Steps all dice in the tray with the specified label up or down the ladder. Throws an error if no die has the label provided.
+    fn step_by_label(&mut self, label: &str, direction: StepDirection, ladder: &StepLadder) -> Result<(), String>;
+
+    ///Sets the mode of the die at the provided index. Throws an error if no die is present at the index.
+    fn set_mode_at(&mut self, index: usize, mode: DieMode) -> Result<(), String>;
+
+    ///Sets the mode of all dice in the tray with the specified label. Throws an error if no die has the label provided.
+    fn set_mode_by_label(&mut self, label: &str, mode: DieMode) -> Result<(), String>;
+
+    ///Records a face by hand on the die at the provided index. Throws an error if no die is present at the index or the face isn't on the die.
+    fn record_face_at(&mut self, index: usize, face: u32) -> Result<(), String>;
+
+    ///Records a face by hand on all dice in the tray with the specified label. Throws an error if no die has the label provided.
+    fn record_face_by_label(&mut self, label: &str, face: u32) -> Result<(), String>;
+
+    ///Turns the die at the provided index by amount faces. Throws an error if no die is present at the index.
+    fn count_at(&mut self, index: usize, amount: i64) -> Result<(), String>;
+
+    ///Turns all dice in the tray with the specified label by amount faces. Throws an error if no die has the label provided.
+    fn count_by_label(&mut self, label: &str, amount: i64) -> Result<(), String>;
+
+    ///Commits the die at the provided index to a new RNG seed and returns the commitment hash.
+    ///Throws an error if no die is present at the index or the die is already committed.
+    fn commit_seed_at(&mut self, index: usize) -> Result<String, String>;
+
+    ///Reveals the seed commitment of the die at the provided index. Throws an error if no die is present at the index or the die isn't committed.
+    fn reveal_seed_at(&mut self, index: usize) -> Result<SeedCommitment, String>;
+
     ///Reorganizes the dice tray based on the sort type provided.
     fn sort(&mut self, sort_by: TraySortType);
 
@@ -98,8 +146,17 @@ pub trait Tray {
     /// Gets the result type of the tray
     fn get_result_type(&self) -> &TrayResultType;
 
+    /// Sets the result type of the tray, changing how get_result combines the dice.
+    fn set_result_type(&mut self, result_type: TrayResultType);
+
     /// Gets the current tray result as a TrayResult enum.
     fn get_result(&self) -> TrayResult;
 
+    /// Gets the flat modifier applied to numeric tray results, i.e. the +5 in "1d20+5".
+    fn get_modifier(&self) -> i64;
+
+    /// Sets the flat modifier applied to numeric tray results.
+    fn set_modifier(&mut self, modifier: i64);
+
     fn get_summary(&self) -> String;
 }
@@ -0,0 +1,451 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::patterns::FaceAnalysis;
+
+///Number of dice rolled in a game of Yahtzee.
+pub const YAHTZEE_DICE: u32 = 5;
+///Number of rolls a player gets each turn.
+pub const ROLLS_PER_TURN: u8 = 3;
+///Upper section total needed for the upper bonus.
+pub const UPPER_BONUS_THRESHOLD: u32 = 63;
+///Points awarded for reaching the upper bonus threshold.
+pub const UPPER_BONUS: u32 = 35;
+///Points awarded for each Yahtzee after the first, if the Yahtzee box was scored 50.
+pub const YAHTZEE_BONUS: u32 = 100;
+
+///The thirteen boxes on a Yahtzee scorecard. Ones to Sixes make up the upper section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ScoreCategory {
+    Ones,
+    Twos,
+    Threes,
+    Fours,
+    Fives,
+    Sixes,
+    ThreeOfAKind,
+    FourOfAKind,
+    FullHouse,
+    SmallStraight,
+    LargeStraight,
+    Yahtzee,
+    Chance,
+}
+
+impl ScoreCategory {
+    ///Every category, in scorecard order.
+    pub const ALL: [ScoreCategory; 13] = [
+        ScoreCategory::Ones,
+        ScoreCategory::Twos,
+        ScoreCategory::Threes,
+        ScoreCategory::Fours,
+        ScoreCategory::Fives,
+        ScoreCategory::Sixes,
+        ScoreCategory::ThreeOfAKind,
+        ScoreCategory::FourOfAKind,
+        ScoreCategory::FullHouse,
+        ScoreCategory::SmallStraight,
+        ScoreCategory::LargeStraight,
+        ScoreCategory::Yahtzee,
+        ScoreCategory::Chance,
+    ];
+
+    ///Returns true for the upper section categories, Ones to Sixes.
+    pub fn is_upper(&self) -> bool {
+        self.upper_face().is_some()
+    }
+
+    ///Gets the face counted by an upper section category.
+    fn upper_face(&self) -> Option<u32> {
+        match self {
+            ScoreCategory::Ones => Some(1),
+            ScoreCategory::Twos => Some(2),
+            ScoreCategory::Threes => Some(3),
+            ScoreCategory::Fours => Some(4),
+            ScoreCategory::Fives => Some(5),
+            ScoreCategory::Sixes => Some(6),
+            _ => None,
+        }
+    }
+
+    ///Finds a category by name, ignoring case, spaces, dashes and underscores i.e. "full house", "3-of-a-kind", "sm straight", "6s".
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        let key: String = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let category = match key.as_str() {
+            "ones" | "1s" | "aces" => ScoreCategory::Ones,
+            "twos" | "2s" => ScoreCategory::Twos,
+            "threes" | "3s" => ScoreCategory::Threes,
+            "fours" | "4s" => ScoreCategory::Fours,
+            "fives" | "5s" => ScoreCategory::Fives,
+            "sixes" | "6s" => ScoreCategory::Sixes,
+            "threeofakind" | "3ofakind" | "3k" => ScoreCategory::ThreeOfAKind,
+            "fourofakind" | "4ofakind" | "4k" => ScoreCategory::FourOfAKind,
+            "fullhouse" | "fh" => ScoreCategory::FullHouse,
+            "smallstraight" | "smstraight" | "ss" => ScoreCategory::SmallStraight,
+            "largestraight" | "lgstraight" | "ls" => ScoreCategory::LargeStraight,
+            "yahtzee" => ScoreCategory::Yahtzee,
+            "chance" => ScoreCategory::Chance,
+            _ => return Err(format!("Unknown Yahtzee category {}.", name)),
+        };
+        Ok(category)
+    }
+
+    ///Scores the faces in this category. Faces that don't fit the category score 0.
+    pub fn score(&self, faces: &[u32]) -> u32 {
+        let analysis = FaceAnalysis::new(faces);
+        let total: u32 = faces.iter().sum();
+        match self {
+            ScoreCategory::ThreeOfAKind if analysis.of_a_kind(3).is_some() => total,
+            ScoreCategory::FourOfAKind if analysis.of_a_kind(4).is_some() => total,
+            ScoreCategory::FullHouse if analysis.full_house().is_some() => 25,
+            ScoreCategory::SmallStraight if analysis.has_straight(4) => 30,
+            ScoreCategory::LargeStraight if analysis.has_straight(5) => 40,
+            ScoreCategory::Yahtzee if is_yahtzee(faces) => 50,
+            ScoreCategory::Chance => total,
+            category => match category.upper_face() {
+                Some(face) => face * analysis.count_of(face) as u32,
+                None => 0,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ScoreCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoreCategory::Ones => write!(f, "Ones"),
+            ScoreCategory::Twos => write!(f, "Twos"),
+            ScoreCategory::Threes => write!(f, "Threes"),
+            ScoreCategory::Fours => write!(f, "Fours"),
+            ScoreCategory::Fives => write!(f, "Fives"),
+            ScoreCategory::Sixes => write!(f, "Sixes"),
+            ScoreCategory::ThreeOfAKind => write!(f, "Three of a Kind"),
+            ScoreCategory::FourOfAKind => write!(f, "Four of a Kind"),
+            ScoreCategory::FullHouse => write!(f, "Full House"),
+            ScoreCategory::SmallStraight => write!(f, "Small Straight"),
+            ScoreCategory::LargeStraight => write!(f, "Large Straight"),
+            ScoreCategory::Yahtzee => write!(f, "Yahtzee"),
+            ScoreCategory::Chance => write!(f, "Chance"),
+        }
+    }
+}
+
+///Returns true if five dice all show the same face.
+pub fn is_yahtzee(faces: &[u32]) -> bool {
+    faces.len() == YAHTZEE_DICE as usize && faces.iter().all(|face| *face == faces[0])
+}
+
+///A player's Yahtzee scorecard. Each category can be scored once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scorecard {
+    player: String,
+    scores: BTreeMap<ScoreCategory, u32>,
+    yahtzee_bonus: u32,
+}
+
+impl Scorecard {
+    ///Creates an empty scorecard for the player.
+    pub fn new(player: String) -> Self {
+        Scorecard {
+            player,
+            scores: BTreeMap::new(),
+            yahtzee_bonus: 0,
+        }
+    }
+
+    pub fn get_player(&self) -> &str {
+        &self.player
+    }
+
+    ///Gets the score in the category, or None if it hasn't been scored yet.
+    pub fn get_score(&self, category: ScoreCategory) -> Option<u32> {
+        self.scores.get(&category).copied()
+    }
+
+    ///Returns true if the category has been scored.
+    pub fn is_filled(&self, category: ScoreCategory) -> bool {
+        self.scores.contains_key(&category)
+    }
+
+    ///Returns true once every category has been scored.
+    pub fn is_complete(&self) -> bool {
+        self.scores.len() == ScoreCategory::ALL.len()
+    }
+
+    ///Gets the categories that haven't been scored yet, in scorecard order.
+    pub fn open_categories(&self) -> Vec<ScoreCategory> {
+        ScoreCategory::ALL
+            .iter()
+            .filter(|category| !self.is_filled(**category))
+            .copied()
+            .collect()
+    }
+
+    ///Gets the total of the upper section, without the bonus.
+    pub fn upper_subtotal(&self) -> u32 {
+        self.scores
+            .iter()
+            .filter(|(category, _)| category.is_upper())
+            .map(|(_, score)| score)
+            .sum()
+    }
+
+    ///Gets the upper section bonus: 35 points once the upper section totals 63 or more.
+    pub fn upper_bonus(&self) -> u32 {
+        if self.upper_subtotal() >= UPPER_BONUS_THRESHOLD {
+            UPPER_BONUS
+        } else {
+            0
+        }
+    }
+
+    ///Gets the Yahtzee bonus points earned from extra Yahtzees.
+    pub fn get_yahtzee_bonus(&self) -> u32 {
+        self.yahtzee_bonus
+    }
+
+    ///Gets the total of the lower section, including Yahtzee bonuses.
+    pub fn lower_total(&self) -> u32 {
+        let lower: u32 = self
+            .scores
+            .iter()
+            .filter(|(category, _)| !category.is_upper())
+            .map(|(_, score)| score)
+            .sum();
+        lower + self.yahtzee_bonus
+    }
+
+    ///Gets the grand total: both sections and every bonus.
+    pub fn total(&self) -> u32 {
+        self.upper_subtotal() + self.upper_bonus() + self.lower_total()
+    }
+
+    ///Works out what the faces would score in the category on this scorecard, without scoring them.
+    ///Once the Yahtzee box is filled, another Yahtzee is a joker: it must go in its upper box if that is open,
+    ///otherwise it scores in full in Full House and the straights. Returns an error if the category can't be scored.
+    pub fn points_for(&self, category: ScoreCategory, faces: &[u32]) -> Result<u32, String> {
+        if self.is_filled(category) {
+            return Err(format!("{} has already scored {}.", self.player, category));
+        }
+        if !is_yahtzee(faces) || !self.is_filled(ScoreCategory::Yahtzee) {
+            return Ok(category.score(faces));
+        }
+
+        let upper = ScoreCategory::ALL
+            .iter()
+            .copied()
+            .find(|upper| upper.upper_face() == Some(faces[0]));
+        match upper {
+            Some(upper) if !self.is_filled(upper) && upper != category => Err(format!(
+                "A joker Yahtzee of {}s must be scored in {} while it is open.",
+                faces[0], upper
+            )),
+            _ => Ok(match category {
+                ScoreCategory::FullHouse => 25,
+                ScoreCategory::SmallStraight => 30,
+                ScoreCategory::LargeStraight => 40,
+                category => category.score(faces),
+            }),
+        }
+    }
+
+    ///Scores the faces in the category, returning the points scored. Jokers follow points_for.
+    ///Rolling another Yahtzee after scoring 50 in the Yahtzee box earns a 100 point bonus on top.
+    pub fn score(&mut self, category: ScoreCategory, faces: &[u32]) -> Result<u32, String> {
+        let points = self.points_for(category, faces)?;
+        if is_yahtzee(faces) && self.get_score(ScoreCategory::Yahtzee) == Some(50) {
+            self.yahtzee_bonus += YAHTZEE_BONUS;
+        }
+        self.scores.insert(category, points);
+        Ok(points)
+    }
+}
+
+///A game of Yahtzee. The dice themselves live in a tray, the game keeps the scorecards and whose turn it is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YahtzeeGame {
+    tray_id: String,
+    scorecards: Vec<Scorecard>,
+    current_player: usize,
+    rolls_used: u8,
+}
+
+impl YahtzeeGame {
+    ///Creates a new game for the players, played with the dice in the tray.
+    pub fn new(tray_id: String, players: Vec<String>) -> Result<Self, String> {
+        if players.is_empty() {
+            return Err("A game of Yahtzee needs at least one player.".to_string());
+        }
+        Ok(YahtzeeGame {
+            tray_id,
+            scorecards: players.into_iter().map(Scorecard::new).collect(),
+            current_player: 0,
+            rolls_used: 0,
+        })
+    }
+
+    ///Gets the ID of the tray the game is played in.
+    pub fn get_tray_id(&self) -> &str {
+        &self.tray_id
+    }
+
+    ///Gets every player's scorecard, in turn order.
+    pub fn get_scorecards(&self) -> &Vec<Scorecard> {
+        &self.scorecards
+    }
+
+    ///Gets the scorecard of the player whose turn it is.
+    pub fn get_current_scorecard(&self) -> &Scorecard {
+        &self.scorecards[self.current_player]
+    }
+
+    ///Gets the number of rolls used so far this turn.
+    pub fn get_rolls_used(&self) -> u8 {
+        self.rolls_used
+    }
+
+    ///Returns true if this turn's first roll is next, so every die should be rolled.
+    pub fn is_first_roll(&self) -> bool {
+        self.rolls_used == 0
+    }
+
+    ///Returns true once every player has filled their scorecard.
+    pub fn is_over(&self) -> bool {
+        self.scorecards.iter().all(|card| card.is_complete())
+    }
+
+    ///Uses one of the current player's rolls. Errors once all three rolls have been used, or if the game is over.
+    pub fn use_roll(&mut self) -> Result<(), String> {
+        if self.is_over() {
+            return Err("The game is over. Start a new game to play again.".to_string());
+        }
+        if self.rolls_used >= ROLLS_PER_TURN {
+            return Err(format!(
+                "{} has used all {} rolls. Score the dice to end the turn.",
+                self.get_current_scorecard().get_player(),
+                ROLLS_PER_TURN
+            ));
+        }
+        self.rolls_used += 1;
+        Ok(())
+    }
+
+    ///Scores the faces for the current player and passes the turn on. Returns the points scored.
+    pub fn score(&mut self, category: ScoreCategory, faces: &[u32]) -> Result<u32, String> {
+        if self.rolls_used == 0 {
+            return Err("Roll the dice before scoring.".to_string());
+        }
+        if faces.len() != YAHTZEE_DICE as usize {
+            return Err(format!("Yahtzee is scored with {} dice, found {}.", YAHTZEE_DICE, faces.len()));
+        }
+        let points = self.scorecards[self.current_player].score(category, faces)?;
+        self.rolls_used = 0;
+        self.current_player = (self.current_player + 1) % self.scorecards.len();
+        Ok(points)
+    }
+
+    ///Suggests a score for the faces in every category the current player can score them in, best first.
+    pub fn suggestions(&self, faces: &[u32]) -> Vec<(ScoreCategory, u32)> {
+        let scorecard = self.get_current_scorecard();
+        let mut suggestions: Vec<(ScoreCategory, u32)> = scorecard
+            .open_categories()
+            .into_iter()
+            .filter_map(|category| scorecard.points_for(category, faces).ok().map(|points| (category, points)))
+            .collect();
+        suggestions.sort_by_key(|(_, points)| std::cmp::Reverse(*points));
+        suggestions
+    }
+
+    ///Gets the scorecards with the highest total.
+    pub fn leaders(&self) -> Vec<&Scorecard> {
+        let best = self.scorecards.iter().map(|card| card.total()).max().unwrap_or(0);
+        self.scorecards.iter().filter(|card| card.total() == best).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_scores() {
+        assert_eq!(ScoreCategory::Threes.score(&[3, 3, 1, 3, 6]), 9);
+        assert_eq!(ScoreCategory::ThreeOfAKind.score(&[3, 3, 1, 3, 6]), 16);
+        assert_eq!(ScoreCategory::FourOfAKind.score(&[3, 3, 1, 3, 6]), 0);
+        assert_eq!(ScoreCategory::FullHouse.score(&[2, 5, 2, 5, 5]), 25);
+        assert_eq!(ScoreCategory::SmallStraight.score(&[1, 3, 4, 2, 6]), 30);
+        assert_eq!(ScoreCategory::LargeStraight.score(&[1, 3, 4, 2, 6]), 0);
+        assert_eq!(ScoreCategory::LargeStraight.score(&[6, 3, 4, 2, 5]), 40);
+        assert_eq!(ScoreCategory::Yahtzee.score(&[4, 4, 4, 4, 4]), 50);
+        assert_eq!(ScoreCategory::Chance.score(&[1, 2, 3, 4, 6]), 16);
+    }
+
+    #[test]
+    fn category_names() {
+        assert_eq!(ScoreCategory::from_name("full house"), Ok(ScoreCategory::FullHouse));
+        assert_eq!(ScoreCategory::from_name("3-of-a-kind"), Ok(ScoreCategory::ThreeOfAKind));
+        assert_eq!(ScoreCategory::from_name("6s"), Ok(ScoreCategory::Sixes));
+        assert!(ScoreCategory::from_name("sevens").is_err());
+    }
+
+    #[test]
+    fn upper_bonus() {
+        let mut card = Scorecard::new("Alex".to_string());
+        for (category, face) in [
+            (ScoreCategory::Fours, 4),
+            (ScoreCategory::Fives, 5),
+            (ScoreCategory::Sixes, 6),
+        ] {
+            card.score(category, &[face, face, face, face, 1]).unwrap();
+        }
+        assert_eq!(card.upper_subtotal(), 60);
+        assert_eq!(card.upper_bonus(), 0);
+        card.score(ScoreCategory::Threes, &[3, 1, 1, 2, 2]).unwrap();
+        assert_eq!(card.upper_subtotal(), 63);
+        assert_eq!(card.upper_bonus(), UPPER_BONUS);
+        assert!(card.score(ScoreCategory::Threes, &[3, 3, 3, 3, 3]).is_err());
+    }
+
+    #[test]
+    fn yahtzee_bonus_and_joker() {
+        let mut card = Scorecard::new("Sam".to_string());
+        card.score(ScoreCategory::Yahtzee, &[2, 2, 2, 2, 2]).unwrap();
+
+        //The matching upper box is open, so the joker must go there.
+        assert!(card.score(ScoreCategory::FullHouse, &[5, 5, 5, 5, 5]).is_err());
+        assert_eq!(card.score(ScoreCategory::Fives, &[5, 5, 5, 5, 5]), Ok(25));
+        assert_eq!(card.get_yahtzee_bonus(), YAHTZEE_BONUS);
+
+        //With Fives filled, another Yahtzee of 5s scores in full in the lower section.
+        assert_eq!(card.points_for(ScoreCategory::LargeStraight, &[5, 5, 5, 5, 5]), Ok(40));
+        assert_eq!(card.score(ScoreCategory::FullHouse, &[5, 5, 5, 5, 5]), Ok(25));
+        assert_eq!(card.get_yahtzee_bonus(), 2 * YAHTZEE_BONUS);
+        assert_eq!(card.lower_total(), 50 + 25 + 2 * YAHTZEE_BONUS);
+    }
+
+    #[test]
+    fn no_bonus_after_a_scratched_yahtzee() {
+        let mut card = Scorecard::new("Sam".to_string());
+        card.score(ScoreCategory::Yahtzee, &[1, 2, 3, 4, 6]).unwrap();
+        card.score(ScoreCategory::Sixes, &[6, 6, 6, 6, 6]).unwrap();
+        assert_eq!(card.get_yahtzee_bonus(), 0);
+    }
+
+    #[test]
+    fn turns_use_three_rolls() {
+        let mut game = YahtzeeGame::new("yahtzee".to_string(), vec!["Alex".to_string(), "Sam".to_string()]).unwrap();
+        assert!(game.score(ScoreCategory::Chance, &[1, 2, 3, 4, 5]).is_err());
+        for _ in 0..ROLLS_PER_TURN {
+            game.use_roll().unwrap();
+        }
+        assert!(game.use_roll().is_err());
+        assert_eq!(game.score(ScoreCategory::Chance, &[1, 2, 3, 4, 5]), Ok(15));
+        assert_eq!(game.get_current_scorecard().get_player(), "Sam");
+        assert!(game.is_first_roll());
+    }
+}